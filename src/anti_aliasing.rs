@@ -1,4 +1,7 @@
-mod graphics;
+use sdl2::rect::Point;
+
+use crate::clip;
+use crate::raster::{self, DrawCommand, PixelBuffer, Primitive};
 
 fn ipart(x: f32) -> i32 {
     x.floor() as i32
@@ -16,11 +19,7 @@ fn rfpart(x: f32) -> f32 {
     1.0 - fpart(x)
 }
 
-/*
-    Cool, but wont be really using this.
-    Anti-aliasing will fuck up the flood fill
-*/
-pub fn draw_wu_line(p_start: Point, p_end: Point, canvas: &mut Canvas<Window>) {
+pub fn plot_wu_line(p_start: Point, p_end: Point, plot: &mut impl FnMut(i32, i32, f32)) {
     let steep = i32::abs(p_end.y - p_start.y) > i32::abs(p_end.x - p_start.x);
 
     let (x1, y1, x2, y2) = if steep {
@@ -43,48 +42,129 @@ pub fn draw_wu_line(p_start: Point, p_end: Point, canvas: &mut Canvas<Window>) {
         gradient = 1.0;
     }
 
-    let mut x_end = round(x1 as f32);
-    let y_end = y1 as f32 + gradient * (x_end - x1) as f32;
-    let xpxl1 = x_end as i32;
-    let mut intery = y_end + gradient;
+    let xpxl1 = round(x1 as f32);
+    let xpxl2 = round(x2 as f32);
+    let mut intery = y1 as f32;
+
+    for x in xpxl1..=xpxl2 {
+        if steep {
+            plot(ipart(intery), x, rfpart(intery));
+            plot(ipart(intery) + 1, x, fpart(intery));
+        } else {
+            plot(x, ipart(intery), rfpart(intery));
+            plot(x, ipart(intery) + 1, fpart(intery));
+        }
+        intery += gradient;
+    }
+}
 
-    x_end = round(x2 as f32);
-    let xpxl2 = x_end as i32;
+// Coverage goes through PixelBuffer::blend, so it is applied in linear
+// light when the target blends that way and thin lines keep their weight.
+pub fn draw_wu_line(p_start: Point, p_end: Point, color: u32, target: &mut PixelBuffer) {
+    let bounds = match target.clip_bounds() {
+        Some(bounds) => bounds,
+        None => return,
+    };
 
-    if steep {
-        for x in xpxl1..xpxl2 {
-            graphics::draw_point(ipart(intery), x, rfpart(intery), canvas);
-            graphics::draw_point(ipart(intery) - 1, x, fpart(intery), canvas);
-            intery += gradient;
+    plot_wu_line(p_start, p_end, &mut |x, y, coverage| {
+        if !bounds.contains_point((x, y)) || coverage <= 0.0 {
+            return;
         }
-    } else {
-        for x in (xpxl1 + 1)..xpxl2 {
-            graphics::draw_point(x, ipart(intery), rfpart(intery), canvas);
-            graphics::draw_point(x, ipart(intery) - 1, fpart(intery), canvas);
-            intery += gradient;
-        }
-    }
+
+        let blended = target.blend(x, y, color, (coverage.min(1.0) * 255.0).round() as u8);
+        target.set(x, y, blended);
+    });
 }
 
-pub fn draw_wu_rect(p_1: Point, p_2: Point, canvas: &mut Canvas<Window>) {
-    draw_wu_line(p_1, Point::new(p_1.x, p_2.y), canvas);
-    draw_wu_line(p_1, Point::new(p_2.x, p_1.y), canvas);
+fn draw_wu_polyline(points: &[(f32, f32)], color: u32, target: &mut PixelBuffer) {
+    let to_point = |p: &(f32, f32)| Point::new(p.0.round() as i32, p.1.round() as i32);
+    for pair in points.windows(2) {
+        draw_wu_line(to_point(&pair[0]), to_point(&pair[1]), color, target);
+    }
+}
 
-    draw_wu_line(Point::new(p_1.x, p_2.y), p_2, canvas);
-    draw_wu_line(p_2, Point::new(p_2.x, p_1.y), canvas);
+// Circles are drawn as polygons with a segment every few pixels.
+fn circle_points(center: Point, radius: i32) -> Vec<(f32, f32)> {
+    let r = radius.abs() as f32;
+    let segments = ((std::f32::consts::TAU * r / 4.0) as usize).clamp(16, 2000);
+
+    (0..=segments)
+        .map(|i| {
+            let angle = std::f32::consts::TAU * i as f32 / segments as f32;
+            (center.x as f32 + r * angle.cos(), center.y as f32 + r * angle.sin())
+        })
+        .collect()
+}
 
-    let x_is_reversed = p_2.x < p_1.x;
-    let y_is_reversed = p_2.y < p_1.y;
+// raster::render with anti-aliased strokes. Points and filled polygons
+// have no edges to smooth and go through the plain path.
+pub fn render_smooth(commands: &[DrawCommand], target: &mut PixelBuffer) {
+    for command in commands {
+        let color = command.color;
+        match &command.primitive {
+            Primitive::Line(p_1, p_2) => draw_wu_line(*p_1, *p_2, color, target),
+            Primitive::Circle(center, radius) => {
+                draw_wu_polyline(&circle_points(*center, *radius), color, target)
+            }
+            Primitive::QuadraticBezier([p_1, p_2, p_3]) => draw_wu_polyline(
+                &clip::flatten_quadratic_bezier(*p_1, *p_2, *p_3),
+                color,
+                target,
+            ),
+            Primitive::CubicBezier([p_1, p_2, p_3, p_4]) => draw_wu_polyline(
+                &clip::flatten_cubic_bezier(*p_1, *p_2, *p_3, *p_4),
+                color,
+                target,
+            ),
+            Primitive::Point(_) | Primitive::FilledPolygon(_) => {
+                raster::render_plain(std::slice::from_ref(command), target)
+            }
+        }
+    }
+}
 
-    let (start_x, end_x) = if x_is_reversed {
-        (p_2.x, p_1.x)
-    } else {
-        (p_1.x, p_2.x)
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamma::Blending;
+
+    #[test]
+    fn shallow_line_has_partial_coverage() {
+        let mut target = PixelBuffer::new(20, 10, 0xFFFFFFFF);
+        let commands = [DrawCommand::new(
+            Primitive::Line(Point::new(0, 2), Point::new(19, 7)),
+            0xFF000000,
+        )];
+        render_smooth(&commands, &mut target);
+
+        let column: Vec<u8> = (0..10).map(|y| (target.get(10, y) & 0xFF) as u8).collect();
+        assert!(column.iter().any(|&c| c > 0 && c < 255));
+        // The two pixels sharing a column add up to one full pixel of ink,
+        // counted in light rather than in encoded values.
+        let ink: f32 = column
+            .iter()
+            .map(|&c| 1.0 - Blending::Linear.decode(c))
+            .sum();
+        assert!((ink - 1.0).abs() < 0.05, "ink {}", ink);
+    }
 
-    let (start_y, end_y) = if y_is_reversed {
-        (p_2.y, p_1.y)
-    } else {
-        (p_1.y, p_2.y)
-    };
+    #[test]
+    fn curves_and_circles_stay_inside_the_buffer() {
+        let mut target = PixelBuffer::new(30, 30, 0xFFFFFFFF);
+        let commands = [
+            DrawCommand::new(Primitive::Circle(Point::new(15, 15), 40), 0xFF000000),
+            DrawCommand::new(
+                Primitive::CubicBezier([
+                    Point::new(-10, 5),
+                    Point::new(10, 50),
+                    Point::new(20, -20),
+                    Point::new(40, 25),
+                ]),
+                0xFF000000,
+            ),
+        ];
+        render_smooth(&commands, &mut target);
+        assert_eq!(target.pixels.len(), 30 * 30 * 4);
+        assert!((0..30).any(|x| (0..30).any(|y| target.get(x, y) != 0xFFFFFFFF)));
+    }
 }
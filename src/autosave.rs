@@ -0,0 +1,200 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::panic;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::hud;
+use crate::raster::PixelBuffer;
+use crate::scene::Scene;
+use crate::scene_file;
+
+pub const RECOVERY_PATH: &str = "recovery.scene";
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+// Written before the recovery files are swapped in, so a crash half way
+// through a save leaves the previous ones intact.
+const TEMP_PATH: &str = "recovery.tmp";
+const IMAGE_TEMP_PATH: &str = "recovery.png.tmp";
+// Exists for as long as a session runs, finding it at startup means the
+// last session never got to remove it.
+const LOCK_PATH: &str = "rust-sdl.lock";
+
+pub struct Autosave {
+    pub interval: Duration,
+    last_save: Instant,
+    saved_revision: u64,
+}
+
+impl Autosave {
+    pub fn new(interval: Duration) -> Self {
+        Autosave {
+            interval,
+            last_save: Instant::now(),
+            saved_revision: 0,
+        }
+    }
+
+    // Called every frame, only writes once the interval has passed and the
+    // history says something changed since the last save.
+    pub fn tick(&mut self, scene: &Scene, backdrop: &PixelBuffer, revision: u64) {
+        if self.last_save.elapsed() < self.interval || revision == self.saved_revision {
+            return;
+        }
+
+        self.last_save = Instant::now();
+        match write_recovery(Path::new(""), scene, backdrop) {
+            Ok(()) => self.saved_revision = revision,
+            Err(e) => hud::status(format!("Autosave failed: {}", e)),
+        }
+    }
+
+    // After loading a file the history starts over, there is nothing new to
+    // save until the next edit.
+    pub fn reset(&mut self, revision: u64) {
+        self.last_save = Instant::now();
+        self.saved_revision = revision;
+    }
+}
+
+// The files live in `dir`, the working directory outside of the tests.
+fn write_recovery(dir: &Path, scene: &Scene, backdrop: &PixelBuffer) -> io::Result<()> {
+    // The scene names the backdrop by its final name, which it only gets
+    // once both files are complete.
+    let scene_path = dir.join(RECOVERY_PATH);
+    let image_path = scene_path.with_extension("png");
+    let file_name = image_path.file_name().unwrap_or_default().to_string_lossy();
+    let image_temp_path = dir.join(IMAGE_TEMP_PATH);
+    let temp_path = dir.join(TEMP_PATH);
+
+    scene_file::save_backdrop(backdrop, &image_temp_path.to_string_lossy())?;
+    fs::write(
+        &temp_path,
+        scene_file::scene_to_string(scene, Some(&file_name)),
+    )?;
+
+    fs::rename(&image_temp_path, &image_path)?;
+    fs::rename(&temp_path, &scene_path)
+}
+
+// Returns true if the lock was already there.
+fn take_lock(dir: &Path) -> bool {
+    let lock_path = dir.join(LOCK_PATH);
+    let unclean = lock_path.exists();
+
+    if let Err(e) = fs::write(&lock_path, std::process::id().to_string()) {
+        hud::status(format!("Failed to create '{}': {}", lock_path.display(), e));
+    }
+
+    unclean
+}
+
+fn remove_session_files(dir: &Path) {
+    let scene_path = dir.join(RECOVERY_PATH);
+    let image_path = scene_path.with_extension("png");
+
+    for path in [dir.join(LOCK_PATH), scene_path, image_path] {
+        if path.exists() {
+            if let Err(e) = fs::remove_file(&path) {
+                println!("Failed to remove '{}': {}", path.display(), e);
+            }
+        }
+    }
+}
+
+// Takes the lock for this session. Returns true if the previous session
+// did not shut down cleanly.
+pub fn start_session() -> bool {
+    let unclean = take_lock(Path::new(""));
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        println!("Last autosave is in '{}'", RECOVERY_PATH);
+    }));
+
+    unclean
+}
+
+// A clean exit drops the lock and the recovery files with it.
+pub fn end_session() {
+    remove_session_files(Path::new(""));
+}
+
+// Asks on the terminal whether to restore the last autosave. Anything but
+// yes, including no terminal at all, starts fresh.
+pub fn offer_recovery() -> Option<(Scene, Option<PixelBuffer>)> {
+    if !Path::new(RECOVERY_PATH).exists() {
+        hud::status(String::from(
+            "Previous session did not exit cleanly, no autosave found",
+        ));
+        return None;
+    }
+
+    print!("Previous session did not exit cleanly. Restore autosave? [y/N] ");
+    io::stdout().flush().ok()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).ok()?;
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        return None;
+    }
+
+    match scene_file::load_scene(RECOVERY_PATH) {
+        Ok(recovered) => Some(recovered),
+        Err(e) => {
+            hud::status(format!("Failed to restore '{}': {}", RECOVERY_PATH, e));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rust-sdl-autosave-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn recovery_is_written_under_a_temporary_name_first() {
+        let dir = test_dir("write");
+        let mut scene = Scene::new();
+        scene.layers[0].name = String::from("first");
+        let backdrop = PixelBuffer::new(2, 2, 0xFF336699);
+
+        write_recovery(&dir, &scene, &backdrop).unwrap();
+        assert!(dir.join(RECOVERY_PATH).exists());
+        assert!(!dir.join(TEMP_PATH).exists());
+        assert!(!dir.join(IMAGE_TEMP_PATH).exists());
+
+        let recovery = dir.join(RECOVERY_PATH);
+        let (loaded, loaded_backdrop) =
+            scene_file::load_scene(&recovery.to_string_lossy()).unwrap();
+        assert_eq!(loaded.layers[0].name, "first");
+        assert_eq!(loaded_backdrop.unwrap().pixels, backdrop.pixels);
+
+        // A save that fails before the rename leaves the last recovery alone.
+        fs::create_dir(dir.join(IMAGE_TEMP_PATH)).unwrap();
+        scene.layers[0].name = String::from("second");
+        assert!(write_recovery(&dir, &scene, &backdrop).is_err());
+        let (loaded, _) = scene_file::load_scene(&recovery.to_string_lossy()).unwrap();
+        assert_eq!(loaded.layers[0].name, "first");
+    }
+
+    #[test]
+    fn a_lock_left_behind_means_an_unclean_exit() {
+        let dir = test_dir("lock");
+
+        assert!(!take_lock(&dir));
+        assert!(take_lock(&dir));
+
+        remove_session_files(&dir);
+        assert!(!dir.join(LOCK_PATH).exists());
+        assert!(!take_lock(&dir));
+    }
+}
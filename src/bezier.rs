@@ -0,0 +1,619 @@
+use sdl2::rect::{Point, Rect};
+
+use crate::graphics::CubicBezierCurve;
+
+// Geometry on cubic Beziers. The math runs on f32 control points, the
+// CubicBezierCurve methods at the bottom convert from and to the integer
+// control points the glyphs are stored with.
+
+pub type Vec2 = (f32, f32);
+
+pub type Cubic = [Vec2; 4];
+
+// Subdivision stops once both boxes are smaller than this, in pixels.
+const INTERSECTION_TOLERANCE: f32 = 0.05;
+
+// Curve intersections closer than this, in pixels, are one crossing found
+// by several neighbouring boxes.
+const INTERSECTION_MERGE_DISTANCE: f32 = 0.5;
+
+const NEWTON_ITERATIONS: usize = 8;
+
+pub fn add(a: Vec2, b: Vec2) -> Vec2 {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+pub fn sub(a: Vec2, b: Vec2) -> Vec2 {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+pub fn mul(a: Vec2, s: f32) -> Vec2 {
+    (a.0 * s, a.1 * s)
+}
+
+pub fn dot(a: Vec2, b: Vec2) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+pub fn length(a: Vec2) -> f32 {
+    dot(a, a).sqrt()
+}
+
+pub fn normalize(a: Vec2) -> Vec2 {
+    let l = length(a);
+    if l == 0.0 {
+        a
+    } else {
+        mul(a, 1.0 / l)
+    }
+}
+
+fn lerp(a: Vec2, b: Vec2, t: f32) -> Vec2 {
+    add(a, mul(sub(b, a), t))
+}
+
+pub fn evaluate(curve: &Cubic, t: f32) -> Vec2 {
+    let mt = 1.0 - t;
+    let a = mul(curve[0], mt * mt * mt);
+    let b = mul(curve[1], 3.0 * mt * mt * t);
+    let c = mul(curve[2], 3.0 * mt * t * t);
+    let d = mul(curve[3], t * t * t);
+    add(add(a, b), add(c, d))
+}
+
+pub fn derivative(curve: &Cubic, t: f32) -> Vec2 {
+    let mt = 1.0 - t;
+    let a = mul(sub(curve[1], curve[0]), 3.0 * mt * mt);
+    let b = mul(sub(curve[2], curve[1]), 6.0 * mt * t);
+    let c = mul(sub(curve[3], curve[2]), 3.0 * t * t);
+    add(add(a, b), c)
+}
+
+pub fn second_derivative(curve: &Cubic, t: f32) -> Vec2 {
+    let a = mul(
+        add(sub(curve[2], mul(curve[1], 2.0)), curve[0]),
+        6.0 * (1.0 - t),
+    );
+    let b = mul(add(sub(curve[3], mul(curve[2], 2.0)), curve[1]), 6.0 * t);
+    add(a, b)
+}
+
+// Falls back to the chord direction where a handle collapses onto its end
+// point and the derivative vanishes.
+pub fn tangent(curve: &Cubic, t: f32) -> Vec2 {
+    let d = derivative(curve, t);
+    if length(d) > 1.0e-6 {
+        return normalize(d);
+    }
+
+    let nudged = if t < 0.5 { t + 1.0e-3 } else { t - 1.0e-3 };
+    let d = derivative(curve, nudged);
+    if length(d) > 1.0e-6 {
+        normalize(d)
+    } else {
+        normalize(sub(curve[3], curve[0]))
+    }
+}
+
+// Tangent turned 90 degrees clockwise on screen (y points down).
+pub fn normal(curve: &Cubic, t: f32) -> Vec2 {
+    let (x, y) = tangent(curve, t);
+    (-y, x)
+}
+
+// de Casteljau.
+pub fn split(curve: &Cubic, t: f32) -> (Cubic, Cubic) {
+    let p_01 = lerp(curve[0], curve[1], t);
+    let p_12 = lerp(curve[1], curve[2], t);
+    let p_23 = lerp(curve[2], curve[3], t);
+    let p_012 = lerp(p_01, p_12, t);
+    let p_123 = lerp(p_12, p_23, t);
+    let middle = lerp(p_012, p_123, t);
+
+    (
+        [curve[0], p_01, p_012, middle],
+        [middle, p_123, p_23, curve[3]],
+    )
+}
+
+pub fn segment(curve: &Cubic, t_0: f32, t_1: f32) -> Cubic {
+    let (_, right) = split(curve, t_0);
+    if t_0 >= 1.0 {
+        return right;
+    }
+    let (middle, _) = split(&right, (t_1 - t_0) / (1.0 - t_0));
+    middle
+}
+
+const GAUSS_LEGENDRE: [(f32, f32); 8] = [
+    (-0.1834346425, 0.3626837834),
+    (0.1834346425, 0.3626837834),
+    (-0.5255324099, 0.3137066459),
+    (0.5255324099, 0.3137066459),
+    (-0.7966664774, 0.2223810345),
+    (0.7966664774, 0.2223810345),
+    (-0.9602898565, 0.1012285363),
+    (0.9602898565, 0.1012285363),
+];
+
+// Length of the curve between 0 and t.
+pub fn arc_length_to(curve: &Cubic, t: f32) -> f32 {
+    let half = t / 2.0;
+    GAUSS_LEGENDRE
+        .iter()
+        .map(|(x, w)| w * length(derivative(curve, half * x + half)))
+        .sum::<f32>()
+        * half
+}
+
+pub fn arc_length(curve: &Cubic) -> f32 {
+    // Split in halves so long S-shaped handles stay accurate.
+    let (left, right) = split(curve, 0.5);
+    arc_length_to(&left, 1.0) + arc_length_to(&right, 1.0)
+}
+
+// Parameter at which the curve has covered the given length, by Newton
+// steps kept inside a shrinking bracket.
+pub fn t_at_length(curve: &Cubic, distance: f32) -> f32 {
+    let total = arc_length(curve);
+    if distance <= 0.0 || total == 0.0 {
+        return 0.0;
+    }
+    if distance >= total {
+        return 1.0;
+    }
+
+    let (mut low, mut high) = (0.0f32, 1.0f32);
+    let mut t = distance / total;
+
+    for _ in 0..NEWTON_ITERATIONS * 2 {
+        let error = arc_length_to(curve, t) - distance;
+        if error.abs() < 1.0e-3 {
+            break;
+        }
+
+        if error > 0.0 {
+            high = t;
+        } else {
+            low = t;
+        }
+
+        let speed = length(derivative(curve, t));
+        let next = if speed > 0.0 { t - error / speed } else { -1.0 };
+        t = if next > low && next < high {
+            next
+        } else {
+            (low + high) / 2.0
+        };
+    }
+
+    t
+}
+
+// Real roots of a*t^2 + b*t + c inside 0..=1.
+fn quadratic_roots(a: f32, b: f32, c: f32) -> Vec<f32> {
+    let roots = if a.abs() < 1.0e-9 {
+        if b.abs() < 1.0e-9 {
+            Vec::new()
+        } else {
+            vec![-c / b]
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            Vec::new()
+        } else {
+            let root = discriminant.sqrt();
+            vec![(-b + root) / (2.0 * a), (-b - root) / (2.0 * a)]
+        }
+    };
+
+    roots
+        .into_iter()
+        .filter(|t| (0.0..=1.0).contains(t))
+        .collect()
+}
+
+// Real roots of a*t^3 + b*t^2 + c*t + d inside 0..=1 (Cardano).
+fn cubic_roots(a: f32, b: f32, c: f32, d: f32) -> Vec<f32> {
+    if a.abs() < 1.0e-7 {
+        return quadratic_roots(b, c, d);
+    }
+
+    let (b, c, d) = ((b / a) as f64, (c / a) as f64, (d / a) as f64);
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let shift = -b / 3.0;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    let roots: Vec<f64> = if discriminant > 1.0e-12 {
+        let root = discriminant.sqrt();
+        vec![(-q / 2.0 + root).cbrt() + (-q / 2.0 - root).cbrt() + shift]
+    } else if discriminant < -1.0e-12 {
+        let r = (-p / 3.0).sqrt();
+        let phi = (-q / (2.0 * r * r * r)).clamp(-1.0, 1.0).acos();
+        (0..3)
+            .map(|k| 2.0 * r * ((phi + 2.0 * std::f64::consts::PI * k as f64) / 3.0).cos() + shift)
+            .collect()
+    } else {
+        let u = (-q / 2.0).cbrt();
+        vec![2.0 * u + shift, -u + shift]
+    };
+
+    roots
+        .into_iter()
+        .map(|t| t as f32)
+        .filter(|t| (-1.0e-4..=1.0 + 1.0e-4).contains(t))
+        .map(|t| t.clamp(0.0, 1.0))
+        .collect()
+}
+
+// Exact bounds from the end points and wherever x' or y' is zero.
+pub fn bounding_box(curve: &Cubic) -> (Vec2, Vec2) {
+    let mut ts = vec![0.0, 1.0];
+
+    for axis in 0..2 {
+        let value = |p: Vec2| if axis == 0 { p.0 } else { p.1 };
+        let (p_0, p_1, p_2, p_3) = (
+            value(curve[0]),
+            value(curve[1]),
+            value(curve[2]),
+            value(curve[3]),
+        );
+
+        // Derivative divided by 3: a*t^2 + b*t + c
+        let a = -p_0 + 3.0 * p_1 - 3.0 * p_2 + p_3;
+        let b = 2.0 * (p_0 - 2.0 * p_1 + p_2);
+        let c = p_1 - p_0;
+        ts.extend(quadratic_roots(a, b, c));
+    }
+
+    let points: Vec<Vec2> = ts.iter().map(|t| evaluate(curve, *t)).collect();
+    let min = points
+        .iter()
+        .fold((f32::MAX, f32::MAX), |m, p| (m.0.min(p.0), m.1.min(p.1)));
+    let max = points
+        .iter()
+        .fold((f32::MIN, f32::MIN), |m, p| (m.0.max(p.0), m.1.max(p.1)));
+    (min, max)
+}
+
+fn control_box(curve: &Cubic) -> (Vec2, Vec2) {
+    let min = curve
+        .iter()
+        .fold((f32::MAX, f32::MAX), |m, p| (m.0.min(p.0), m.1.min(p.1)));
+    let max = curve
+        .iter()
+        .fold((f32::MIN, f32::MIN), |m, p| (m.0.max(p.0), m.1.max(p.1)));
+    (min, max)
+}
+
+// Returns (t, point on curve, distance). Coarse sampling picks the start,
+// Newton-Raphson on the squared distance refines it.
+pub fn closest_point(curve: &Cubic, point: Vec2) -> (f32, Vec2, f32) {
+    const SAMPLES: usize = 32;
+
+    let mut best_t = (0..=SAMPLES)
+        .map(|i| i as f32 / SAMPLES as f32)
+        .min_by(|a, b| {
+            let da = length(sub(evaluate(curve, *a), point));
+            let db = length(sub(evaluate(curve, *b), point));
+            da.partial_cmp(&db).unwrap()
+        })
+        .unwrap();
+
+    for _ in 0..NEWTON_ITERATIONS {
+        let difference = sub(evaluate(curve, best_t), point);
+        let d_1 = derivative(curve, best_t);
+        let d_2 = second_derivative(curve, best_t);
+
+        let numerator = dot(difference, d_1);
+        let denominator = dot(d_1, d_1) + dot(difference, d_2);
+        if denominator.abs() < 1.0e-9 {
+            break;
+        }
+
+        let next = (best_t - numerator / denominator).clamp(0.0, 1.0);
+        if (next - best_t).abs() < 1.0e-6 {
+            best_t = next;
+            break;
+        }
+        best_t = next;
+    }
+
+    let closest = evaluate(curve, best_t);
+    (best_t, closest, length(sub(closest, point)))
+}
+
+// Intersections with the segment a-b, as (t on curve, point). The curve is
+// expressed as distance from the line, which gives a cubic in t.
+pub fn intersect_line(curve: &Cubic, a: Vec2, b: Vec2) -> Vec<(f32, Vec2)> {
+    let direction = sub(b, a);
+    let line_length = length(direction);
+    if line_length == 0.0 {
+        return Vec::new();
+    }
+
+    let normal = (-direction.1 / line_length, direction.0 / line_length);
+    let d: Vec<f32> = curve.iter().map(|p| dot(sub(*p, a), normal)).collect();
+
+    let coefficients = (
+        -d[0] + 3.0 * d[1] - 3.0 * d[2] + d[3],
+        3.0 * d[0] - 6.0 * d[1] + 3.0 * d[2],
+        -3.0 * d[0] + 3.0 * d[1],
+        d[0],
+    );
+
+    let mut hits: Vec<(f32, Vec2)> = cubic_roots(
+        coefficients.0,
+        coefficients.1,
+        coefficients.2,
+        coefficients.3,
+    )
+    .into_iter()
+    .map(|t| (t, evaluate(curve, t)))
+    .filter(|(_, p)| {
+        let s = dot(sub(*p, a), direction) / (line_length * line_length);
+        (-1.0e-4..=1.0 + 1.0e-4).contains(&s)
+    })
+    .collect();
+
+    hits.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+    hits.dedup_by(|x, y| (x.0 - y.0).abs() < 1.0e-4);
+    hits
+}
+
+// Intersections of two curves as (t on first, t on second), found by
+// splitting both wherever their control boxes overlap. Hits that land
+// within INTERSECTION_MERGE_DISTANCE of each other on both curves are
+// averaged into one.
+pub fn intersect_curves(first: &Cubic, second: &Cubic) -> Vec<(f32, f32)> {
+    let mut hits = Vec::new();
+    intersect_recursive(first, (0.0, 1.0), second, (0.0, 1.0), 0, &mut hits);
+    hits.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+    let close = |x: &(f32, f32), y: &(f32, f32)| {
+        length(sub(evaluate(first, x.0), evaluate(first, y.0))) < INTERSECTION_MERGE_DISTANCE
+            && length(sub(evaluate(second, x.1), evaluate(second, y.1)))
+                < INTERSECTION_MERGE_DISTANCE
+    };
+
+    let mut groups: Vec<Vec<(f32, f32)>> = Vec::new();
+    for hit in hits {
+        match groups.iter_mut().find(|group| close(&group[0], &hit)) {
+            Some(group) => group.push(hit),
+            None => groups.push(vec![hit]),
+        }
+    }
+
+    groups
+        .iter()
+        .map(|group| {
+            let n = group.len() as f32;
+            (
+                group.iter().map(|hit| hit.0).sum::<f32>() / n,
+                group.iter().map(|hit| hit.1).sum::<f32>() / n,
+            )
+        })
+        .collect()
+}
+
+fn intersect_recursive(
+    a: &Cubic,
+    a_range: (f32, f32),
+    b: &Cubic,
+    b_range: (f32, f32),
+    depth: u32,
+    hits: &mut Vec<(f32, f32)>,
+) {
+    let (a_min, a_max) = control_box(a);
+    let (b_min, b_max) = control_box(b);
+
+    if a_max.0 < b_min.0 || b_max.0 < a_min.0 || a_max.1 < b_min.1 || b_max.1 < a_min.1 {
+        return;
+    }
+
+    let a_size = (a_max.0 - a_min.0).max(a_max.1 - a_min.1);
+    let b_size = (b_max.0 - b_min.0).max(b_max.1 - b_min.1);
+
+    if (a_size < INTERSECTION_TOLERANCE && b_size < INTERSECTION_TOLERANCE) || depth > 40 {
+        hits.push(((a_range.0 + a_range.1) / 2.0, (b_range.0 + b_range.1) / 2.0));
+        return;
+    }
+
+    let a_middle = (a_range.0 + a_range.1) / 2.0;
+    let b_middle = (b_range.0 + b_range.1) / 2.0;
+    let (a_left, a_right) = split(a, 0.5);
+    let (b_left, b_right) = split(b, 0.5);
+
+    for (a_part, a_part_range) in [
+        (&a_left, (a_range.0, a_middle)),
+        (&a_right, (a_middle, a_range.1)),
+    ] {
+        for (b_part, b_part_range) in [
+            (&b_left, (b_range.0, b_middle)),
+            (&b_right, (b_middle, b_range.1)),
+        ] {
+            intersect_recursive(a_part, a_part_range, b_part, b_part_range, depth + 1, hits);
+        }
+    }
+}
+
+// The same curve as a quadratic, with one degree more.
+pub fn elevate_quadratic(p_1: Vec2, p_2: Vec2, p_3: Vec2) -> Cubic {
+    [
+        p_1,
+        add(p_1, mul(sub(p_2, p_1), 2.0 / 3.0)),
+        add(p_3, mul(sub(p_2, p_3), 2.0 / 3.0)),
+        p_3,
+    ]
+}
+
+fn to_vec2(p: Point) -> Vec2 {
+    (p.x as f32, p.y as f32)
+}
+
+fn to_point(p: Vec2) -> Point {
+    Point::new(p.0.round() as i32, p.1.round() as i32)
+}
+
+impl CubicBezierCurve {
+    pub fn from_cubic(cubic: &Cubic) -> Self {
+        CubicBezierCurve::new(cubic.iter().map(|p| to_point(*p)).collect())
+    }
+
+    pub fn from_quadratic(p_1: Point, p_2: Point, p_3: Point) -> Self {
+        CubicBezierCurve::from_cubic(&elevate_quadratic(to_vec2(p_1), to_vec2(p_2), to_vec2(p_3)))
+    }
+
+    // Missing control points repeat the last one, so half built curves
+    // still give sensible answers.
+    pub fn to_cubic(&self) -> Cubic {
+        let last = self
+            .controll_points
+            .last()
+            .copied()
+            .unwrap_or(Point::new(0, 0));
+        let at = |i: usize| to_vec2(self.controll_points.get(i).copied().unwrap_or(last));
+        [at(0), at(1), at(2), at(3)]
+    }
+
+    pub fn point_at(&self, t: f32) -> Vec2 {
+        evaluate(&self.to_cubic(), t)
+    }
+
+    pub fn derivative_at(&self, t: f32) -> Vec2 {
+        derivative(&self.to_cubic(), t)
+    }
+
+    pub fn tangent_at(&self, t: f32) -> Vec2 {
+        tangent(&self.to_cubic(), t)
+    }
+
+    pub fn normal_at(&self, t: f32) -> Vec2 {
+        normal(&self.to_cubic(), t)
+    }
+
+    pub fn split_at(&self, t: f32) -> (CubicBezierCurve, CubicBezierCurve) {
+        let (left, right) = split(&self.to_cubic(), t);
+        (
+            CubicBezierCurve::from_cubic(&left),
+            CubicBezierCurve::from_cubic(&right),
+        )
+    }
+
+    pub fn length(&self) -> f32 {
+        arc_length(&self.to_cubic())
+    }
+
+    pub fn t_at_length(&self, distance: f32) -> f32 {
+        t_at_length(&self.to_cubic(), distance)
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        let (min, max) = bounding_box(&self.to_cubic());
+        let (x, y) = (min.0.floor() as i32, min.1.floor() as i32);
+        Rect::new(
+            x,
+            y,
+            (max.0.ceil() as i32 - x + 1) as u32,
+            (max.1.ceil() as i32 - y + 1) as u32,
+        )
+    }
+
+    pub fn closest_point(&self, point: Point) -> (f32, Vec2, f32) {
+        closest_point(&self.to_cubic(), to_vec2(point))
+    }
+
+    pub fn intersect_line(&self, p_1: Point, p_2: Point) -> Vec<(f32, Vec2)> {
+        intersect_line(&self.to_cubic(), to_vec2(p_1), to_vec2(p_2))
+    }
+
+    pub fn intersect_curve(&self, other: &CubicBezierCurve) -> Vec<(f32, f32)> {
+        intersect_curves(&self.to_cubic(), &other.to_cubic())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rises from (0, 0) to a peak at y = 75 and comes back down at x = 100.
+    const ARCH: Cubic = [(0.0, 0.0), (0.0, 100.0), (100.0, 100.0), (100.0, 0.0)];
+
+    fn close_to(a: Vec2, b: Vec2) -> bool {
+        length(sub(a, b)) < 1.0e-3
+    }
+
+    #[test]
+    fn split_halves_follow_the_curve() {
+        for t in [0.25, 0.5, 0.8] {
+            let (left, right) = split(&ARCH, t);
+            assert!(close_to(left[0], ARCH[0]));
+            assert!(close_to(right[3], ARCH[3]));
+            assert!(close_to(left[3], evaluate(&ARCH, t)));
+            assert!(close_to(right[0], evaluate(&ARCH, t)));
+
+            for s in [0.0, 0.3, 0.7, 1.0] {
+                assert!(close_to(evaluate(&left, s), evaluate(&ARCH, s * t)));
+                assert!(close_to(
+                    evaluate(&right, s),
+                    evaluate(&ARCH, t + s * (1.0 - t))
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn bounding_box_includes_the_extremes() {
+        let (min, max) = bounding_box(&ARCH);
+        assert!(close_to(min, (0.0, 0.0)));
+        assert!(close_to(max, (100.0, 75.0)));
+
+        // An S curve overshoots both ends horizontally.
+        let s_curve: Cubic = [(0.0, 0.0), (200.0, 0.0), (-200.0, 50.0), (50.0, 50.0)];
+        let (min, max) = bounding_box(&s_curve);
+        assert!(min.0 < 0.0 && max.0 > 50.0);
+        for i in 0..=100 {
+            let p = evaluate(&s_curve, i as f32 / 100.0);
+            assert!(p.0 >= min.0 - 1.0e-3 && p.0 <= max.0 + 1.0e-3);
+            assert!(p.1 >= min.1 - 1.0e-3 && p.1 <= max.1 + 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn intersect_line_finds_both_crossings() {
+        let hits = intersect_line(&ARCH, (-10.0, 50.0), (110.0, 50.0));
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].0 < hits[1].0);
+        for (t, p) in &hits {
+            assert!((p.1 - 50.0).abs() < 1.0e-2);
+            assert!(close_to(*p, evaluate(&ARCH, *t)));
+        }
+    }
+
+    #[test]
+    fn intersect_line_stops_at_the_segment_ends() {
+        assert_eq!(intersect_line(&ARCH, (-10.0, 50.0), (50.0, 50.0)).len(), 1);
+        assert!(intersect_line(&ARCH, (-10.0, 90.0), (110.0, 90.0)).is_empty());
+        assert!(intersect_line(&ARCH, (5.0, 5.0), (5.0, 5.0)).is_empty());
+    }
+
+    #[test]
+    fn crossing_curves_report_each_intersection_once() {
+        let upside_down: Cubic = [(0.0, 60.0), (0.0, -40.0), (100.0, -40.0), (100.0, 60.0)];
+        let hits = intersect_curves(&ARCH, &upside_down);
+        assert_eq!(hits.len(), 2);
+        for (t_1, t_2) in hits {
+            assert!(length(sub(evaluate(&ARCH, t_1), evaluate(&upside_down, t_2))) < 0.5);
+        }
+
+        // On short curves the boxes meeting at a crossing are far apart in
+        // t, though not on screen.
+        let rising: Cubic = [(0.0, 0.0), (3.0, 3.0), (7.0, 7.0), (10.0, 10.0)];
+        let falling: Cubic = [(0.0, 10.0), (3.0, 7.0), (7.0, 3.0), (10.0, 0.0)];
+        let hits = intersect_curves(&rising, &falling);
+        assert_eq!(hits.len(), 1);
+        assert!(close_to(evaluate(&rising, hits[0].0), (5.0, 5.0)));
+    }
+}
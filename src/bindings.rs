@@ -0,0 +1,465 @@
+use std::fs;
+use std::io;
+
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+
+use crate::hud;
+use crate::scene_file::{invalid, parse};
+use crate::tools::Tool;
+
+// Maps keys, mouse buttons and the wheel to what they do. The file has
+// one binding per line:
+//
+//   rust-sdl bindings <version>
+//   bind <action> <input>
+//
+// Inputs are SDL key names with optional Ctrl+ and Shift+ in front (Ctrl+Z,
+// Shift+X, PageUp), MouseLeft, MouseMiddle, MouseRight, WheelUp, WheelDown
+// or none. An action listed in the file loses its default bindings, an
+// input bound in the file is taken away from whatever had it by default.
+//
+// The glyph_ actions only apply while the glyph editor is open, so they
+// can share inputs with the rest. The text tool reads the keyboard
+// directly while it has it, so does the glyph editor for picking digits.
+pub const BINDINGS_PATH: &str = "bindings.txt";
+const HEADER: &str = "rust-sdl bindings";
+pub const BINDINGS_VERSION: u32 = 1;
+
+const DEFAULT_BINDINGS: &str = "\
+bind quit Escape
+bind screenshot S
+bind use_tool MouseLeft
+bind cancel MouseRight
+bind pan MouseMiddle
+bind zoom_in WheelUp
+bind zoom_out WheelDown
+bind fit_view F
+bind reset_view Home
+bind delete Delete
+bind undo Ctrl+Z
+bind redo Ctrl+Y
+bind save_scene Ctrl+S
+bind load_scene Ctrl+O
+bind next_series Tab
+bind series_left Left
+bind series_right Right
+bind series_up Up
+bind series_down Down
+bind series_turn_left PageUp
+bind series_turn_right PageDown
+bind series_grow =
+bind series_shrink -
+bind grid_snap N
+bind endpoint_snap M
+bind poster P
+bind hatch H
+bind effects E
+bind gamma G
+bind smooth_strokes A
+bind dither D
+bind dither_method Shift+D
+bind dither_palette Ctrl+D
+bind filter_next Shift+F
+bind filter_add Ctrl+F
+bind filter_remove Ctrl+Shift+F
+bind trace T
+bind toggle_fill L
+bind print Shift+X
+bind export X
+bind export_layers Ctrl+E
+bind hud F1
+bind glyph_editor F2
+bind tool_select V
+bind tool_fill 0
+bind tool_line 1
+bind tool_rectangle 2
+bind tool_circle 3
+bind tool_quadratic_bezier 4
+bind tool_cubic_bezier 5
+bind tool_freehand 6
+bind tool_text 7
+bind glyph_save Ctrl+S
+bind glyph_lines L
+bind glyph_curves B
+bind glyph_delete Delete
+bind glyph_delete Backspace
+";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    Screenshot,
+    // Click with the current tool: flood fill, select, place points...
+    UseTool,
+    Cancel,
+    Pan,
+    ZoomIn,
+    ZoomOut,
+    FitView,
+    ResetView,
+    Delete,
+    Undo,
+    Redo,
+    SaveScene,
+    LoadScene,
+    NextSeries,
+    SeriesLeft,
+    SeriesRight,
+    SeriesUp,
+    SeriesDown,
+    SeriesTurnLeft,
+    SeriesTurnRight,
+    SeriesGrow,
+    SeriesShrink,
+    GridSnap,
+    EndpointSnap,
+    Poster,
+    Hatch,
+    Effects,
+    Gamma,
+    SmoothStrokes,
+    Dither,
+    DitherMethod,
+    DitherPalette,
+    FilterNext,
+    FilterAdd,
+    FilterRemove,
+    Trace,
+    ToggleFill,
+    Print,
+    Export,
+    ExportLayers,
+    Hud,
+    GlyphEditor,
+    Tool(Tool),
+    GlyphSave,
+    GlyphLines,
+    GlyphCurves,
+    GlyphDelete,
+}
+
+const ACTIONS: [(&str, Action); 56] = [
+    ("quit", Action::Quit),
+    ("screenshot", Action::Screenshot),
+    ("use_tool", Action::UseTool),
+    ("cancel", Action::Cancel),
+    ("pan", Action::Pan),
+    ("zoom_in", Action::ZoomIn),
+    ("zoom_out", Action::ZoomOut),
+    ("fit_view", Action::FitView),
+    ("reset_view", Action::ResetView),
+    ("delete", Action::Delete),
+    ("undo", Action::Undo),
+    ("redo", Action::Redo),
+    ("save_scene", Action::SaveScene),
+    ("load_scene", Action::LoadScene),
+    ("next_series", Action::NextSeries),
+    ("series_left", Action::SeriesLeft),
+    ("series_right", Action::SeriesRight),
+    ("series_up", Action::SeriesUp),
+    ("series_down", Action::SeriesDown),
+    ("series_turn_left", Action::SeriesTurnLeft),
+    ("series_turn_right", Action::SeriesTurnRight),
+    ("series_grow", Action::SeriesGrow),
+    ("series_shrink", Action::SeriesShrink),
+    ("grid_snap", Action::GridSnap),
+    ("endpoint_snap", Action::EndpointSnap),
+    ("poster", Action::Poster),
+    ("hatch", Action::Hatch),
+    ("effects", Action::Effects),
+    ("gamma", Action::Gamma),
+    ("smooth_strokes", Action::SmoothStrokes),
+    ("dither", Action::Dither),
+    ("dither_method", Action::DitherMethod),
+    ("dither_palette", Action::DitherPalette),
+    ("filter_next", Action::FilterNext),
+    ("filter_add", Action::FilterAdd),
+    ("filter_remove", Action::FilterRemove),
+    ("trace", Action::Trace),
+    ("toggle_fill", Action::ToggleFill),
+    ("print", Action::Print),
+    ("export", Action::Export),
+    ("export_layers", Action::ExportLayers),
+    ("hud", Action::Hud),
+    ("glyph_editor", Action::GlyphEditor),
+    ("tool_select", Action::Tool(Tool::Select)),
+    ("tool_fill", Action::Tool(Tool::Fill)),
+    ("tool_line", Action::Tool(Tool::Line)),
+    ("tool_rectangle", Action::Tool(Tool::Rectangle)),
+    ("tool_circle", Action::Tool(Tool::Circle)),
+    ("tool_quadratic_bezier", Action::Tool(Tool::QuadraticBezier)),
+    ("tool_cubic_bezier", Action::Tool(Tool::CubicBezier)),
+    ("tool_freehand", Action::Tool(Tool::Freehand)),
+    ("tool_text", Action::Tool(Tool::Text)),
+    ("glyph_save", Action::GlyphSave),
+    ("glyph_lines", Action::GlyphLines),
+    ("glyph_curves", Action::GlyphCurves),
+    ("glyph_delete", Action::GlyphDelete),
+];
+
+impl Action {
+    fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(action_name, _)| *action_name == name)
+            .map(|(_, action)| *action)
+    }
+
+    fn in_glyph_editor(&self) -> bool {
+        matches!(
+            self,
+            Action::GlyphSave | Action::GlyphLines | Action::GlyphCurves | Action::GlyphDelete
+        )
+    }
+
+    // Whether holding the key down keeps doing it. Saving, exporting and
+    // toggling once per press is enough.
+    fn repeats(&self) -> bool {
+        matches!(
+            self,
+            Action::Undo
+                | Action::Redo
+                | Action::ZoomIn
+                | Action::ZoomOut
+                | Action::SeriesLeft
+                | Action::SeriesRight
+                | Action::SeriesUp
+                | Action::SeriesDown
+                | Action::SeriesTurnLeft
+                | Action::SeriesTurnRight
+                | Action::SeriesGrow
+                | Action::SeriesShrink
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Input {
+    Key {
+        keycode: Keycode,
+        ctrl: bool,
+        shift: bool,
+    },
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+}
+
+impl Input {
+    pub fn key(keycode: Keycode, keymod: Mod) -> Input {
+        Input::Key {
+            keycode,
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        }
+    }
+
+    // None for "none", which only clears the action's defaults.
+    fn parse(text: &str, line: usize) -> io::Result<Option<Input>> {
+        let input = match text {
+            "none" => return Ok(None),
+            "MouseLeft" => Input::Mouse(MouseButton::Left),
+            "MouseMiddle" => Input::Mouse(MouseButton::Middle),
+            "MouseRight" => Input::Mouse(MouseButton::Right),
+            "WheelUp" => Input::WheelUp,
+            "WheelDown" => Input::WheelDown,
+            _ => {
+                let mut name = text;
+                let (mut ctrl, mut shift) = (false, false);
+                loop {
+                    if let Some(rest) = name.strip_prefix("Ctrl+").filter(|r| !r.is_empty()) {
+                        ctrl = true;
+                        name = rest;
+                    } else if let Some(rest) = name.strip_prefix("Shift+").filter(|r| !r.is_empty())
+                    {
+                        shift = true;
+                        name = rest;
+                    } else {
+                        break;
+                    }
+                }
+
+                match Keycode::from_name(name) {
+                    Some(keycode) => Input::Key {
+                        keycode,
+                        ctrl,
+                        shift,
+                    },
+                    None => return Err(invalid(line, &format!("unknown key '{}'", name))),
+                }
+            }
+        };
+
+        Ok(Some(input))
+    }
+}
+
+pub struct Bindings {
+    bindings: Vec<(Input, Action)>,
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        let mut bindings = Bindings {
+            bindings: Vec::new(),
+        };
+        bindings
+            .apply(DEFAULT_BINDINGS, 0)
+            .expect("Parsing default bindings failed");
+        bindings
+    }
+
+    fn find(&self, input: Input, glyph_editor: bool) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, action)| *bound == input && action.in_glyph_editor() == glyph_editor)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn action(&self, input: Input) -> Option<Action> {
+        self.find(input, false)
+    }
+
+    fn find_event(&self, event: &Event, glyph_editor: bool) -> Option<Action> {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                repeat,
+                ..
+            } => self
+                .find(Input::key(keycode, keymod), glyph_editor)
+                .filter(|action| !repeat || action.repeats()),
+            Event::MouseButtonDown { mouse_btn, .. } => {
+                self.find(Input::Mouse(mouse_btn), glyph_editor)
+            }
+            Event::MouseWheel { y, .. } if y > 0 => self.find(Input::WheelUp, glyph_editor),
+            Event::MouseWheel { y, .. } if y < 0 => self.find(Input::WheelDown, glyph_editor),
+            _ => None,
+        }
+    }
+
+    // What a key press, click or wheel turn is bound to. Held keys only
+    // repeat actions where that makes sense.
+    pub fn event_action(&self, event: &Event) -> Option<Action> {
+        self.find_event(event, false)
+    }
+
+    // The same for the glyph_ actions.
+    pub fn glyph_editor_action(&self, event: &Event) -> Option<Action> {
+        self.find_event(event, true)
+    }
+
+    // Records from text, the first line of which is line first_line of the
+    // file.
+    fn apply(&mut self, text: &str, first_line: usize) -> io::Result<()> {
+        let mut rebound: Vec<Action> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let i = first_line + i;
+            let mut tokens = line.splitn(3, char::is_whitespace);
+
+            match tokens.next() {
+                None | Some("") => continue,
+                Some("bind") => {
+                    let name = tokens.next().unwrap_or_default();
+                    let action = match Action::from_name(name) {
+                        Some(action) => action,
+                        None => return Err(invalid(i, &format!("unknown action '{}'", name))),
+                    };
+                    let input = Input::parse(tokens.next().unwrap_or_default().trim(), i)?;
+
+                    if !rebound.contains(&action) {
+                        self.bindings.retain(|(_, bound)| *bound != action);
+                        rebound.push(action);
+                    }
+                    if let Some(input) = input {
+                        self.bindings.retain(|(bound, other)| {
+                            *bound != input || other.in_glyph_editor() != action.in_glyph_editor()
+                        });
+                        self.bindings.push((input, action));
+                    }
+                }
+                Some(record) => hud::status(format!(
+                    "Skipping unknown record '{}' on line {}",
+                    record,
+                    i + 1
+                )),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The defaults with the file's bindings applied on top.
+pub fn load_bindings(path: &str) -> io::Result<Bindings> {
+    let text = fs::read_to_string(path)?;
+    let (first, rest) = text.split_once('\n').unwrap_or((&text, ""));
+
+    let version: u32 = match first.trim().strip_prefix(HEADER) {
+        Some(version) => parse(Some(version.trim()), 0)?,
+        None => return Err(invalid(0, "not a bindings file")),
+    };
+    if version != BINDINGS_VERSION {
+        return Err(invalid(
+            0,
+            &format!("unsupported bindings version {}", version),
+        ));
+    }
+
+    let mut bindings = Bindings::new();
+    bindings.apply(rest, 1)?;
+    Ok(bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mouse inputs only, key names need SDL to look up.
+    fn bindings(text: &str) -> Bindings {
+        let mut bindings = Bindings {
+            bindings: Vec::new(),
+        };
+        bindings.apply(text, 0).unwrap();
+        bindings
+    }
+
+    #[test]
+    fn glyph_editor_actions_share_inputs() {
+        let bindings = bindings("bind cancel MouseRight\nbind glyph_delete MouseRight\n");
+        let right = Input::Mouse(MouseButton::Right);
+        assert!(bindings.action(right) == Some(Action::Cancel));
+        assert!(bindings.find(right, true) == Some(Action::GlyphDelete));
+    }
+
+    #[test]
+    fn rebinding_takes_inputs_within_one_context() {
+        let bindings = bindings(
+            "bind cancel MouseRight\n\
+             bind glyph_delete MouseRight\n\
+             bind pan MouseRight\n",
+        );
+        let right = Input::Mouse(MouseButton::Right);
+        assert!(bindings.action(right) == Some(Action::Pan));
+        assert!(bindings.find(right, true) == Some(Action::GlyphDelete));
+    }
+
+    #[test]
+    fn rebinding_an_action_drops_its_earlier_inputs() {
+        let mut bindings = bindings("bind pan MouseMiddle\n");
+        bindings.apply("bind pan WheelUp\n", 0).unwrap();
+        assert!(bindings.action(Input::Mouse(MouseButton::Middle)).is_none());
+        assert!(bindings.action(Input::WheelUp) == Some(Action::Pan));
+    }
+
+    #[test]
+    fn unknown_actions_are_errors() {
+        let mut bindings = bindings("");
+        let error = bindings.apply("\nbind fly MouseLeft\n", 4).unwrap_err();
+        assert!(error.to_string().starts_with("line 6:"), "{}", error);
+    }
+}
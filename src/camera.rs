@@ -0,0 +1,314 @@
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::graphics::CubicBezierCurve;
+use crate::numbers::NumberSeries;
+use crate::raster::{PixelBuffer, Primitive, RenderSettings};
+use crate::scene::{Geometry, Scene, Shape};
+
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 32.0;
+// Fraction of the window fit_to leaves around the content.
+const FIT_MARGIN: f32 = 0.05;
+
+// Maps scene coordinates to window pixels. The scene point (x, y) sits at
+// the top left corner of the window, zoom is window pixels per scene unit.
+pub struct Camera {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+    pub width: u32,
+    pub height: u32,
+    // Last window position while middle dragging.
+    pan: Option<Point>,
+}
+
+impl Camera {
+    pub fn new(width: u32, height: u32) -> Self {
+        Camera {
+            x: 0.0,
+            y: 0.0,
+            zoom: 1.0,
+            width,
+            height,
+            pan: None,
+        }
+    }
+
+    pub fn to_screen(&self, point: Point) -> Point {
+        Point::new(
+            ((point.x as f32 - self.x) * self.zoom).round() as i32,
+            ((point.y as f32 - self.y) * self.zoom).round() as i32,
+        )
+    }
+
+    pub fn to_scene(&self, point: Point) -> Point {
+        Point::new(
+            (point.x as f32 / self.zoom + self.x).floor() as i32,
+            (point.y as f32 / self.zoom + self.y).floor() as i32,
+        )
+    }
+
+    // Keeps the scene point under the cursor where it is.
+    pub fn zoom_at(&mut self, screen: Point, factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let anchor_x = screen.x as f32 / self.zoom + self.x;
+        let anchor_y = screen.y as f32 / self.zoom + self.y;
+
+        self.zoom = zoom;
+        self.x = anchor_x - screen.x as f32 / zoom;
+        self.y = anchor_y - screen.y as f32 / zoom;
+    }
+
+    pub fn start_pan(&mut self, screen: Point) {
+        self.pan = Some(screen);
+    }
+
+    pub fn is_panning(&self) -> bool {
+        self.pan.is_some()
+    }
+
+    pub fn pan_to(&mut self, screen: Point) {
+        if let Some(last) = self.pan {
+            self.x -= (screen.x - last.x) as f32 / self.zoom;
+            self.y -= (screen.y - last.y) as f32 / self.zoom;
+            self.pan = Some(screen);
+        }
+    }
+
+    pub fn end_pan(&mut self) {
+        self.pan = None;
+    }
+
+    pub fn reset(&mut self) {
+        self.x = 0.0;
+        self.y = 0.0;
+        self.zoom = 1.0;
+    }
+
+    // Zooms so the rectangle fills the window and centers it.
+    pub fn fit_to(&mut self, bounds: Rect) {
+        let usable_w = self.width as f32 * (1.0 - 2.0 * FIT_MARGIN);
+        let usable_h = self.height as f32 * (1.0 - 2.0 * FIT_MARGIN);
+        self.zoom = (usable_w / bounds.width().max(1) as f32)
+            .min(usable_h / bounds.height().max(1) as f32)
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+
+        let center = bounds.center();
+        self.x = center.x as f32 - self.width as f32 / (2.0 * self.zoom);
+        self.y = center.y as f32 - self.height as f32 / (2.0 * self.zoom);
+    }
+
+    fn map_points(&self, points: &mut [Point]) {
+        for point in points.iter_mut() {
+            *point = self.to_screen(*point);
+        }
+    }
+
+    fn map_curves(&self, curves: &mut [CubicBezierCurve]) {
+        for curve in curves.iter_mut() {
+            self.map_points(&mut curve.controll_points);
+        }
+    }
+
+    // A copy of the shape in window pixels.
+    pub fn shape(&self, shape: &Shape) -> Shape {
+        let mut shape = shape.clone();
+
+        match &mut shape.geometry {
+            Geometry::Line(line) => self.map_points(&mut line.controll_points),
+            Geometry::Rectangle(rectangle) => self.map_points(&mut rectangle.controll_points),
+            Geometry::Circle { center, radius } => {
+                *center = self.to_screen(*center);
+                *radius = (*radius as f32 * self.zoom).round() as i32;
+            }
+            Geometry::QuadraticBezier(points) => self.map_points(points),
+            Geometry::CubicBezier(curve) => self.map_points(&mut curve.controll_points),
+            Geometry::Path { curves, .. } => self.map_curves(curves),
+        }
+
+        shape
+    }
+
+    fn series(&self, series: &NumberSeries) -> NumberSeries {
+        let mut series = series.clone();
+
+        for number in series.numbers.iter_mut() {
+            for line in number.lines.iter_mut() {
+                self.map_points(&mut line.controll_points);
+            }
+            self.map_curves(&mut number.bezier_curves);
+            number.center = self.to_screen(number.center);
+        }
+        // Effects are sized by the series scale.
+        series.scale *= self.zoom;
+
+        series
+    }
+
+    // The backdrop is stretched by the GPU, the area around it is left grey
+    // so the edge of the drawing stays visible when zoomed out or when the
+    // window is bigger than the drawing.
+    pub fn draw_backdrop(&self, backdrop: &PixelBuffer, canvas: &mut Canvas<Window>) {
+        canvas.set_draw_color(Color::RGB(160, 160, 160));
+        canvas.clear();
+
+        let dst = self.screen_rect(backdrop.width, backdrop.height);
+        backdrop.copy_to_canvas_rect(canvas, Some(dst));
+    }
+
+    // Where a width by height area at the scene origin ends up in the window.
+    pub fn screen_rect(&self, width: u32, height: u32) -> Rect {
+        let top_left = self.to_screen(Point::new(0, 0));
+        let bottom_right = self.to_screen(Point::new(width as i32, height as i32));
+        Rect::new(
+            top_left.x,
+            top_left.y,
+            (bottom_right.x - top_left.x).max(1) as u32,
+            (bottom_right.y - top_left.y).max(1) as u32,
+        )
+    }
+
+    pub fn draw_scene(&self, scene: &Scene, settings: RenderSettings, canvas: &mut Canvas<Window>) {
+        self.view(scene).draw(canvas, settings);
+    }
+
+    // A copy of the scene in window pixels.
+    pub fn view(&self, scene: &Scene) -> Scene {
+        let mut view = Scene::new();
+        view.layers = scene.layers.clone();
+        view.series = scene
+            .series
+            .iter()
+            .map(|series| self.series(series))
+            .collect();
+        view.shapes = scene.shapes.iter().map(|shape| self.shape(shape)).collect();
+        view
+    }
+}
+
+// Everything in the scene, None if it is empty.
+pub fn content_bounds(scene: &Scene) -> Option<Rect> {
+    let mut boxes: Vec<Rect> = scene.shapes.iter().map(Shape::bounding_box).collect();
+    for series in scene.series.iter() {
+        for number in series.numbers.iter() {
+            boxes.extend(number.primitives().iter().map(Primitive::bounding_box));
+        }
+    }
+
+    let (first, rest) = boxes.split_first()?;
+    Some(rest.iter().fold(*first, |a, b| a.union(*b)))
+}
+
+// The scene as it would be drawn over the backdrop, in scene coordinates.
+// Flood fills look for their boundaries here, independent of the view.
+pub fn scene_frame(scene: &Scene, backdrop: &PixelBuffer) -> PixelBuffer {
+    let mut frame = PixelBuffer::new(backdrop.width, backdrop.height, 0);
+    frame.pixels = backdrop.pixels.clone();
+    frame.settings = backdrop.settings;
+    scene.render(&mut frame);
+    frame
+}
+
+// Looks at the whole drawing at scale times its size, whatever the window is.
+fn offscreen_camera(backdrop: &PixelBuffer, scale: f32) -> Camera {
+    let width = ((backdrop.width as f32 * scale).round() as u32).max(1);
+    let height = ((backdrop.height as f32 * scale).round() as u32).max(1);
+
+    let mut camera = Camera::new(width, height);
+    camera.zoom = scale;
+    camera
+}
+
+// The backdrop is sampled nearest neighbour.
+pub fn scale_backdrop(backdrop: &PixelBuffer, scale: f32) -> PixelBuffer {
+    let camera = offscreen_camera(backdrop, scale);
+
+    let mut target = PixelBuffer::new(camera.width, camera.height, 0xFFFFFFFF);
+    target.settings = backdrop.settings;
+    for y in 0..camera.height as i32 {
+        for x in 0..camera.width as i32 {
+            let source = camera.to_scene(Point::new(x, y));
+            if backdrop.contains(source.x, source.y) {
+                target.set(x, y, backdrop.get(source.x, source.y));
+            }
+        }
+    }
+    target
+}
+
+// Backdrop and scene at scale times their size. Vector shapes are
+// rasterized at the new size.
+pub fn render_offscreen(scene: &Scene, backdrop: &PixelBuffer, scale: f32) -> PixelBuffer {
+    let mut target = scale_backdrop(backdrop, scale);
+    offscreen_camera(backdrop, scale)
+        .view(scene)
+        .render(&mut target);
+    target
+}
+
+// One layer on a transparent buffer the size render_offscreen gives.
+pub fn render_layer_offscreen(
+    scene: &Scene,
+    index: usize,
+    backdrop: &PixelBuffer,
+    scale: f32,
+) -> PixelBuffer {
+    let camera = offscreen_camera(backdrop, scale);
+
+    let mut target = PixelBuffer::new(camera.width, camera.height, 0x00000000);
+    target.settings = backdrop.settings;
+    camera.view(scene).render_layer(index, &mut target);
+    target
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Line;
+
+    #[test]
+    fn backdrop_keeps_its_size_in_a_bigger_window() {
+        let camera = Camera::new(1200, 900);
+        assert_eq!(camera.screen_rect(840, 680), Rect::new(0, 0, 840, 680));
+    }
+
+    #[test]
+    fn backdrop_follows_zoom_and_pan() {
+        let mut camera = Camera::new(840, 680);
+        camera.zoom_at(Point::new(0, 0), 2.0);
+        assert_eq!(camera.screen_rect(100, 50), Rect::new(0, 0, 200, 100));
+
+        camera.x = 10.0;
+        camera.y = -5.0;
+        assert_eq!(camera.screen_rect(100, 50), Rect::new(-20, 10, 200, 100));
+    }
+
+    #[test]
+    fn offscreen_renders_are_scaled_from_the_drawing() {
+        let mut scene = Scene::new();
+        let line = Line::new(vec![Point::new(0, 5), Point::new(9, 5)]);
+        scene.add(Shape::new(Geometry::Line(line), 0xFF000000));
+        let backdrop = PixelBuffer::new(10, 8, 0xFFFF0000);
+
+        let whole = render_offscreen(&scene, &backdrop, 2.0);
+        assert_eq!((whole.width, whole.height), (20, 16));
+        assert_eq!(whole.get(3, 1), 0xFFFF0000);
+        assert_eq!(whole.get(3, 10), 0xFF000000);
+
+        let layer = render_layer_offscreen(&scene, 0, &backdrop, 2.0);
+        assert_eq!((layer.width, layer.height), (20, 16));
+        assert_eq!(layer.get(3, 1), 0x00000000);
+        assert_eq!(layer.get(3, 10), 0xFF000000);
+    }
+
+    #[test]
+    fn to_scene_undoes_to_screen() {
+        let mut camera = Camera::new(840, 680);
+        camera.zoom_at(Point::new(300, 200), 3.0);
+        let point = Point::new(123, -45);
+        assert_eq!(camera.to_scene(camera.to_screen(point)), point);
+    }
+}
@@ -0,0 +1,387 @@
+use sdl2::rect::{Point, Rect};
+
+const INSIDE: u8 = 0;
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const BOTTOM: u8 = 4;
+const TOP: u8 = 8;
+
+// Pixel bounds of the rect as floats. The max edges are inclusive, since
+// Rect::right and Rect::bottom are one past the last pixel.
+fn edges(rect: Rect) -> (f32, f32, f32, f32) {
+    (
+        rect.left() as f32,
+        rect.top() as f32,
+        (rect.right() - 1) as f32,
+        (rect.bottom() - 1) as f32,
+    )
+}
+
+fn out_code(x: f32, y: f32, rect: Rect) -> u8 {
+    let (x_min, y_min, x_max, y_max) = edges(rect);
+    let mut code = INSIDE;
+
+    if x < x_min {
+        code |= LEFT;
+    } else if x > x_max {
+        code |= RIGHT;
+    }
+
+    if y < y_min {
+        code |= TOP;
+    } else if y > y_max {
+        code |= BOTTOM;
+    }
+
+    code
+}
+
+pub fn cohen_sutherland(p_1: Point, p_2: Point, rect: Rect) -> Option<(Point, Point)> {
+    let (x_min, y_min, x_max, y_max) = edges(rect);
+
+    let (mut x0, mut y0) = (p_1.x as f32, p_1.y as f32);
+    let (mut x1, mut y1) = (p_2.x as f32, p_2.y as f32);
+    let mut code_0 = out_code(x0, y0, rect);
+    let mut code_1 = out_code(x1, y1, rect);
+
+    loop {
+        if code_0 | code_1 == INSIDE {
+            return Some((
+                Point::new(x0.round() as i32, y0.round() as i32),
+                Point::new(x1.round() as i32, y1.round() as i32),
+            ));
+        }
+
+        if code_0 & code_1 != INSIDE {
+            return None;
+        }
+
+        let code_out = if code_0 != INSIDE { code_0 } else { code_1 };
+
+        let (x, y) = if code_out & TOP != 0 {
+            (x0 + (x1 - x0) * (y_min - y0) / (y1 - y0), y_min)
+        } else if code_out & BOTTOM != 0 {
+            (x0 + (x1 - x0) * (y_max - y0) / (y1 - y0), y_max)
+        } else if code_out & RIGHT != 0 {
+            (x_max, y0 + (y1 - y0) * (x_max - x0) / (x1 - x0))
+        } else {
+            (x_min, y0 + (y1 - y0) * (x_min - x0) / (x1 - x0))
+        };
+
+        if code_out == code_0 {
+            x0 = x;
+            y0 = y;
+            code_0 = out_code(x0, y0, rect);
+        } else {
+            x1 = x;
+            y1 = y;
+            code_1 = out_code(x1, y1, rect);
+        }
+    }
+}
+
+pub fn liang_barsky(
+    p_1: (f32, f32),
+    p_2: (f32, f32),
+    rect: Rect,
+) -> Option<((f32, f32), (f32, f32))> {
+    let (x_min, y_min, x_max, y_max) = edges(rect);
+
+    let dx = p_2.0 - p_1.0;
+    let dy = p_2.1 - p_1.1;
+
+    let p = [-dx, dx, -dy, dy];
+    let q = [
+        p_1.0 - x_min,
+        x_max - p_1.0,
+        p_1.1 - y_min,
+        y_max - p_1.1,
+    ];
+
+    let mut t_0: f32 = 0.0;
+    let mut t_1: f32 = 1.0;
+
+    for i in 0..4 {
+        if p[i] == 0.0 {
+            if q[i] < 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = q[i] / p[i];
+        if p[i] < 0.0 {
+            t_0 = t_0.max(t);
+        } else {
+            t_1 = t_1.min(t);
+        }
+
+        if t_0 > t_1 {
+            return None;
+        }
+    }
+
+    Some((
+        (p_1.0 + t_0 * dx, p_1.1 + t_0 * dy),
+        (p_1.0 + t_1 * dx, p_1.1 + t_1 * dy),
+    ))
+}
+
+pub fn clip_line(p_1: Point, p_2: Point, rect: Rect) -> Option<(Point, Point)> {
+    cohen_sutherland(p_1, p_2, rect)
+}
+
+// Sutherland–Hodgman, one clip edge at a time.
+pub fn clip_polygon(points: &[(f32, f32)], rect: Rect) -> Vec<(f32, f32)> {
+    let (x_min, y_min, x_max, y_max) = edges(rect);
+
+    let mut output: Vec<(f32, f32)> = points.to_vec();
+
+    let planes: [(fn((f32, f32), f32) -> bool, bool, f32); 4] = [
+        (|p, v| p.0 >= v, true, x_min),
+        (|p, v| p.0 <= v, true, x_max),
+        (|p, v| p.1 >= v, false, y_min),
+        (|p, v| p.1 <= v, false, y_max),
+    ];
+
+    for (is_inside, vertical, value) in planes {
+        if output.is_empty() {
+            break;
+        }
+
+        let input = output;
+        output = Vec::with_capacity(input.len() + 4);
+
+        let intersect = |a: (f32, f32), b: (f32, f32)| {
+            if vertical {
+                let t = (value - a.0) / (b.0 - a.0);
+                (value, a.1 + t * (b.1 - a.1))
+            } else {
+                let t = (value - a.1) / (b.1 - a.1);
+                (a.0 + t * (b.0 - a.0), value)
+            }
+        };
+
+        let mut previous = input[input.len() - 1];
+        for &current in input.iter() {
+            match (is_inside(current, value), is_inside(previous, value)) {
+                (true, true) => output.push(current),
+                (true, false) => {
+                    output.push(intersect(previous, current));
+                    output.push(current);
+                }
+                (false, true) => output.push(intersect(previous, current)),
+                (false, false) => {}
+            }
+            previous = current;
+        }
+    }
+
+    output
+}
+
+// Number of straight segments used to approximate a curve, based on the
+// length of its control polygon.
+pub fn flatten_segments(points: &[Point]) -> usize {
+    let length: f32 = points
+        .windows(2)
+        .map(|pair| {
+            let dx = (pair[1].x - pair[0].x) as f32;
+            let dy = (pair[1].y - pair[0].y) as f32;
+            (dx * dx + dy * dy).sqrt()
+        })
+        .sum();
+
+    ((length / 4.0) as usize).clamp(8, 1000)
+}
+
+pub fn flatten_cubic_bezier(p_1: Point, p_2: Point, p_3: Point, p_4: Point) -> Vec<(f32, f32)> {
+    let segments = flatten_segments(&[p_1, p_2, p_3, p_4]);
+
+    (0..=segments)
+        .map(|i| {
+            let u = i as f32 / segments as f32;
+
+            let x_u = (1.0 - u).powi(3) * p_1.x as f32
+                + 3.0 * u * (1.0 - u).powi(2) * p_2.x as f32
+                + 3.0 * u.powi(2) * (1.0 - u) * p_3.x as f32
+                + u.powi(3) * p_4.x as f32;
+
+            let y_u = (1.0 - u).powi(3) * p_1.y as f32
+                + 3.0 * u * (1.0 - u).powi(2) * p_2.y as f32
+                + 3.0 * u.powi(2) * (1.0 - u) * p_3.y as f32
+                + u.powi(3) * p_4.y as f32;
+
+            (x_u, y_u)
+        })
+        .collect()
+}
+
+pub fn flatten_quadratic_bezier(p_1: Point, p_2: Point, p_3: Point) -> Vec<(f32, f32)> {
+    let segments = flatten_segments(&[p_1, p_2, p_3]);
+
+    (0..=segments)
+        .map(|i| {
+            let u = i as f32 / segments as f32;
+
+            let x_u = (1.0 - u).powi(2) * p_1.x as f32
+                + 2.0 * u * (1.0 - u) * p_2.x as f32
+                + u.powi(2) * p_3.x as f32;
+
+            let y_u = (1.0 - u).powi(2) * p_1.y as f32
+                + 2.0 * u * (1.0 - u) * p_2.y as f32
+                + u.powi(2) * p_3.y as f32;
+
+            (x_u, y_u)
+        })
+        .collect()
+}
+
+pub fn clip_polyline(points: &[(f32, f32)], rect: Rect) -> Vec<(Point, Point)> {
+    points
+        .windows(2)
+        .filter_map(|pair| liang_barsky(pair[0], pair[1], rect))
+        .map(|(a, b)| {
+            (
+                Point::new(a.0.round() as i32, a.1.round() as i32),
+                Point::new(b.0.round() as i32, b.1.round() as i32),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pixels 0 to 9 on both axes.
+    fn rect() -> Rect {
+        Rect::new(0, 0, 10, 10)
+    }
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4
+    }
+
+    // Same vertices, possibly starting somewhere else along the polygon.
+    fn same_polygon(a: &[(f32, f32)], b: &[(f32, f32)]) -> bool {
+        a.len() == b.len()
+            && (0..a.len()).any(|shift| (0..a.len()).all(|i| close(a[(i + shift) % a.len()], b[i])))
+    }
+
+    #[test]
+    fn cohen_sutherland_keeps_inside_line() {
+        let (a, b) = (Point::new(2, 3), Point::new(7, 8));
+        assert_eq!(cohen_sutherland(a, b, rect()), Some((a, b)));
+    }
+
+    #[test]
+    fn cohen_sutherland_rejects_outside_lines() {
+        let r = rect();
+        assert_eq!(
+            cohen_sutherland(Point::new(-5, 2), Point::new(-1, 8), r),
+            None
+        );
+        assert_eq!(
+            cohen_sutherland(Point::new(2, 10), Point::new(8, 20), r),
+            None
+        );
+        // Both ends outside different edges, passing just outside the corner.
+        assert_eq!(
+            cohen_sutherland(Point::new(-5, 3), Point::new(3, -5), r),
+            None
+        );
+    }
+
+    #[test]
+    fn cohen_sutherland_clips_crossing_lines() {
+        let r = rect();
+        assert_eq!(
+            cohen_sutherland(Point::new(-5, 4), Point::new(15, 4), r),
+            Some((Point::new(0, 4), Point::new(9, 4)))
+        );
+        assert_eq!(
+            cohen_sutherland(Point::new(5, 5), Point::new(5, 30), r),
+            Some((Point::new(5, 5), Point::new(5, 9)))
+        );
+        assert_eq!(
+            cohen_sutherland(Point::new(-5, -5), Point::new(15, 15), r),
+            Some((Point::new(0, 0), Point::new(9, 9)))
+        );
+    }
+
+    #[test]
+    fn cohen_sutherland_touches_corner() {
+        assert_eq!(
+            cohen_sutherland(Point::new(-3, 3), Point::new(3, -3), rect()),
+            Some((Point::new(0, 0), Point::new(0, 0)))
+        );
+    }
+
+    #[test]
+    fn liang_barsky_keeps_inside_line() {
+        let (a, b) = ((1.5, 2.0), (8.0, 7.25));
+        let (c, d) = liang_barsky(a, b, rect()).unwrap();
+        assert!(close(a, c) && close(b, d));
+    }
+
+    #[test]
+    fn liang_barsky_rejects_outside_lines() {
+        let r = rect();
+        assert!(liang_barsky((-1.0, -5.0), (-1.0, 15.0), r).is_none());
+        assert!(liang_barsky((-5.0, 3.0), (3.0, -5.0), r).is_none());
+        assert!(liang_barsky((12.0, 0.0), (20.0, 9.0), r).is_none());
+    }
+
+    #[test]
+    fn liang_barsky_clips_crossing_lines() {
+        let r = rect();
+        let (a, b) = liang_barsky((5.0, -5.0), (5.0, 15.0), r).unwrap();
+        assert!(close(a, (5.0, 0.0)) && close(b, (5.0, 9.0)));
+
+        let (a, b) = liang_barsky((-9.0, -9.0), (18.0, 18.0), r).unwrap();
+        assert!(close(a, (0.0, 0.0)) && close(b, (9.0, 9.0)));
+
+        // Reversed direction keeps the order of the ends.
+        let (a, b) = liang_barsky((15.0, 4.0), (-5.0, 4.0), r).unwrap();
+        assert!(close(a, (9.0, 4.0)) && close(b, (0.0, 4.0)));
+    }
+
+    #[test]
+    fn liang_barsky_touches_corner() {
+        let (a, b) = liang_barsky((-3.0, 3.0), (3.0, -3.0), rect()).unwrap();
+        assert!(close(a, (0.0, 0.0)) && close(b, (0.0, 0.0)));
+    }
+
+    #[test]
+    fn clip_polygon_keeps_inside_polygon() {
+        let square = [(2.0, 2.0), (6.0, 2.0), (6.0, 6.0), (2.0, 6.0)];
+        assert!(same_polygon(&clip_polygon(&square, rect()), &square));
+    }
+
+    #[test]
+    fn clip_polygon_drops_outside_polygon() {
+        let triangle = [(12.0, 0.0), (20.0, 0.0), (16.0, 8.0)];
+        assert!(clip_polygon(&triangle, rect()).is_empty());
+    }
+
+    #[test]
+    fn clip_polygon_cuts_crossing_polygon() {
+        let square = [(5.0, 5.0), (15.0, 5.0), (15.0, 15.0), (5.0, 15.0)];
+        let clipped = clip_polygon(&square, rect());
+        assert!(same_polygon(
+            &clipped,
+            &[(5.0, 5.0), (9.0, 5.0), (9.0, 9.0), (5.0, 9.0)]
+        ));
+    }
+
+    #[test]
+    fn clip_polygon_around_rect_gives_corners() {
+        let square = [(-5.0, -5.0), (15.0, -5.0), (15.0, 15.0), (-5.0, 15.0)];
+        let clipped = clip_polygon(&square, rect());
+        assert!(same_polygon(
+            &clipped,
+            &[(0.0, 0.0), (9.0, 0.0), (9.0, 9.0), (0.0, 9.0)]
+        ));
+    }
+}
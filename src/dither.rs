@@ -0,0 +1,382 @@
+// Reduces ARGB8888 pixels (read_pixels layout) to a small palette for
+// low-bit displays like e-ink and 1-bit LCD panels.
+
+use crate::hud;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DitherMethod {
+    None,
+    FloydSteinberg,
+    Atkinson,
+    // Side of the Bayer threshold matrix: 2, 4 or 8.
+    Bayer(u32),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Grayscale,
+    Color,
+}
+
+// What the dither action writes, cycled from the keyboard.
+const METHODS: [(&str, DitherMethod); 6] = [
+    ("Floyd-Steinberg", DitherMethod::FloydSteinberg),
+    ("Atkinson", DitherMethod::Atkinson),
+    ("Bayer 2x2", DitherMethod::Bayer(2)),
+    ("Bayer 4x4", DitherMethod::Bayer(4)),
+    ("Bayer 8x8", DitherMethod::Bayer(8)),
+    ("no dithering", DitherMethod::None),
+];
+
+// Color palettes come from median cut on the image itself.
+const PALETTES: [(&str, ColorMode, u8); 6] = [
+    ("1-bit gray", ColorMode::Grayscale, 1),
+    ("2-bit gray", ColorMode::Grayscale, 2),
+    ("4-bit gray", ColorMode::Grayscale, 4),
+    ("8-bit gray", ColorMode::Grayscale, 8),
+    ("4-bit color", ColorMode::Color, 4),
+    ("8-bit color", ColorMode::Color, 8),
+];
+
+pub struct DitherSettings {
+    method: usize,
+    palette: usize,
+}
+
+impl DitherSettings {
+    pub fn new() -> Self {
+        DitherSettings {
+            method: 0,
+            palette: 0,
+        }
+    }
+
+    pub fn next_method(&mut self) {
+        self.method = (self.method + 1) % METHODS.len();
+    }
+
+    pub fn next_palette(&mut self) {
+        self.palette = (self.palette + 1) % PALETTES.len();
+    }
+
+    pub fn bits(&self) -> u8 {
+        PALETTES[self.palette].2
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{}, {}", PALETTES[self.palette].0, METHODS[self.method].0)
+    }
+
+    pub fn reduce(&self, pixels: &Vec<u8>, width: u32, height: u32) -> IndexedImage {
+        let (_, mode, bits) = PALETTES[self.palette];
+        reduce(pixels, width, height, bits, mode, METHODS[self.method].1)
+    }
+}
+
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    pub bits: u8,
+    // 0x00RRGGBB entries, at most 2^bits of them.
+    pub palette: Vec<u32>,
+    pub indices: Vec<u8>,
+}
+
+pub fn reduce(
+    pixels: &Vec<u8>,
+    width: u32,
+    height: u32,
+    bits: u8,
+    mode: ColorMode,
+    method: DitherMethod,
+) -> IndexedImage {
+    let bits = match bits {
+        1 | 2 | 4 | 8 => bits,
+        _ => {
+            hud::status(format!("Unsupported bit depth {}, using 8", bits));
+            8
+        }
+    };
+    let colors = 1usize << bits;
+
+    let mut working: Vec<[f32; 3]> = pixels
+        .chunks(4)
+        .map(|p| {
+            let (r, g, b) = (p[2] as f32, p[1] as f32, p[0] as f32);
+            match mode {
+                ColorMode::Grayscale => {
+                    let l = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                    [l, l, l]
+                }
+                ColorMode::Color => [r, g, b],
+            }
+        })
+        .collect();
+
+    let palette = match mode {
+        ColorMode::Grayscale => grayscale_palette(colors),
+        ColorMode::Color => median_cut(&working, colors),
+    };
+
+    let indices = match method {
+        DitherMethod::None => working.iter().map(|c| nearest(&palette, *c)).collect(),
+        DitherMethod::FloydSteinberg => diffuse(
+            &mut working,
+            width,
+            height,
+            &palette,
+            &[(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)],
+        ),
+        // Atkinson only spreads 6/8 of the error, which keeps highlights clean.
+        DitherMethod::Atkinson => diffuse(
+            &mut working,
+            width,
+            height,
+            &palette,
+            &[
+                (1, 0, 1.0 / 8.0),
+                (2, 0, 1.0 / 8.0),
+                (-1, 1, 1.0 / 8.0),
+                (0, 1, 1.0 / 8.0),
+                (1, 1, 1.0 / 8.0),
+                (0, 2, 1.0 / 8.0),
+            ],
+        ),
+        DitherMethod::Bayer(size) => {
+            let matrix = bayer_matrix(size);
+            let side = (matrix.len() as f32).sqrt() as u32;
+            let levels = match mode {
+                ColorMode::Grayscale => colors as f32,
+                ColorMode::Color => (colors as f32).cbrt(),
+            };
+            let spread = 255.0 / (levels - 1.0).max(1.0);
+
+            working
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let x = i as u32 % width;
+                    let y = i as u32 / width;
+                    let threshold = matrix[((y % side) * side + x % side) as usize] - 0.5;
+                    let offset = threshold * spread;
+                    nearest(&palette, [c[0] + offset, c[1] + offset, c[2] + offset])
+                })
+                .collect()
+        }
+    };
+
+    IndexedImage {
+        width,
+        height,
+        bits,
+        palette: palette
+            .iter()
+            .map(|c| ((c[0] as u32) << 16) | ((c[1] as u32) << 8) | c[2] as u32)
+            .collect(),
+        indices,
+    }
+}
+
+pub fn grayscale_palette(colors: usize) -> Vec<[f32; 3]> {
+    (0..colors)
+        .map(|i| {
+            let l = (i as f32 * 255.0 / (colors - 1).max(1) as f32).round();
+            [l, l, l]
+        })
+        .collect()
+}
+
+// Splits the color box with the widest channel range at its median until
+// there are as many boxes as palette entries, then averages each box.
+pub fn median_cut(colors: &[[f32; 3]], count: usize) -> Vec<[f32; 3]> {
+    if colors.is_empty() {
+        return vec![[0.0, 0.0, 0.0]];
+    }
+
+    let mut boxes: Vec<Vec<[f32; 3]>> = vec![colors.to_vec()];
+
+    while boxes.len() < count {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+        let (index, channel, range) = match widest {
+            Some(widest) => widest,
+            None => break,
+        };
+        if range == 0.0 {
+            break;
+        }
+
+        let mut split = boxes.swap_remove(index);
+        split.sort_by(|a, b| a[channel].partial_cmp(&b[channel]).unwrap());
+        let upper = split.split_off(split.len() / 2);
+        boxes.push(split);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let mut sum = [0.0f32; 3];
+            for c in b {
+                for channel in 0..3 {
+                    sum[channel] += c[channel];
+                }
+            }
+            let n = b.len() as f32;
+            [
+                (sum[0] / n).round(),
+                (sum[1] / n).round(),
+                (sum[2] / n).round(),
+            ]
+        })
+        .collect()
+}
+
+fn widest_channel(colors: &[[f32; 3]]) -> (usize, f32) {
+    (0..3)
+        .map(|channel| {
+            let min = colors.iter().map(|c| c[channel]).fold(f32::MAX, f32::min);
+            let max = colors.iter().map(|c| c[channel]).fold(f32::MIN, f32::max);
+            (channel, max - min)
+        })
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap()
+}
+
+fn nearest(palette: &[[f32; 3]], color: [f32; 3]) -> u8 {
+    palette
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let d = (p[0] - color[0]).powi(2) + (p[1] - color[1]).powi(2) + (p[2] - color[2]).powi(2);
+            (i, d)
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(i, _)| i as u8)
+        .unwrap_or(0)
+}
+
+fn diffuse(
+    working: &mut Vec<[f32; 3]>,
+    width: u32,
+    height: u32,
+    palette: &[[f32; 3]],
+    weights: &[(i32, i32, f32)],
+) -> Vec<u8> {
+    let mut indices = vec![0u8; working.len()];
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let i = (y as u32 * width + x as u32) as usize;
+            let old = working[i];
+            let index = nearest(palette, old);
+            indices[i] = index;
+
+            let new = palette[index as usize];
+            let error = [old[0] - new[0], old[1] - new[1], old[2] - new[2]];
+
+            for (dx, dy, weight) in weights {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+
+                let n = (ny as u32 * width + nx as u32) as usize;
+                for channel in 0..3 {
+                    working[n][channel] += error[channel] * weight;
+                }
+            }
+        }
+    }
+
+    indices
+}
+
+// Normalized thresholds in 0.0..1.0, built recursively from the 2x2 matrix.
+pub fn bayer_matrix(size: u32) -> Vec<f32> {
+    let size = match size {
+        2 | 4 | 8 => size,
+        _ => 4,
+    };
+
+    let mut matrix: Vec<u32> = vec![0, 2, 3, 1];
+    let mut side = 2;
+
+    while side < size {
+        let next = side * 2;
+        let mut bigger = vec![0u32; (next * next) as usize];
+
+        for y in 0..side {
+            for x in 0..side {
+                let value = 4 * matrix[(y * side + x) as usize];
+                bigger[(y * next + x) as usize] = value;
+                bigger[(y * next + x + side) as usize] = value + 2;
+                bigger[((y + side) * next + x) as usize] = value + 3;
+                bigger[((y + side) * next + x + side) as usize] = value + 1;
+            }
+        }
+
+        matrix = bigger;
+        side = next;
+    }
+
+    let count = (side * side) as f32;
+    matrix.iter().map(|v| (*v as f32 + 0.5) / count).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bayer_2x2() {
+        assert_eq!(bayer_matrix(2), vec![0.125, 0.625, 0.875, 0.375]);
+    }
+
+    #[test]
+    fn bayer_4x4() {
+        let expected: Vec<f32> = [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5]
+            .iter()
+            .map(|v| (*v as f32 + 0.5) / 16.0)
+            .collect();
+        assert_eq!(bayer_matrix(4), expected);
+    }
+
+    #[test]
+    fn bayer_8x8_uses_every_threshold_once() {
+        let mut matrix = bayer_matrix(8);
+        matrix.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for (i, threshold) in matrix.iter().enumerate() {
+            assert_eq!(*threshold, (i as f32 + 0.5) / 64.0);
+        }
+    }
+
+    #[test]
+    fn every_setting_stays_within_its_palette() {
+        // A gray ramp, 16 pixels of ARGB8888 in read_pixels byte order.
+        let pixels: Vec<u8> = (0..16u8)
+            .flat_map(|i| [i * 16, i * 16, i * 16, 255])
+            .collect();
+        let mut settings = DitherSettings::new();
+
+        for _ in 0..PALETTES.len() {
+            for _ in 0..METHODS.len() {
+                let image = settings.reduce(&pixels, 4, 4);
+                assert_eq!(image.bits, settings.bits());
+                assert_eq!(image.indices.len(), 16);
+                assert!(image.palette.len() <= 1 << image.bits);
+                assert!(image.indices.iter().all(|i| (*i as usize) < image.palette.len()));
+                settings.next_method();
+            }
+            settings.next_palette();
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::filters;
+use crate::mask::{self, CoverageMask, Edge};
+use crate::numbers::NumberSeries;
+use crate::raster::PixelBuffer;
+
+// Sizes and offsets are in glyph units, they get multiplied by the series
+// scale and the shadow offset is turned by the series angle.
+#[derive(Clone, Copy)]
+pub struct DropShadow {
+    pub offset: (f32, f32),
+    pub blur_radius: f32,
+    pub color: u32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Outline {
+    pub width: f32,
+    pub color: u32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Glow {
+    pub radius: f32,
+    pub color: u32,
+}
+
+#[derive(Clone, Copy)]
+pub struct TextEffects {
+    pub shadow: Option<DropShadow>,
+    pub outline: Option<Outline>,
+    pub glow: Option<Glow>,
+}
+
+impl TextEffects {
+    pub fn none() -> Self {
+        TextEffects {
+            shadow: None,
+            outline: None,
+            glow: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shadow.is_none() && self.outline.is_none() && self.glow.is_none()
+    }
+}
+
+fn distance_to_segment(x: f32, y: f32, (a, b): &Edge) -> f32 {
+    let dx = b.0 - a.0;
+    let dy = b.1 - a.1;
+    let length_squared = dx * dx + dy * dy;
+
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((x - a.0) * dx + (y - a.1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+
+    let px = a.0 + t * dx - x;
+    let py = a.1 + t * dy - y;
+    (px * px + py * py).sqrt()
+}
+
+// Distance from every pixel center to the closest outline edge, 0 inside the
+// glyph. Only pixels within reach of the outline are computed, the rest are
+// left at f32::MAX.
+fn distance_field(edges: &[Edge], fill: &CoverageMask, reach: f32) -> Vec<f32> {
+    let mut distances = vec![f32::MAX; (fill.width * fill.height) as usize];
+    if edges.is_empty() {
+        return distances;
+    }
+
+    let min_x = edges.iter().map(|(a, b)| a.0.min(b.0)).fold(f32::MAX, f32::min) - reach;
+    let max_x = edges.iter().map(|(a, b)| a.0.max(b.0)).fold(f32::MIN, f32::max) + reach;
+    let min_y = edges.iter().map(|(a, b)| a.1.min(b.1)).fold(f32::MAX, f32::min) - reach;
+    let max_y = edges.iter().map(|(a, b)| a.1.max(b.1)).fold(f32::MIN, f32::max) + reach;
+
+    let x_range = (min_x.floor().max(0.0) as i32)..=(max_x.ceil().min(fill.width as f32 - 1.0) as i32);
+    let y_range = (min_y.floor().max(0.0) as i32)..=(max_y.ceil().min(fill.height as f32 - 1.0) as i32);
+
+    for y in y_range {
+        for x in x_range.clone() {
+            let index = (y as u32 * fill.width + x as u32) as usize;
+
+            if fill.get(x, y) == 255 {
+                distances[index] = 0.0;
+                continue;
+            }
+
+            let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+            distances[index] = edges
+                .iter()
+                .map(|edge| distance_to_segment(cx, cy, edge))
+                .fold(f32::MAX, f32::min);
+        }
+    }
+
+    distances
+}
+
+fn composite_coverage(target: &mut PixelBuffer, coverage: &[u8], color: u32) {
+    let alpha = color >> 24;
+
+    for y in 0..target.height as i32 {
+        for x in 0..target.width as i32 {
+            let c = coverage[(y as u32 * target.width + x as u32) as usize] as u32 * alpha / 255;
+            if c == 0 {
+                continue;
+            }
+
+            let blended = target.blend(x, y, color | 0xFF000000, c as u8);
+            target.set(x, y, blended);
+        }
+    }
+}
+
+impl NumberSeries {
+    // Composites the series' effects into the target. Glow and outline only
+    // cover what is outside the glyphs, the shadow covers the whole shape.
+    // The glyphs themselves are not drawn, Scene::render puts them on top.
+    pub fn render_effects(&self, target: &mut PixelBuffer) {
+        let effects = self.effects;
+        if effects.is_empty() {
+            return;
+        }
+
+        let (width, height) = (target.width, target.height);
+        let edges: Vec<Edge> = self.numbers.iter().flat_map(mask::number_edges).collect();
+        let fill = CoverageMask::from_edges(&edges, width, height);
+
+        let reach = [
+            effects.glow.map(|glow| glow.radius),
+            effects.outline.map(|outline| outline.width),
+        ]
+        .iter()
+        .flatten()
+        .fold(0.0f32, |a, b| a.max(*b))
+            * self.scale;
+        let distances = distance_field(&edges, &fill, reach + 1.0);
+
+        if let Some(glow) = effects.glow {
+            let radius = (glow.radius * self.scale).max(1.0);
+            let coverage: Vec<u8> = distances
+                .iter()
+                .zip(fill.coverage.iter())
+                .map(|(d, inside)| {
+                    let falloff = (1.0 - d / radius).max(0.0);
+                    (falloff * falloff * (255 - inside) as f32) as u8
+                })
+                .collect();
+            composite_coverage(target, &coverage, glow.color);
+        }
+
+        if let Some(shadow) = effects.shadow {
+            let (sin, cos) = self.angle.sin_cos();
+            let ox = (shadow.offset.0 * cos - shadow.offset.1 * sin) * self.scale;
+            let oy = (shadow.offset.0 * sin + shadow.offset.1 * cos) * self.scale;
+
+            let shifted: Vec<Edge> = edges
+                .iter()
+                .map(|(a, b)| ((a.0 + ox, a.1 + oy), (b.0 + ox, b.1 + oy)))
+                .collect();
+            let shadow_mask = CoverageMask::from_edges(&shifted, width, height);
+
+            // Blur the coverage through the alpha channel of a scratch buffer.
+            let mut scratch: Vec<u8> = shadow_mask
+                .coverage
+                .iter()
+                .flat_map(|c| [0, 0, 0, *c])
+                .collect();
+            let kernel = filters::gaussian_kernel(shadow.blur_radius * self.scale / 2.0);
+            filters::convolve_separable(&mut scratch, width, height, &kernel, &[3]);
+
+            let coverage: Vec<u8> = scratch.chunks(4).map(|pixel| pixel[3]).collect();
+            composite_coverage(target, &coverage, shadow.color);
+        }
+
+        if let Some(outline) = effects.outline {
+            let outline_width = outline.width * self.scale;
+            let coverage: Vec<u8> = distances
+                .iter()
+                .zip(fill.coverage.iter())
+                .map(|(d, inside)| {
+                    ((outline_width - d + 0.5).clamp(0.0, 1.0) * (255 - inside) as f32) as u8
+                })
+                .collect();
+            composite_coverage(target, &coverage, outline.color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series() -> NumberSeries {
+        NumberSeries::new(20, 20, String::from("1"), 1.0, 0.0)
+    }
+
+    fn shadow_only() -> TextEffects {
+        TextEffects {
+            shadow: Some(DropShadow {
+                offset: (10.0, 0.0),
+                blur_radius: 0.0,
+                color: 0xFF000000,
+            }),
+            outline: None,
+            glow: None,
+        }
+    }
+
+    // Center of mass of the pixels the effects darkened.
+    fn darkened_center(series: &NumberSeries) -> (f32, f32) {
+        let mut target = PixelBuffer::new(200, 200, 0xFFFFFFFF);
+        series.render_effects(&mut target);
+
+        let (mut sum_x, mut sum_y, mut count) = (0.0, 0.0, 0.0);
+        for y in 0..target.height as i32 {
+            for x in 0..target.width as i32 {
+                if target.get(x, y) != 0xFFFFFFFF {
+                    sum_x += x as f32;
+                    sum_y += y as f32;
+                    count += 1.0;
+                }
+            }
+        }
+        assert!(count > 0.0);
+        (sum_x / count, sum_y / count)
+    }
+
+    #[test]
+    fn distance_is_zero_on_ink_and_grows_away_from_it() {
+        // A 10x10 square with its left edge at x = 10.
+        let edges: Vec<Edge> = vec![
+            ((10.0, 10.0), (20.0, 10.0)),
+            ((20.0, 10.0), (20.0, 20.0)),
+            ((20.0, 20.0), (10.0, 20.0)),
+            ((10.0, 20.0), (10.0, 10.0)),
+        ];
+        let fill = CoverageMask::from_edges(&edges, 40, 40);
+        let distances = distance_field(&edges, &fill, 8.0);
+        let at = |x: u32, y: u32| distances[(y * 40 + x) as usize];
+
+        assert_eq!(at(15, 15), 0.0);
+        for x in 3..10 {
+            assert!(at(x - 1, 15) > at(x, 15));
+            assert!((at(x, 15) - (9.5 - x as f32)).abs() < 0.01);
+        }
+        assert_eq!(at(0, 15), f32::MAX);
+    }
+
+    #[test]
+    fn shadow_follows_the_series_transform() {
+        let mut series = series();
+        series.effects = shadow_only();
+        let (x, y) = darkened_center(&series);
+
+        series.set_transform(60, 50, 1.0, 0.0);
+        let (moved_x, moved_y) = darkened_center(&series);
+        assert!((moved_x - x - 40.0).abs() < 0.5);
+        assert!((moved_y - y - 30.0).abs() < 0.5);
+
+        // Turned a quarter, the offset points down instead of right.
+        let mut turned = series.clone();
+        turned.set_transform(60, 50, 1.0, std::f32::consts::FRAC_PI_2);
+        let mut plain = series.clone();
+        plain.set_transform(60, 50, 1.0, std::f32::consts::FRAC_PI_2);
+        plain.effects.shadow.as_mut().unwrap().offset = (0.0, 0.0);
+        let (shadow_x, shadow_y) = darkened_center(&turned);
+        let (glyph_x, glyph_y) = darkened_center(&plain);
+        assert!((shadow_x - glyph_x).abs() < 0.5);
+        assert!((shadow_y - glyph_y - 10.0).abs() < 0.5);
+    }
+}
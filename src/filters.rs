@@ -0,0 +1,368 @@
+use crate::hud;
+use crate::raster::PixelBuffer;
+
+// Filters work on the ARGB8888 bytes returned by canvas.read_pixels, i.e.
+// B, G, R, A per pixel. Blurs and morphology touch alpha too so they can be
+// used on transparent layers, the rest leave it alone.
+pub enum Filter {
+    GaussianBlur(f32),
+    BoxBlur(u32),
+    Sharpen,
+    // Square kernel, row major, with an odd side length.
+    Convolution(Vec<f32>),
+    SobelEdges,
+    Dilate(u32),
+    Erode(u32),
+    Threshold(u8),
+    Invert,
+}
+
+const COLOR_CHANNELS: [usize; 3] = [0, 1, 2];
+const ALL_CHANNELS: [usize; 4] = [0, 1, 2, 3];
+
+// What the chain can be built from, in the order filter_next goes through
+// them.
+fn presets() -> Vec<Filter> {
+    vec![
+        Filter::GaussianBlur(2.0),
+        Filter::BoxBlur(2),
+        Filter::Sharpen,
+        // Emboss.
+        Filter::Convolution(vec![-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0]),
+        Filter::SobelEdges,
+        Filter::Dilate(1),
+        Filter::Erode(1),
+        Filter::Threshold(128),
+        Filter::Invert,
+    ]
+}
+
+impl Filter {
+    pub fn describe(&self) -> String {
+        match self {
+            Filter::GaussianBlur(sigma) => format!("gaussian blur {}", sigma),
+            Filter::BoxBlur(radius) => format!("box blur {}", radius),
+            Filter::Sharpen => String::from("sharpen"),
+            Filter::Convolution(kernel) => {
+                let side = (kernel.len() as f32).sqrt() as usize;
+                format!("convolution {}x{}", side, side)
+            }
+            Filter::SobelEdges => String::from("edges"),
+            Filter::Dilate(radius) => format!("dilate {}", radius),
+            Filter::Erode(radius) => format!("erode {}", radius),
+            Filter::Threshold(level) => format!("threshold {}", level),
+            Filter::Invert => String::from("invert"),
+        }
+    }
+
+    pub fn apply(&self, pixels: &mut Vec<u8>, width: u32, height: u32) {
+        match self {
+            Filter::GaussianBlur(sigma) => {
+                let kernel = gaussian_kernel(*sigma);
+                convolve_separable(pixels, width, height, &kernel, &ALL_CHANNELS);
+            }
+            Filter::BoxBlur(radius) => {
+                let size = (2 * radius + 1) as usize;
+                let kernel = vec![1.0 / size as f32; size];
+                convolve_separable(pixels, width, height, &kernel, &ALL_CHANNELS);
+            }
+            Filter::Sharpen => {
+                let kernel = vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0];
+                convolve(pixels, width, height, &kernel, &COLOR_CHANNELS);
+            }
+            Filter::Convolution(kernel) => convolve(pixels, width, height, kernel, &COLOR_CHANNELS),
+            Filter::SobelEdges => sobel(pixels, width, height),
+            Filter::Dilate(radius) => morphology(pixels, width, height, *radius, u8::max),
+            Filter::Erode(radius) => morphology(pixels, width, height, *radius, u8::min),
+            Filter::Threshold(level) => {
+                for pixel in pixels.chunks_mut(4) {
+                    let value = if luminance(pixel) >= *level as f32 { 255 } else { 0 };
+                    pixel[0] = value;
+                    pixel[1] = value;
+                    pixel[2] = value;
+                }
+            }
+            Filter::Invert => {
+                for pixel in pixels.chunks_mut(4) {
+                    for channel in COLOR_CHANNELS {
+                        pixel[channel] = 255 - pixel[channel];
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Filters run over screenshots and exports, first to last. Built from the
+// keyboard: one preset is picked at a time and added to the end.
+pub struct FilterChain {
+    pub filters: Vec<Filter>,
+    preset: usize,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        FilterChain {
+            filters: Vec::new(),
+            preset: 0,
+        }
+    }
+
+    pub fn next_preset(&mut self) {
+        self.preset = (self.preset + 1) % presets().len();
+    }
+
+    pub fn preset(&self) -> Filter {
+        presets().swap_remove(self.preset)
+    }
+
+    pub fn add(&mut self) {
+        self.filters.push(self.preset());
+    }
+
+    pub fn remove_last(&mut self) -> Option<Filter> {
+        self.filters.pop()
+    }
+
+    pub fn describe(&self) -> String {
+        if self.filters.is_empty() {
+            return String::from("none");
+        }
+        let names: Vec<String> = self.filters.iter().map(Filter::describe).collect();
+        names.join(", ")
+    }
+}
+
+pub fn apply_filters(pixels: &mut Vec<u8>, width: u32, height: u32, filters: &[Filter]) {
+    for filter in filters {
+        filter.apply(pixels, width, height);
+    }
+}
+
+impl PixelBuffer {
+    pub fn apply_filters(&mut self, filters: &[Filter]) {
+        apply_filters(&mut self.pixels, self.width, self.height, filters);
+    }
+}
+
+fn luminance(pixel: &[u8]) -> f32 {
+    0.0722 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.2126 * pixel[2] as f32
+}
+
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+
+    let radius = (sigma * 3.0).ceil() as i32;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+
+    let sum: f32 = kernel.iter().sum();
+    for k in kernel.iter_mut() {
+        *k /= sum;
+    }
+
+    kernel
+}
+
+fn sample_index(x: i32, y: i32, width: u32, height: u32) -> usize {
+    let x = x.clamp(0, width as i32 - 1) as u32;
+    let y = y.clamp(0, height as i32 - 1) as u32;
+    ((y * width + x) * 4) as usize
+}
+
+// Runs the 1D kernel horizontally then vertically, edges clamped.
+pub fn convolve_separable(
+    pixels: &mut Vec<u8>,
+    width: u32,
+    height: u32,
+    kernel: &[f32],
+    channels: &[usize],
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let radius = (kernel.len() / 2) as i32;
+
+    for (dx, dy) in [(1, 0), (0, 1)] {
+        let source = pixels.clone();
+
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let index = sample_index(x, y, width, height);
+
+                for &channel in channels {
+                    let mut sum = 0.0;
+                    for (k, weight) in kernel.iter().enumerate() {
+                        let offset = k as i32 - radius;
+                        let sample = sample_index(x + offset * dx, y + offset * dy, width, height);
+                        sum += source[sample + channel] as f32 * weight;
+                    }
+                    pixels[index + channel] = sum.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+pub fn convolve(pixels: &mut Vec<u8>, width: u32, height: u32, kernel: &[f32], channels: &[usize]) {
+    let size = (kernel.len() as f32).sqrt() as usize;
+    if size * size != kernel.len() || size % 2 == 0 {
+        hud::status(String::from(
+            "Convolution kernel must be square with an odd side",
+        ));
+        return;
+    }
+
+    let radius = (size / 2) as i32;
+    let source = pixels.clone();
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let index = sample_index(x, y, width, height);
+
+            for &channel in channels {
+                let mut sum = 0.0;
+                for ky in 0..size as i32 {
+                    for kx in 0..size as i32 {
+                        let sample = sample_index(x + kx - radius, y + ky - radius, width, height);
+                        sum += source[sample + channel] as f32 * kernel[(ky * size as i32 + kx) as usize];
+                    }
+                }
+                pixels[index + channel] = sum.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+fn sobel(pixels: &mut Vec<u8>, width: u32, height: u32) {
+    let gray: Vec<f32> = pixels.chunks(4).map(luminance).collect();
+    let at = |x: i32, y: i32| gray[sample_index(x, y, width, height) / 4];
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let gx = -at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1)
+                + at(x + 1, y - 1)
+                + 2.0 * at(x + 1, y)
+                + at(x + 1, y + 1);
+            let gy = -at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1)
+                + at(x - 1, y + 1)
+                + 2.0 * at(x, y + 1)
+                + at(x + 1, y + 1);
+
+            let magnitude = (gx * gx + gy * gy).sqrt().clamp(0.0, 255.0) as u8;
+            let index = sample_index(x, y, width, height);
+            for channel in COLOR_CHANNELS {
+                pixels[index + channel] = magnitude;
+            }
+        }
+    }
+}
+
+fn morphology(pixels: &mut Vec<u8>, width: u32, height: u32, radius: u32, pick: fn(u8, u8) -> u8) {
+    let radius = radius as i32;
+
+    // Square structuring element, so it separates like the blurs do.
+    for (dx, dy) in [(1, 0), (0, 1)] {
+        let source = pixels.clone();
+
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let index = sample_index(x, y, width, height);
+
+                for channel in ALL_CHANNELS {
+                    let mut value = source[index + channel];
+                    for offset in -radius..=radius {
+                        let sample = sample_index(x + offset * dx, y + offset * dy, width, height);
+                        value = pick(value, source[sample + channel]);
+                    }
+                    pixels[index + channel] = value;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 8;
+
+    fn flat(color: u32) -> Vec<u8> {
+        PixelBuffer::new(SIZE, SIZE, color).pixels
+    }
+
+    // Black on the left half, white on the right.
+    fn split() -> Vec<u8> {
+        let mut buffer = PixelBuffer::new(SIZE, SIZE, 0xFF000000);
+        for y in 0..SIZE as i32 {
+            for x in SIZE as i32 / 2..SIZE as i32 {
+                buffer.set(x, y, 0xFFFFFFFF);
+            }
+        }
+        buffer.pixels
+    }
+
+    fn applied(filter: Filter, mut pixels: Vec<u8>) -> Vec<u8> {
+        filter.apply(&mut pixels, SIZE, SIZE);
+        pixels
+    }
+
+    #[test]
+    fn blurs_leave_a_flat_image_alone() {
+        let image = flat(0xFF4080C0);
+        assert_eq!(applied(Filter::GaussianBlur(1.5), image.clone()), image);
+        assert_eq!(applied(Filter::BoxBlur(2), image.clone()), image);
+    }
+
+    #[test]
+    fn inverting_twice_gives_the_image_back() {
+        let mut image = split();
+        image[0..4].copy_from_slice(&0x80123456u32.to_le_bytes());
+        let once = applied(Filter::Invert, image.clone());
+        assert!(once != image);
+        assert_eq!(applied(Filter::Invert, once), image);
+    }
+
+    #[test]
+    fn threshold_leaves_two_values() {
+        let mut image = PixelBuffer::new(SIZE, SIZE, 0xFF000000);
+        for (i, pixel) in image.pixels.chunks_mut(4).enumerate() {
+            let gray = (i * 4) as u8;
+            pixel[..3].copy_from_slice(&[gray, gray, gray]);
+        }
+        let thresholded = applied(Filter::Threshold(128), image.pixels);
+        for pixel in thresholded.chunks(4) {
+            assert!(pixel[..3] == [0, 0, 0] || pixel[..3] == [255, 255, 255]);
+        }
+        assert!(thresholded.chunks(4).any(|pixel| pixel[0] == 0));
+        assert!(thresholded.chunks(4).any(|pixel| pixel[0] == 255));
+    }
+
+    #[test]
+    fn edges_of_a_flat_image_are_zero() {
+        let edges = applied(Filter::SobelEdges, flat(0xFF808080));
+        assert!(edges.chunks(4).all(|pixel| pixel[..3] == [0, 0, 0]));
+
+        let edges = applied(Filter::SobelEdges, split());
+        let at = |x: u32| edges[((SIZE * 3 + x) * 4) as usize];
+        assert_eq!(at(0), 0);
+        assert_eq!(at(SIZE / 2), 255);
+    }
+
+    #[test]
+    fn chain_adds_the_current_preset() {
+        let mut chain = FilterChain::new();
+        assert_eq!(chain.describe(), "none");
+        chain.add();
+        chain.next_preset();
+        chain.add();
+        assert_eq!(chain.describe(), "gaussian blur 2, box blur 2");
+        assert!(chain.remove_last().is_some());
+        assert_eq!(chain.filters.len(), 1);
+    }
+}
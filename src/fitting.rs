@@ -0,0 +1,313 @@
+// Fits a chain of cubic Beziers through sampled points, after Philip J.
+// Schneider, "An Algorithm for Automatically Fitting Digitized Curves"
+// (Graphics Gems, 1990). Strokes are first cut at sharp corners so each
+// piece can be fitted with continuous tangents.
+
+use crate::bezier::{
+    add, derivative, dot, evaluate, length, mul, normalize, second_derivative, sub, Cubic, Vec2,
+};
+
+const MAX_REPARAMETERIZE: usize = 4;
+
+// Turns sharper than this, measured over a few samples, split the stroke.
+const CORNER_ANGLE: f32 = 70.0 * 0.017453293;
+const CORNER_SPAN: usize = 3;
+
+pub fn fit_stroke(samples: &[Vec2], tolerance: f32) -> Vec<Cubic> {
+    let points = smooth(&dedupe(samples));
+    if points.len() < 2 {
+        return Vec::new();
+    }
+
+    let mut curves = Vec::new();
+    let corners = find_corners(&points);
+
+    for pair in corners.windows(2) {
+        let piece = &points[pair[0]..=pair[1]];
+        if piece.len() < 2 {
+            continue;
+        }
+
+        let tangent_1 = normalize(sub(piece[1], piece[0]));
+        let tangent_2 = normalize(sub(piece[piece.len() - 2], piece[piece.len() - 1]));
+        fit_cubic(piece, tangent_1, tangent_2, tolerance, &mut curves);
+    }
+
+    curves
+}
+
+fn dedupe(samples: &[Vec2]) -> Vec<Vec2> {
+    let mut points: Vec<Vec2> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        if points.last() != Some(sample) {
+            points.push(*sample);
+        }
+    }
+    points
+}
+
+// Light moving average against mouse jitter, the ends stay where they were.
+fn smooth(points: &[Vec2]) -> Vec<Vec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut smoothed = vec![points[0]];
+    for window in points.windows(3) {
+        smoothed.push(mul(add(add(window[0], window[1]), window[2]), 1.0 / 3.0));
+    }
+    smoothed.push(points[points.len() - 1]);
+    smoothed
+}
+
+// Indices the stroke is cut at, always including both ends.
+fn find_corners(points: &[Vec2]) -> Vec<usize> {
+    let last = points.len() - 1;
+    let mut corners = vec![0];
+
+    let mut i = CORNER_SPAN;
+    while i + CORNER_SPAN <= last {
+        let incoming = sub(points[i], points[i - CORNER_SPAN]);
+        let outgoing = sub(points[i + CORNER_SPAN], points[i]);
+        let cos = dot(normalize(incoming), normalize(outgoing));
+
+        if cos < CORNER_ANGLE.cos() {
+            corners.push(i);
+            i += CORNER_SPAN;
+        } else {
+            i += 1;
+        }
+    }
+
+    corners.push(last);
+    corners
+}
+
+fn fit_cubic(
+    points: &[Vec2],
+    tangent_1: Vec2,
+    tangent_2: Vec2,
+    tolerance: f32,
+    curves: &mut Vec<Cubic>,
+) {
+    let first = points[0];
+    let last = points[points.len() - 1];
+
+    if points.len() == 2 {
+        let distance = length(sub(last, first)) / 3.0;
+        curves.push([
+            first,
+            add(first, mul(tangent_1, distance)),
+            add(last, mul(tangent_2, distance)),
+            last,
+        ]);
+        return;
+    }
+
+    let mut u = chord_length_parameterize(points);
+    let mut curve = generate_bezier(points, &u, tangent_1, tangent_2);
+    let (mut max_error, mut split) = max_error(points, &curve, &u);
+
+    if max_error < tolerance * tolerance {
+        curves.push(curve);
+        return;
+    }
+
+    // Close enough to be worth improving the parameterization first. Chord
+    // length is a poor start when the samples are unevenly spaced along the
+    // curve, so this allows a few tolerances before giving up on one piece.
+    if max_error < 16.0 * tolerance * tolerance {
+        for _ in 0..MAX_REPARAMETERIZE {
+            u = reparameterize(points, &u, &curve);
+            curve = generate_bezier(points, &u, tangent_1, tangent_2);
+            let (error, index) = self::max_error(points, &curve, &u);
+            max_error = error;
+            split = index;
+
+            if max_error < tolerance * tolerance {
+                curves.push(curve);
+                return;
+            }
+        }
+    }
+
+    let center = normalize(sub(points[split - 1], points[split + 1]));
+    fit_cubic(&points[..=split], tangent_1, center, tolerance, curves);
+    fit_cubic(
+        &points[split..],
+        mul(center, -1.0),
+        tangent_2,
+        tolerance,
+        curves,
+    );
+}
+
+fn chord_length_parameterize(points: &[Vec2]) -> Vec<f32> {
+    let mut u = vec![0.0];
+    for i in 1..points.len() {
+        u.push(u[i - 1] + length(sub(points[i], points[i - 1])));
+    }
+
+    let total = u[u.len() - 1];
+    if total > 0.0 {
+        for value in u.iter_mut() {
+            *value /= total;
+        }
+    }
+    u
+}
+
+// Least squares fit of the two inner control point distances along the
+// fixed end tangents.
+fn generate_bezier(points: &[Vec2], u: &[f32], tangent_1: Vec2, tangent_2: Vec2) -> Cubic {
+    let first = points[0];
+    let last = points[points.len() - 1];
+
+    let mut c = [[0.0f32; 2]; 2];
+    let mut x = [0.0f32; 2];
+
+    for (point, &t) in points.iter().zip(u.iter()) {
+        let mt = 1.0 - t;
+        let a_1 = mul(tangent_1, 3.0 * mt * mt * t);
+        let a_2 = mul(tangent_2, 3.0 * mt * t * t);
+
+        c[0][0] += dot(a_1, a_1);
+        c[0][1] += dot(a_1, a_2);
+        c[1][1] += dot(a_2, a_2);
+
+        let on_chord = add(
+            mul(first, mt * mt * mt + 3.0 * mt * mt * t),
+            mul(last, 3.0 * mt * t * t + t * t * t),
+        );
+        let rest = sub(*point, on_chord);
+        x[0] += dot(a_1, rest);
+        x[1] += dot(a_2, rest);
+    }
+    c[1][0] = c[0][1];
+
+    let det_c0_c1 = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let det_c0_x = c[0][0] * x[1] - c[1][0] * x[0];
+    let det_x_c1 = x[0] * c[1][1] - x[1] * c[0][1];
+
+    let (alpha_1, alpha_2) = if det_c0_c1 == 0.0 {
+        (0.0, 0.0)
+    } else {
+        (det_x_c1 / det_c0_c1, det_c0_x / det_c0_c1)
+    };
+
+    // Degenerate or backwards handles fall back to the Wu/Barsky heuristic.
+    let segment_length = length(sub(last, first));
+    let epsilon = 1.0e-6 * segment_length;
+    if alpha_1 < epsilon || alpha_2 < epsilon {
+        let distance = segment_length / 3.0;
+        return [
+            first,
+            add(first, mul(tangent_1, distance)),
+            add(last, mul(tangent_2, distance)),
+            last,
+        ];
+    }
+
+    [
+        first,
+        add(first, mul(tangent_1, alpha_1)),
+        add(last, mul(tangent_2, alpha_2)),
+        last,
+    ]
+}
+
+// One Newton-Raphson step per sample towards its closest point on the curve.
+fn reparameterize(points: &[Vec2], u: &[f32], curve: &Cubic) -> Vec<f32> {
+    points
+        .iter()
+        .zip(u.iter())
+        .map(|(point, &t)| {
+            let difference = sub(evaluate(curve, t), *point);
+            let d_1 = derivative(curve, t);
+            let d_2 = second_derivative(curve, t);
+
+            let numerator = dot(difference, d_1);
+            let denominator = dot(d_1, d_1) + dot(difference, d_2);
+
+            if denominator == 0.0 {
+                t
+            } else {
+                (t - numerator / denominator).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
+
+// Largest squared distance between a sample and its point on the curve.
+fn max_error(points: &[Vec2], curve: &Cubic, u: &[f32]) -> (f32, usize) {
+    let mut max = 0.0;
+    let mut split = points.len() / 2;
+
+    for i in 1..points.len() - 1 {
+        let difference = sub(evaluate(curve, u[i]), points[i]);
+        let distance = dot(difference, difference);
+        if distance >= max {
+            max = distance;
+            split = i;
+        }
+    }
+
+    (max, split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier::closest_point;
+
+    const ARCH: Cubic = [(0.0, 0.0), (30.0, 80.0), (120.0, 90.0), (150.0, 10.0)];
+
+    fn distance_to(curves: &[Cubic], point: Vec2) -> f32 {
+        curves
+            .iter()
+            .map(|curve| closest_point(curve, point).2)
+            .fold(f32::MAX, f32::min)
+    }
+
+    #[test]
+    fn samples_of_one_cubic_fit_one_segment() {
+        let samples: Vec<Vec2> = (0..=60).map(|i| evaluate(&ARCH, i as f32 / 60.0)).collect();
+        let curves = fit_stroke(&samples, 2.0);
+
+        assert_eq!(curves.len(), 1);
+        assert_eq!(curves[0][0], ARCH[0]);
+        assert_eq!(curves[0][3], ARCH[3]);
+        for sample in &samples {
+            assert!(distance_to(&curves, *sample) < 2.0);
+        }
+    }
+
+    #[test]
+    fn an_l_shaped_stroke_is_split_at_the_corner() {
+        let mut samples: Vec<Vec2> = (0..=20).map(|i| (0.0, i as f32 * 5.0)).collect();
+        samples.extend((1..=20).map(|i| (i as f32 * 5.0, 100.0)));
+        let curves = fit_stroke(&samples, 1.0);
+
+        assert!(curves.len() >= 2);
+        let corner_ends = curves
+            .iter()
+            .filter(|curve| length(sub(curve[3], (0.0, 100.0))) < 5.0)
+            .count();
+        assert_eq!(corner_ends, 1);
+        assert_eq!(curves[curves.len() - 1][3], (100.0, 100.0));
+    }
+
+    #[test]
+    fn too_few_samples_give_no_curve_or_a_line() {
+        assert!(fit_stroke(&[], 1.0).is_empty());
+        assert!(fit_stroke(&[(4.0, 4.0)], 1.0).is_empty());
+        assert!(fit_stroke(&[(4.0, 4.0), (4.0, 4.0)], 1.0).is_empty());
+
+        let curves = fit_stroke(&[(0.0, 0.0), (30.0, 0.0)], 1.0);
+        assert_eq!(curves.len(), 1);
+        for point in curves[0] {
+            assert_eq!(point.1, 0.0);
+        }
+        assert_eq!(curves[0][3], (30.0, 0.0));
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::OnceLock;
+
+// How color channels are mixed. Linear does blending, coverage and gradient
+// interpolation in linear light and only sRGB encodes colors when they are
+// stored. Raw is the old 8-bit channel math, kept around for comparison.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Blending {
+    Linear,
+    Raw,
+}
+
+static DECODE_TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+
+pub fn srgb_to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let c = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+
+    (c * 255.0).round() as u8
+}
+
+impl Blending {
+    pub fn toggled(self) -> Blending {
+        match self {
+            Blending::Linear => Blending::Raw,
+            Blending::Raw => Blending::Linear,
+        }
+    }
+
+    // Channel value as used for blending math, in 0.0..=1.0.
+    pub fn decode(self, channel: u8) -> f32 {
+        match self {
+            Blending::Linear => {
+                let table = DECODE_TABLE.get_or_init(|| {
+                    let mut table = [0.0; 256];
+                    for (i, value) in table.iter_mut().enumerate() {
+                        *value = srgb_to_linear(i as u8);
+                    }
+                    table
+                });
+                table[channel as usize]
+            }
+            Blending::Raw => channel as f32 / 255.0,
+        }
+    }
+
+    pub fn encode(self, value: f32) -> u8 {
+        match self {
+            Blending::Linear => linear_to_srgb(value),
+            Blending::Raw => (value.clamp(0.0, 1.0) * 255.0).round() as u8,
+        }
+    }
+
+    // Interpolates a color channel, t = 0 gives from and t = 1 gives to.
+    pub fn mix_channel(self, from: u8, to: u8, t: f32) -> u8 {
+        let a = self.decode(from);
+        let b = self.decode(to);
+        self.encode(a + (b - a) * t)
+    }
+
+    // Alpha is coverage, not light, so it is always mixed linearly.
+    pub fn mix_colors(self, from: u32, to: u32, t: f32) -> u32 {
+        let channel = |color: u32, shift: u32| ((color >> shift) & 0xFF) as u8;
+
+        let a_from = channel(from, 24) as f32;
+        let a_to = channel(to, 24) as f32;
+        let alpha = (a_from + (a_to - a_from) * t).round().clamp(0.0, 255.0) as u32;
+
+        let r = self.mix_channel(channel(from, 16), channel(to, 16), t) as u32;
+        let g = self.mix_channel(channel(from, 8), channel(to, 8), t) as u32;
+        let b = self.mix_channel(channel(from, 0), channel(to, 0), t) as u32;
+
+        (alpha << 24) | (r << 16) | (g << 8) | b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips() {
+        for channel in 0..=255u8 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(channel)), channel);
+            for blending in [Blending::Linear, Blending::Raw] {
+                assert_eq!(blending.encode(blending.decode(channel)), channel);
+            }
+        }
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+    }
+
+    #[test]
+    fn black_and_white_midpoint() {
+        // Half the light of white is much brighter than half the code value.
+        let linear = Blending::Linear.mix_colors(0xFF000000, 0xFFFFFFFF, 0.5);
+        assert_eq!(linear, 0xFFBCBCBC);
+        let raw = Blending::Raw.mix_colors(0xFF000000, 0xFFFFFFFF, 0.5);
+        assert_eq!(raw, 0xFF808080);
+    }
+
+    #[test]
+    fn mix_colors_ends_and_alpha() {
+        for blending in [Blending::Linear, Blending::Raw] {
+            assert_eq!(blending.mix_colors(0xFF102030, 0x80C0B0A0, 0.0), 0xFF102030);
+            assert_eq!(blending.mix_colors(0xFF102030, 0x80C0B0A0, 1.0), 0x80C0B0A0);
+            // Alpha goes in a straight line whatever the blending.
+            let quarter = blending.mix_colors(0x00000000, 0xFF000000, 0.25);
+            assert_eq!(quarter >> 24, 0x40);
+        }
+
+        let linear = Blending::Linear.mix_colors(0xFFFF0000, 0xFF00FF00, 0.5);
+        let raw = Blending::Raw.mix_colors(0xFFFF0000, 0xFF00FF00, 0.5);
+        assert_eq!(raw, 0xFF808000);
+        assert!((linear >> 16) & 0xFF > 0x80);
+        assert!((linear >> 8) & 0xFF > 0x80);
+        assert_eq!(Blending::Linear.toggled(), Blending::Raw);
+    }
+}
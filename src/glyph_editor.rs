@@ -0,0 +1,479 @@
+use std::io;
+
+use sdl2::gfx::primitives::DrawRenderer;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::glyph_file;
+use crate::graphics::{self, CubicBezierCurve, Line};
+use crate::hud;
+use crate::numbers::{self, Number, DEFAULT_NUM_HEIGHT, DEFAULT_NUM_WIDTH};
+
+pub const GLYPH_PATH: &str = "glyphs.txt";
+
+// The glyph is shown ZOOM times its size with its origin at ORIGIN_X,
+// ORIGIN_Y on screen, previews at PREVIEW_SCALES stack up to the right.
+const ZOOM: i32 = 4;
+const ORIGIN_X: i32 = 40;
+const ORIGIN_Y: i32 = 40;
+const GRID_STEP: i32 = 10;
+// Curves in the built in digits overshoot the em box a little.
+const OVERSHOOT: i32 = 20;
+const PREVIEW_X: i32 = 460;
+const PREVIEW_SCALES: [f32; 3] = [0.25, 0.5, 1.0];
+const POINT_RADIUS: i32 = 6;
+
+#[derive(Clone, Copy, PartialEq)]
+enum GlyphPoint {
+    Line(usize, usize),
+    Curve(usize, usize),
+    Center,
+}
+
+// What clicking on empty space adds.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line,
+    Cubic,
+}
+
+impl Segment {
+    fn points_needed(&self) -> usize {
+        match self {
+            Segment::Line => 2,
+            Segment::Cubic => 4,
+        }
+    }
+}
+
+fn to_screen(point: Point) -> Point {
+    Point::new(ORIGIN_X + point.x * ZOOM, ORIGIN_Y + point.y * ZOOM)
+}
+
+fn to_glyph(point: Point) -> Point {
+    let x = (point.x - ORIGIN_X) as f32 / ZOOM as f32;
+    let y = (point.y - ORIGIN_Y) as f32 / ZOOM as f32;
+    Point::new(x.round() as i32, y.round() as i32)
+}
+
+fn digit_key(keycode: Keycode) -> Option<char> {
+    let name = keycode.name();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_digit() => Some(c),
+        _ => None,
+    }
+}
+
+// Edits one digit of the font in em box units. Clicking a point grabs it,
+// clicking elsewhere collects points for a new segment.
+pub struct GlyphEditor {
+    pub glyph: Number,
+    pub segment: Segment,
+    pending: Vec<Point>,
+    selected: Option<GlyphPoint>,
+    dragging: bool,
+    cursor: Point,
+    // Whether glyph differs from the font's.
+    modified: bool,
+    // Modified glyphs put aside while another one is open, saved together.
+    drafts: Vec<Number>,
+    // Set when closing was refused because of unsaved edits.
+    close_warned: bool,
+}
+
+impl GlyphEditor {
+    pub fn new(number: char) -> Self {
+        let mut editor = GlyphEditor {
+            glyph: Number::new(number, 0, 0, 0, 0, Point::new(0, 0), None, None),
+            segment: Segment::Line,
+            pending: Vec::new(),
+            selected: None,
+            dragging: false,
+            cursor: Point::new(0, 0),
+            modified: false,
+            drafts: Vec::new(),
+            close_warned: false,
+        };
+        editor.open(number);
+        editor
+    }
+
+    // Starts from this session's unsaved edits of the glyph if there are
+    // any, otherwise from the one the font currently uses, custom or built
+    // in. Edits to the glyph open so far are kept for later.
+    pub fn open(&mut self, number: char) {
+        if self.modified {
+            self.drafts.push(self.glyph.clone());
+        }
+
+        match self.drafts.iter().position(|draft| draft.number == number) {
+            Some(index) => {
+                self.glyph = self.drafts.remove(index);
+                self.modified = true;
+            }
+            None => {
+                self.glyph = numbers::create_digit(number, 0, 0).unwrap_or_else(|| {
+                    Number::new(
+                        number,
+                        DEFAULT_NUM_WIDTH,
+                        DEFAULT_NUM_HEIGHT,
+                        0,
+                        0,
+                        Point::new(DEFAULT_NUM_WIDTH / 2, 15),
+                        None,
+                        None,
+                    )
+                });
+                self.modified = false;
+            }
+        }
+
+        self.pending.clear();
+        self.selected = None;
+        self.dragging = false;
+        hud::status(format!("Editing glyph '{}'", number));
+    }
+
+    // Digits with edits that are not saved yet.
+    pub fn unsaved(&self) -> Vec<char> {
+        let mut unsaved: Vec<char> = self.drafts.iter().map(|draft| draft.number).collect();
+        if self.modified {
+            unsaved.push(self.glyph.number);
+        }
+        unsaved.sort_unstable();
+        unsaved
+    }
+
+    // True if the editor can close. The first attempt with unsaved edits
+    // only warns, the next one discards them.
+    pub fn close(&mut self) -> bool {
+        let unsaved = self.unsaved();
+        if unsaved.is_empty() || self.close_warned {
+            return true;
+        }
+
+        let digits: Vec<String> = unsaved.iter().map(|c| format!("'{}'", c)).collect();
+        hud::status(format!(
+            "Unsaved edits to glyphs {}, close again to discard them",
+            digits.join(", ")
+        ));
+        self.close_warned = true;
+        false
+    }
+
+    fn touch(&mut self) {
+        self.modified = true;
+        self.close_warned = false;
+    }
+
+    pub fn set_segment(&mut self, segment: Segment) {
+        self.pending.clear();
+        self.segment = segment;
+        hud::status(String::from(match segment {
+            Segment::Line => "Adding lines",
+            Segment::Cubic => "Adding cubic curves",
+        }));
+    }
+
+    fn point(&self, which: GlyphPoint) -> Option<Point> {
+        match which {
+            GlyphPoint::Line(i, j) => self.glyph.lines.get(i)?.controll_points.get(j).copied(),
+            GlyphPoint::Curve(i, j) => self
+                .glyph
+                .bezier_curves
+                .get(i)?
+                .controll_points
+                .get(j)
+                .copied(),
+            GlyphPoint::Center => Some(self.glyph.center),
+        }
+    }
+
+    fn point_mut(&mut self, which: GlyphPoint) -> Option<&mut Point> {
+        match which {
+            GlyphPoint::Line(i, j) => self.glyph.lines.get_mut(i)?.controll_points.get_mut(j),
+            GlyphPoint::Curve(i, j) => self
+                .glyph
+                .bezier_curves
+                .get_mut(i)?
+                .controll_points
+                .get_mut(j),
+            GlyphPoint::Center => Some(&mut self.glyph.center),
+        }
+    }
+
+    fn points(&self) -> Vec<GlyphPoint> {
+        let mut points = vec![GlyphPoint::Center];
+        for (i, line) in self.glyph.lines.iter().enumerate() {
+            points.extend((0..line.controll_points.len()).map(|j| GlyphPoint::Line(i, j)));
+        }
+        for (i, curve) in self.glyph.bezier_curves.iter().enumerate() {
+            points.extend((0..curve.controll_points.len()).map(|j| GlyphPoint::Curve(i, j)));
+        }
+        points
+    }
+
+    // Later segments are drawn on top, so they win when points overlap.
+    fn point_at(&self, screen: Point) -> Option<GlyphPoint> {
+        self.points()
+            .into_iter()
+            .rev()
+            .find(|which| match self.point(*which) {
+                Some(point) => {
+                    let p = to_screen(point);
+                    (p.x - screen.x).abs() <= POINT_RADIUS && (p.y - screen.y).abs() <= POINT_RADIUS
+                }
+                None => false,
+            })
+    }
+
+    pub fn mouse_down(&mut self, screen: Point) {
+        self.cursor = screen;
+
+        if self.pending.is_empty() {
+            if let Some(hit) = self.point_at(screen) {
+                self.selected = Some(hit);
+                self.dragging = true;
+                return;
+            }
+        }
+
+        self.selected = None;
+        self.pending.push(to_glyph(screen));
+        if self.pending.len() < self.segment.points_needed() {
+            return;
+        }
+
+        let points: Vec<Point> = self.pending.drain(..).collect();
+        self.touch();
+        match self.segment {
+            Segment::Line => self.glyph.lines.push(Line::new(points)),
+            Segment::Cubic => self.glyph.bezier_curves.push(CubicBezierCurve::new(points)),
+        }
+    }
+
+    pub fn mouse_move(&mut self, screen: Point) {
+        self.cursor = screen;
+
+        if !self.dragging {
+            return;
+        }
+
+        let target = to_glyph(screen);
+        if let Some(point) = self.selected.and_then(|which| self.point_mut(which)) {
+            if *point != target {
+                *point = target;
+                self.touch();
+            }
+        }
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.dragging = false;
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+        self.selected = None;
+        self.dragging = false;
+    }
+
+    // A point can't exist on its own, removing it removes its segment. The
+    // center stays.
+    pub fn delete_selected(&mut self) {
+        match self.selected.take() {
+            Some(GlyphPoint::Line(i, _)) if i < self.glyph.lines.len() => {
+                self.glyph.lines.remove(i);
+                self.touch();
+            }
+            Some(GlyphPoint::Curve(i, _)) if i < self.glyph.bezier_curves.len() => {
+                self.glyph.bezier_curves.remove(i);
+                self.touch();
+            }
+            _ => {}
+        }
+        self.dragging = false;
+    }
+
+    // The edited glyphs replace the font's ones right away and go to the
+    // glyph file together with every other custom glyph. Text using the
+    // font needs to be laid out again afterwards.
+    pub fn save(&mut self, path: &str) -> io::Result<()> {
+        for draft in self.drafts.drain(..) {
+            numbers::set_custom_glyph(draft);
+        }
+        numbers::set_custom_glyph(self.glyph.clone());
+        self.modified = false;
+        self.close_warned = false;
+        glyph_file::save_glyphs(&numbers::custom_glyphs(), path)
+    }
+
+    // Digit keys open that digit's glyph, everything else goes through the
+    // glyph_ bindings.
+    pub fn key_down(&mut self, keycode: Keycode) {
+        if let Some(number) = digit_key(keycode) {
+            self.open(number);
+        }
+    }
+
+    fn draw_glyph(&self, scale: f32, x: i32, y: i32, canvas: &mut Canvas<Window>) {
+        let mut glyph = self.glyph.clone();
+        graphics::scale_number(&mut glyph, scale, Point::new(0, 0));
+        graphics::translate_number(&mut glyph, x, y);
+        glyph.draw(canvas, false);
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+        let w = self.glyph.w;
+        let h = self.glyph.h;
+
+        canvas.set_draw_color(Color::RGB(255, 255, 255));
+        canvas.clear();
+
+        canvas.set_draw_color(Color::RGB(225, 225, 225));
+        for gx in (0..=w).step_by(GRID_STEP as usize) {
+            graphics::draw_line(
+                to_screen(Point::new(gx, 0)),
+                to_screen(Point::new(gx, h + OVERSHOOT)),
+                canvas,
+            );
+        }
+        for gy in (0..=h + OVERSHOOT).step_by(GRID_STEP as usize) {
+            graphics::draw_line(
+                to_screen(Point::new(0, gy)),
+                to_screen(Point::new(w, gy)),
+                canvas,
+            );
+        }
+
+        canvas.set_draw_color(Color::RGB(100, 100, 100));
+        canvas
+            .draw_rect(Rect::new(
+                ORIGIN_X,
+                ORIGIN_Y,
+                (w * ZOOM) as u32,
+                (h * ZOOM) as u32,
+            ))
+            .expect("Drawing em box failed");
+
+        canvas.set_draw_color(Color::RGB(0, 160, 0));
+        graphics::draw_line(
+            to_screen(Point::new(-5, h)),
+            to_screen(Point::new(w + 5, h)),
+            canvas,
+        );
+
+        self.draw_glyph(ZOOM as f32, ORIGIN_X, ORIGIN_Y, canvas);
+
+        // Curve handles, so the control polygon is visible while dragging.
+        canvas.set_draw_color(Color::RGB(160, 160, 160));
+        for curve in self.glyph.bezier_curves.iter() {
+            if let [a, b, c, d] = curve.controll_points[..] {
+                graphics::draw_line(to_screen(a), to_screen(b), canvas);
+                graphics::draw_line(to_screen(c), to_screen(d), canvas);
+            }
+        }
+
+        for which in self.points() {
+            let point = match self.point(which) {
+                Some(point) => to_screen(point),
+                None => continue,
+            };
+            if which == GlyphPoint::Center {
+                canvas
+                    .filled_circle(point.x as i16, point.y as i16, 4, Color::RGB(0, 0, 255))
+                    .unwrap();
+            } else {
+                graphics::draw_target(point, canvas);
+            }
+            if Some(which) == self.selected {
+                canvas
+                    .circle(
+                        point.x as i16,
+                        point.y as i16,
+                        POINT_RADIUS as i16,
+                        Color::RGB(0, 0, 0),
+                    )
+                    .unwrap();
+            }
+        }
+
+        for point in self.pending.iter() {
+            canvas
+                .circle(
+                    to_screen(*point).x as i16,
+                    to_screen(*point).y as i16,
+                    3,
+                    Color::RGB(0, 160, 0),
+                )
+                .unwrap();
+        }
+        if let Some(last) = self.pending.last() {
+            canvas.set_draw_color(Color::RGB(0, 160, 0));
+            graphics::draw_line(to_screen(*last), self.cursor, canvas);
+        }
+
+        let mut y = ORIGIN_Y;
+        for scale in PREVIEW_SCALES {
+            self.draw_glyph(scale, PREVIEW_X, y, canvas);
+            y += (h as f32 * scale) as i32 + GRID_STEP * 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Clicks on existing points grab them, so each line needs its own
+    // spot.
+    fn add_line(editor: &mut GlyphEditor, y: i32) {
+        editor.set_segment(Segment::Line);
+        editor.mouse_down(to_screen(Point::new(1, y)));
+        editor.mouse_down(to_screen(Point::new(30, y + 3)));
+    }
+
+    #[test]
+    fn edits_survive_switching_glyphs() {
+        let mut editor = GlyphEditor::new('3');
+        let lines = editor.glyph.lines.len();
+        add_line(&mut editor, 1);
+        assert_eq!(editor.glyph.lines.len(), lines + 1);
+
+        editor.open('5');
+        assert_eq!(editor.glyph.number, '5');
+        assert_eq!(editor.unsaved(), vec!['3']);
+
+        editor.open('3');
+        assert_eq!(editor.glyph.lines.len(), lines + 1);
+        assert_eq!(editor.unsaved(), vec!['3']);
+    }
+
+    #[test]
+    fn opening_without_edits_keeps_nothing() {
+        let mut editor = GlyphEditor::new('3');
+        editor.open('5');
+        editor.open('3');
+        assert!(editor.unsaved().is_empty());
+        assert!(editor.close());
+    }
+
+    #[test]
+    fn closing_with_unsaved_edits_warns_once() {
+        let mut editor = GlyphEditor::new('7');
+        add_line(&mut editor, 1);
+        assert!(!editor.close());
+        assert!(editor.close());
+
+        // A new edit after the warning warns again.
+        let mut editor = GlyphEditor::new('7');
+        add_line(&mut editor, 1);
+        assert!(!editor.close());
+        add_line(&mut editor, 50);
+        assert!(!editor.close());
+    }
+}
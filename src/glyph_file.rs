@@ -0,0 +1,178 @@
+use std::fs;
+use std::io;
+
+use sdl2::rect::Point;
+
+use crate::graphics::{CubicBezierCurve, Line};
+use crate::hud;
+use crate::numbers::Number;
+use crate::scene_file::{invalid, parse, parse_points, write_points};
+
+// Same idea as the scene file, one record per line:
+//
+//   rust-sdl glyphs <version>
+//   glyph <char> <width> <height> <center x> <center y>
+//   line <x1> <y1> <x2> <y2>
+//   cubic <x1> <y1> ... <x4> <y4>
+//
+// Coordinates are in em box units, line and cubic records belong to the
+// glyph above them.
+const HEADER: &str = "rust-sdl glyphs";
+pub const GLYPH_VERSION: u32 = 1;
+
+pub fn save_glyphs(glyphs: &[Number], path: &str) -> io::Result<()> {
+    let mut out = format!("{} {}\n", HEADER, GLYPH_VERSION);
+
+    for glyph in glyphs {
+        out.push_str(&format!(
+            "glyph {} {} {} {} {}\n",
+            glyph.number, glyph.w, glyph.h, glyph.center.x, glyph.center.y
+        ));
+
+        for line in glyph.lines.iter() {
+            out.push_str("line");
+            write_points(&mut out, &line.controll_points);
+            out.push('\n');
+        }
+
+        for curve in glyph.bezier_curves.iter() {
+            out.push_str("cubic");
+            write_points(&mut out, &curve.controll_points);
+            out.push('\n');
+        }
+    }
+
+    fs::write(path, out)?;
+
+    hud::status(format!("Glyphs saved as '{}'", path));
+
+    Ok(())
+}
+
+pub fn load_glyphs(path: &str) -> io::Result<Vec<Number>> {
+    let text = fs::read_to_string(path)?;
+    let mut glyphs: Vec<Number> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+
+        if i == 0 {
+            let version: u32 = match line.strip_prefix(HEADER) {
+                Some(version) => parse(Some(version.trim()), i)?,
+                None => return Err(invalid(i, "not a glyph file")),
+            };
+            if version != GLYPH_VERSION {
+                return Err(invalid(
+                    i,
+                    &format!("unsupported glyph version {}", version),
+                ));
+            }
+            continue;
+        }
+
+        match tokens.next() {
+            None => continue,
+            Some("glyph") => {
+                let number: char = parse(tokens.next(), i)?;
+                let w = parse(tokens.next(), i)?;
+                let h = parse(tokens.next(), i)?;
+                let center = Point::new(parse(tokens.next(), i)?, parse(tokens.next(), i)?);
+                glyphs.push(Number::new(number, w, h, 0, 0, center, None, None));
+            }
+            Some("line") => {
+                let points = parse_points(&mut tokens, 2, i)?;
+                match glyphs.last_mut() {
+                    Some(glyph) => glyph.lines.push(Line::new(points)),
+                    None => return Err(invalid(i, "line before any glyph")),
+                }
+            }
+            Some("cubic") => {
+                let points = parse_points(&mut tokens, 4, i)?;
+                match glyphs.last_mut() {
+                    Some(glyph) => glyph.bezier_curves.push(CubicBezierCurve::new(points)),
+                    None => return Err(invalid(i, "cubic before any glyph")),
+                }
+            }
+            Some(record) => hud::status(format!(
+                "Skipping unknown record '{}' on line {}",
+                record,
+                i + 1
+            )),
+        }
+    }
+
+    Ok(glyphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numbers;
+
+    fn glyph_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join("rust-sdl-glyph-test");
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    fn outline(glyph: &Number) -> (Vec<Vec<Point>>, Vec<Vec<Point>>) {
+        (
+            glyph
+                .lines
+                .iter()
+                .map(|line| line.controll_points.clone())
+                .collect(),
+            glyph
+                .bezier_curves
+                .iter()
+                .map(|curve| curve.controll_points.clone())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn built_in_digits_round_trip() {
+        let digits = vec![
+            numbers::create_digit_0(0, 0),
+            numbers::create_digit_1(0, 0),
+            numbers::create_digit_2(0, 0),
+            numbers::create_digit_3(0, 0),
+            numbers::create_digit_4(0, 0),
+            numbers::create_digit_5(0, 0),
+            numbers::create_digit_6(0, 0),
+            numbers::create_digit_7(0, 0),
+            numbers::create_digit_8(0, 0),
+            numbers::create_digit_9(0, 0),
+        ];
+
+        let path = glyph_path("digits.glyphs");
+        save_glyphs(&digits, &path).unwrap();
+        let loaded = load_glyphs(&path).unwrap();
+
+        assert_eq!(loaded.len(), digits.len());
+        for (glyph, digit) in loaded.iter().zip(digits.iter()) {
+            assert_eq!(glyph.number, digit.number);
+            assert_eq!((glyph.w, glyph.h), (digit.w, digit.h));
+            assert_eq!(glyph.center, digit.center);
+            assert_eq!(outline(glyph), outline(digit));
+        }
+    }
+
+    #[test]
+    fn bad_header_or_version_is_rejected() {
+        let path = glyph_path("bad-header.glyphs");
+        fs::write(&path, "rust-sdl scene 5\nglyph 1 10 20 5 10\n").unwrap();
+        let error = load_glyphs(&path).err().unwrap();
+        assert_eq!(error.to_string(), "line 1: not a glyph file");
+
+        let path = glyph_path("bad-version.glyphs");
+        fs::write(&path, "rust-sdl glyphs 2\nglyph 1 10 20 5 10\n").unwrap();
+        let error = load_glyphs(&path).err().unwrap();
+        assert_eq!(error.to_string(), "line 1: unsupported glyph version 2");
+
+        let path = glyph_path("no-glyph.glyphs");
+        fs::write(&path, "rust-sdl glyphs 1\nline 0 0 1 1\n").unwrap();
+        let error = load_glyphs(&path).err().unwrap();
+        assert_eq!(error.to_string(), "line 2: line before any glyph");
+    }
+}
@@ -2,12 +2,16 @@ use std::collections::VecDeque;
 
 use sdl2::gfx::primitives::DrawRenderer;
 use sdl2::pixels::Color;
-use sdl2::rect::Point;
+use sdl2::rect::{Point, Rect};
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
+use crate::clip;
+use crate::hud;
 use crate::numbers;
+use crate::raster;
 
+#[derive(Clone)]
 pub struct Line {
     pub controll_points: Vec<Point>,
 }
@@ -24,6 +28,7 @@ impl Line {
     }
 }
 
+#[derive(Clone)]
 pub struct CubicBezierCurve {
     pub controll_points: Vec<Point>,
 }
@@ -37,7 +42,7 @@ impl CubicBezierCurve {
 
     pub fn draw(&self, canvas: &mut Canvas<Window>) {
         if self.controll_points.len() < 4 {
-            println!("Not enough controll points for drawing");
+            hud::status(String::from("Not enough controll points for drawing"));
             return;
         }
 
@@ -52,7 +57,7 @@ impl CubicBezierCurve {
 
     pub fn add_point(&mut self, point: Point) {
         if self.can_receive_points() == false {
-            println!("Already has all points");
+            hud::status(String::from("Already has all points"));
             return;
         }
 
@@ -69,6 +74,7 @@ pub struct SmoothBezierCurve {
     pub color: f32,
 }
 
+#[derive(Clone)]
 pub struct Rectangle {
     pub controll_points: Vec<Point>,
 }
@@ -89,24 +95,47 @@ impl Rectangle {
                 draw_line(*second, Point::new(second.x, first.y), canvas);
             }
             _ => {
-                println!("Rectangle does not have all points");
+                hud::status(String::from("Rectangle does not have all points"));
             }
         }
     }
 }
 
-pub fn flood_fill(start: Point, fill_color: u32, canvas: &mut Canvas<Window>) {
-    let (width, height) = canvas.output_size().unwrap();
+// The area drawing is limited to: the canvas clip rect if one is set,
+// otherwise the whole output.
+// None when the clip rect lies outside the canvas and nothing may be drawn.
+pub fn clip_bounds(canvas: &Canvas<Window>) -> Option<Rect> {
+    let (width, height) = canvas.output_size().ok()?;
+    let output = Rect::new(0, 0, width, height);
 
-    let mut canvas_pixels = canvas
-        .read_pixels(None, sdl2::pixels::PixelFormatEnum::ARGB8888)
-        .expect("Read pixels failes");
+    match canvas.clip_rect() {
+        Some(clip_rect) => clip_rect.intersection(output),
+        None => Some(output),
+    }
+}
+
+pub fn flood_fill(
+    start: Point,
+    fill_color: u32,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    let (width, _) = canvas.output_size()?;
+    let bounds = match clip_bounds(canvas) {
+        Some(bounds) => bounds,
+        None => return Ok(()),
+    };
+
+    if !bounds.contains_point(start) {
+        return Ok(());
+    }
+
+    let mut canvas_pixels = canvas.read_pixels(None, sdl2::pixels::PixelFormatEnum::ARGB8888)?;
 
     let default_color: u32 = get_color(start, width, &canvas_pixels);
 
     if default_color == fill_color {
-        println!("Cannot flood fill region alredy painted");
-        return;
+        hud::status(String::from("Cannot flood fill region alredy painted"));
+        return Ok(());
     }
 
     let mut stack: VecDeque<Point> = VecDeque::new();
@@ -114,18 +143,16 @@ pub fn flood_fill(start: Point, fill_color: u32, canvas: &mut Canvas<Window>) {
 
     while stack.is_empty() == false {
         let p = stack.pop_back().unwrap();
-        if p.y < 0 || p.y > (height as i32 - 1) || p.x < 0 || p.x > (width as i32 - 1) {
+        if !bounds.contains_point(p) {
             continue;
         }
 
-        canvas_pixels = canvas
-            .read_pixels(None, sdl2::pixels::PixelFormatEnum::ARGB8888)
-            .expect("Read pixels failes");
+        canvas_pixels = canvas.read_pixels(None, sdl2::pixels::PixelFormatEnum::ARGB8888)?;
 
         let pixel_color: u32 = get_color(p, width, &canvas_pixels);
 
         if pixel_color == default_color {
-            draw_point(p.x, p.y, fill_color as f32, canvas);
+            draw_point(p.x, p.y, fill_color as f32, canvas)?;
             stack.push_back(Point::new(p.x + 1, p.y));
             stack.push_back(Point::new(p.x - 1, p.y));
             stack.push_back(Point::new(p.x, p.y + 1));
@@ -133,6 +160,8 @@ pub fn flood_fill(start: Point, fill_color: u32, canvas: &mut Canvas<Window>) {
             canvas.present();
         }
     }
+
+    Ok(())
 }
 
 pub fn get_color(point: Point, screen_width: u32, pixels: &Vec<u8>) -> u32 {
@@ -146,62 +175,81 @@ pub fn get_color(point: Point, screen_width: u32, pixels: &Vec<u8>) -> u32 {
     (a << 24) | (r << 16) | (g << 8) | b
 }
 
-pub fn get_color_component(color: u32, component: char) -> u8 {
+pub fn get_color_component(color: u32, component: char) -> Result<u8, String> {
     match component {
-        'r' | 'R' => ((color >> 16) & 0xFFF) as u8,
-        'g' | 'G' => ((color >> 8) & 0xFFF) as u8,
-        'b' | 'B' => (color & 0xFFF) as u8,
-        _ => panic!("Invalid color component, use r, g or b"),
+        'r' | 'R' => Ok((color >> 16) as u8),
+        'g' | 'G' => Ok((color >> 8) as u8),
+        'b' | 'B' => Ok(color as u8),
+        _ => Err(format!(
+            "Invalid color component '{}', use r, g or b",
+            component
+        )),
     }
 }
 
-pub fn draw_point(x: i32, y: i32, c: f32, canvas: &mut Canvas<Window>) {
-    let r = get_color_component(c as u32, 'r');
-    let g = get_color_component(c as u32, 'g');
-    let b = get_color_component(c as u32, 'b');
-    let color = Color::RGB(r, g, b);
+pub fn draw_point(x: i32, y: i32, c: f32, canvas: &mut Canvas<Window>) -> Result<(), String> {
+    let r = get_color_component(c as u32, 'r')?;
+    let g = get_color_component(c as u32, 'g')?;
+    let b = get_color_component(c as u32, 'b')?;
+
+    canvas.set_draw_color(Color::RGB(r, g, b));
+    canvas.draw_point(Point::new(x, y))
+}
 
-    canvas.set_draw_color(color);
-    canvas
-        .draw_point(Point::new(x, y))
-        .expect("Drawing point failed");
+// The draw functions below plot many points, only the first failure of a
+// call is reported so a broken renderer does not flood the HUD.
+fn report(result: Result<(), String>) {
+    if let Err(e) = result {
+        hud::status(format!("Drawing failed: {}", e));
+    }
 }
 
 pub fn draw_target(point: Point, canvas: &mut Canvas<Window>) {
-    canvas
-        .filled_circle(point.x as i16, point.y as i16, 3, Color::RGB(255, 0, 0))
-        .unwrap();
+    report(canvas.filled_circle(point.x as i16, point.y as i16, 3, Color::RGB(255, 0, 0)));
 }
 
 pub fn draw_line(p_1: Point, p_2: Point, canvas: &mut Canvas<Window>) {
+    let bounds = match clip_bounds(canvas) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let mut result = Ok(());
+    plot_clipped_line(p_1, p_2, bounds, &mut |x, y| {
+        if result.is_ok() {
+            result = canvas.draw_point(Point::new(x, y));
+        }
+    });
+    report(result);
+}
+
+pub fn plot_clipped_line(p_1: Point, p_2: Point, bounds: Rect, plot: &mut impl FnMut(i32, i32)) {
+    if let Some((start, end)) = clip::clip_line(p_1, p_2, bounds) {
+        plot_line(start, end, plot);
+    }
+}
+
+fn plot_polyline(points: &[(f32, f32)], bounds: Rect, plot: &mut impl FnMut(i32, i32)) {
+    for (start, end) in clip::clip_polyline(points, bounds) {
+        plot_line(start, end, plot);
+    }
+}
+
+pub fn plot_line(p_1: Point, p_2: Point, plot: &mut impl FnMut(i32, i32)) {
     let mut x0 = p_1.x;
     let mut y0 = p_1.y;
-    let mut x1 = p_2.x;
-    let mut y1 = p_2.y;
+    let x1 = p_2.x;
+    let y1 = p_2.y;
 
     let dx = (x1 - x0).abs();
     let dy = (y1 - y0).abs();
-    let mut sx = 0;
-    let mut sy = 0;
 
-    if x0 < x1 {
-        sx = 1;
-    } else {
-        sx = -1;
-    }
-
-    if y0 < y1 {
-        sy = 1;
-    } else {
-        sy = -1;
-    }
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
 
     let mut err = dx - dy;
 
     loop {
-        canvas
-            .draw_point(Point::new(x0, y0))
-            .expect("Draw point failed");
+        plot(x0, y0);
 
         if x0 == x1 && y0 == y1 {
             break;
@@ -220,11 +268,109 @@ pub fn draw_line(p_1: Point, p_2: Point, canvas: &mut Canvas<Window>) {
     }
 }
 
+// The pixels plot_line would give for p_1 to p_2 that lie inside bounds,
+// without walking the rest of the line. The major axis advances every
+// step, the minor one after (2 * k * minor + major - 1) / (2 * major) of
+// them.
+pub fn plot_line_within(p_1: Point, p_2: Point, bounds: Rect, plot: &mut impl FnMut(i32, i32)) {
+    let dx = (p_2.x - p_1.x).abs() as i64;
+    let dy = (p_2.y - p_1.y).abs() as i64;
+    let sx = if p_1.x < p_2.x { 1 } else { -1 };
+    let sy = if p_1.y < p_2.y { 1 } else { -1 };
+
+    let (major, minor) = if dx >= dy { (dx, dy) } else { (dy, dx) };
+    let (start, step, low, high) = if dx >= dy {
+        (p_1.x, sx, bounds.left(), bounds.right() - 1)
+    } else {
+        (p_1.y, sy, bounds.top(), bounds.bottom() - 1)
+    };
+
+    // Steps k at which the major coordinate start + step * k is in bounds.
+    let (first, last) = if step > 0 {
+        (low - start, high - start)
+    } else {
+        (start - high, start - low)
+    };
+    let first = (first as i64).max(0);
+    let last = (last as i64).min(major);
+
+    for k in first..=last {
+        let minor_steps = if major == 0 {
+            0
+        } else {
+            ((2 * k * minor + major - 1) / (2 * major)) as i32
+        };
+        let (x, y) = if dx >= dy {
+            (p_1.x + sx * k as i32, p_1.y + sy * minor_steps)
+        } else {
+            (p_1.x + sx * minor_steps, p_1.y + sy * k as i32)
+        };
+
+        if bounds.contains_point((x, y)) {
+            plot(x, y);
+        }
+    }
+}
+
 pub fn draw_circle(center: Point, radius: i32, canvas: &mut Canvas<Window>) {
+    let bounds = match clip_bounds(canvas) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let mut result = Ok(());
+    plot_circle(center, radius, bounds, &mut |x, y| {
+        if result.is_ok() {
+            result = draw_point(x, y, 0.0, canvas);
+        }
+    });
+    report(result);
+}
+
+pub fn plot_circle(center: Point, radius: i32, bounds: Rect, plot: &mut impl FnMut(i32, i32)) {
+    if !circle_visible(center, radius, bounds) {
+        return;
+    }
+
+    let mut clipped = |x: i32, y: i32| {
+        if bounds.contains_point((x, y)) {
+            plot(x, y);
+        }
+    };
+
+    for point in circle_octant(radius) {
+        display_circle(center, point, &mut clipped);
+    }
+}
+
+// Whether any outline pixel of the circle can fall inside bounds.
+pub fn circle_visible(center: Point, radius: i32, bounds: Rect) -> bool {
+    let r = radius.abs();
+    let circle_box = Rect::new(center.x - r, center.y - r, (2 * r + 1) as u32, (2 * r + 1) as u32);
+    if !circle_box.has_intersection(bounds) {
+        return false;
+    }
+
+    // Zoomed far into a big circle the view can sit entirely inside it,
+    // with no outline pixel to plot.
+    let inside = |x: i32, y: i32| {
+        let dx = (x - center.x) as i64;
+        let dy = (y - center.y) as i64;
+        dx * dx + dy * dy < (r as i64 - 1).pow(2)
+    };
+    !(r > 1
+        && inside(bounds.left(), bounds.top())
+        && inside(bounds.right() - 1, bounds.top())
+        && inside(bounds.left(), bounds.bottom() - 1)
+        && inside(bounds.right() - 1, bounds.bottom() - 1))
+}
+
+// One octant of the midpoint circle around the origin, x goes up by one
+// per point. display_circle mirrors each point into the other seven.
+pub fn circle_octant(radius: i32) -> Vec<Point> {
     let mut x: i32 = 0;
     let mut y: i32 = radius;
     let mut decision_param = 3 - 2 * radius;
-    display_circle(center, Point::new(x, y), 0.0, canvas);
+    let mut points = vec![Point::new(x, y)];
     while y >= x {
         x += 1;
         if decision_param > 0 {
@@ -234,59 +380,21 @@ pub fn draw_circle(center: Point, radius: i32, canvas: &mut Canvas<Window>) {
             decision_param = decision_param + 4 * x + 6;
         }
 
-        display_circle(center, Point::new(x, y), 0.0, canvas);
-    }
-}
-
-fn display_circle(center: Point, point_to_draw: Point, color: f32, canvas: &mut Canvas<Window>) {
-    draw_point(
-        center.x + point_to_draw.x,
-        center.y + point_to_draw.y,
-        color,
-        canvas,
-    );
-    draw_point(
-        center.x - point_to_draw.x,
-        center.y + point_to_draw.y,
-        color,
-        canvas,
-    );
-    draw_point(
-        center.x + point_to_draw.x,
-        center.y - point_to_draw.y,
-        color,
-        canvas,
-    );
-    draw_point(
-        center.x - point_to_draw.x,
-        center.y - point_to_draw.y,
-        color,
-        canvas,
-    );
-    draw_point(
-        center.x + point_to_draw.y,
-        center.y + point_to_draw.x,
-        color,
-        canvas,
-    );
-    draw_point(
-        center.x - point_to_draw.y,
-        center.y + point_to_draw.x,
-        color,
-        canvas,
-    );
-    draw_point(
-        center.x + point_to_draw.y,
-        center.y - point_to_draw.x,
-        color,
-        canvas,
-    );
-    draw_point(
-        center.x - point_to_draw.y,
-        center.y - point_to_draw.x,
-        color,
-        canvas,
-    );
+        points.push(Point::new(x, y));
+    }
+
+    points
+}
+
+fn display_circle(center: Point, point_to_draw: Point, plot: &mut impl FnMut(i32, i32)) {
+    plot(center.x + point_to_draw.x, center.y + point_to_draw.y);
+    plot(center.x - point_to_draw.x, center.y + point_to_draw.y);
+    plot(center.x + point_to_draw.x, center.y - point_to_draw.y);
+    plot(center.x - point_to_draw.x, center.y - point_to_draw.y);
+    plot(center.x + point_to_draw.y, center.y + point_to_draw.x);
+    plot(center.x - point_to_draw.y, center.y + point_to_draw.x);
+    plot(center.x + point_to_draw.y, center.y - point_to_draw.x);
+    plot(center.x - point_to_draw.y, center.y - point_to_draw.x);
 }
 
 pub fn draw_cubic_bezier(
@@ -296,36 +404,98 @@ pub fn draw_cubic_bezier(
     p_4: Point,
     canvas: &mut Canvas<Window>,
 ) {
-    for u in 0..1000 {
-        let u = u as f32 / 1000 as f32;
+    let bounds = match clip_bounds(canvas) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let mut result = Ok(());
+    plot_cubic_bezier(p_1, p_2, p_3, p_4, bounds, &mut |x, y| {
+        if result.is_ok() {
+            result = draw_point(x, y, 0.0, canvas);
+        }
+    });
+    report(result);
+}
 
-        let x_u = (1.0 - u).powi(3) * p_1.x as f32
-            + 3.0 * u * (1.0 - u).powi(2) * p_2.x as f32
-            + 3.0 * u.powi(2) * (1.0 - u) * p_3.x as f32
-            + u.powi(3) * p_4.x as f32;
+pub fn plot_cubic_bezier(
+    p_1: Point,
+    p_2: Point,
+    p_3: Point,
+    p_4: Point,
+    bounds: Rect,
+    plot: &mut impl FnMut(i32, i32),
+) {
+    if !raster::bounding_box_of(&[p_1, p_2, p_3, p_4]).has_intersection(bounds) {
+        return;
+    }
 
-        let y_u = (1.0 - u).powi(3) * p_1.y as f32
-            + 3.0 * u * (1.0 - u).powi(2) * p_2.y as f32
-            + 3.0 * u.powi(2) * (1.0 - u) * p_3.y as f32
-            + u.powi(3) * p_4.y as f32;
+    plot_polyline(&clip::flatten_cubic_bezier(p_1, p_2, p_3, p_4), bounds, plot);
+}
+
+pub fn draw_quadratic_bezier(p_1: Point, p_2: Point, p_3: Point, canvas: &mut Canvas<Window>) {
+    let bounds = match clip_bounds(canvas) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let mut result = Ok(());
+    plot_quadratic_bezier(p_1, p_2, p_3, bounds, &mut |x, y| {
+        if result.is_ok() {
+            result = draw_point(x, y, 0.0, canvas);
+        }
+    });
+    report(result);
+}
 
-        draw_point(x_u as i32, y_u as i32, 0.0, canvas);
+pub fn plot_quadratic_bezier(
+    p_1: Point,
+    p_2: Point,
+    p_3: Point,
+    bounds: Rect,
+    plot: &mut impl FnMut(i32, i32),
+) {
+    if !raster::bounding_box_of(&[p_1, p_2, p_3]).has_intersection(bounds) {
+        return;
     }
+
+    plot_polyline(&clip::flatten_quadratic_bezier(p_1, p_2, p_3), bounds, plot);
 }
 
-pub fn draw_quadratic_bezier(p_1: Point, p_2: Point, p_3: Point, canvas: &mut Canvas<Window>) {
-    for u in 0..1000 {
-        let u = u as f32 / 1000 as f32;
+pub fn plot_filled_polygon(points: &[(f32, f32)], bounds: Rect, plot: &mut impl FnMut(i32, i32)) {
+    scan_polygon(&clip::clip_polygon(points, bounds), bounds, plot);
+}
+
+// Scanline fill of a polygon already clipped to the target, limited to the
+// rows and columns of bounds.
+pub fn scan_polygon(polygon: &[(f32, f32)], bounds: Rect, plot: &mut impl FnMut(i32, i32)) {
+    if polygon.len() < 3 {
+        return;
+    }
 
-        let x_u = (1.0 - u).powi(2) * p_1.x as f32
-            + 2.0 * u * (1.0 - u) * p_2.x as f32
-            + u.powi(2) * p_3.x as f32;
+    let min_y = polygon.iter().map(|p| p.1).fold(f32::MAX, f32::min).ceil() as i32;
+    let max_y = polygon.iter().map(|p| p.1).fold(f32::MIN, f32::max).floor() as i32;
 
-        let y_u = (1.0 - u).powi(2) * p_1.y as f32
-            + 2.0 * u * (1.0 - u) * p_2.y as f32
-            + u.powi(2) * p_3.y as f32;
+    for y in min_y.max(bounds.top())..=max_y.min(bounds.bottom() - 1) {
+        let scan_y = y as f32;
+        let mut crossings: Vec<f32> = Vec::new();
 
-        draw_point(x_u as i32, y_u as i32, 0.0, canvas);
+        for (i, a) in polygon.iter().enumerate() {
+            let b = polygon[(i + 1) % polygon.len()];
+            if (a.1 <= scan_y && b.1 > scan_y) || (b.1 <= scan_y && a.1 > scan_y) {
+                crossings.push(a.0 + (scan_y - a.1) / (b.1 - a.1) * (b.0 - a.0));
+            }
+        }
+
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for pair in crossings.chunks(2) {
+            if let [start, end] = pair {
+                let first = (start.ceil() as i32).max(bounds.left());
+                let last = (end.floor() as i32).min(bounds.right() - 1);
+                for x in first..=last {
+                    plot(x, y);
+                }
+            }
+        }
     }
 }
 
@@ -400,3 +570,36 @@ pub fn rotate(point: &mut Point, angle: f32) {
     point.x = (point.x as f32 * angle.cos() - point.y as f32 * angle.sin()) as i32;
     point.y = (old_x as f32 * angle.sin() + point.y as f32 * angle.cos()) as i32;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_components() {
+        let color = 0x80A1B2C3;
+        assert_eq!(get_color_component(color, 'r'), Ok(0xA1));
+        assert_eq!(get_color_component(color, 'G'), Ok(0xB2));
+        assert_eq!(get_color_component(color, 'b'), Ok(0xC3));
+        assert!(get_color_component(color, 'a').is_err());
+    }
+    #[test]
+    fn line_within_bounds_matches_the_full_line() {
+        let bounds = Rect::new(5, 3, 9, 7);
+        for (end_x, end_y) in [(0, 0), (17, 4), (4, 17), (-13, 9), (9, -13), (-12, -12)] {
+            let start = Point::new(8, 6);
+            let end = Point::new(8 + end_x, 6 + end_y);
+
+            let mut full = Vec::new();
+            plot_line(start, end, &mut |x, y| {
+                if bounds.contains_point((x, y)) {
+                    full.push((x, y));
+                }
+            });
+            let mut within = Vec::new();
+            plot_line_within(start, end, bounds, &mut |x, y| within.push((x, y)));
+
+            assert_eq!(within, full, "line to {:?}", end);
+        }
+    }
+}
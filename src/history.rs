@@ -0,0 +1,528 @@
+use crate::effects::TextEffects;
+use crate::layers::Layer;
+use crate::numbers::NumberSeries;
+use crate::raster::PixelBuffer;
+use crate::scene::{Scene, Shape};
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+#[derive(Clone, Copy)]
+pub struct SeriesTransform {
+    pub x: i32,
+    pub y: i32,
+    pub scale: f32,
+    pub angle: f32,
+}
+
+impl SeriesTransform {
+    pub fn of(series: &NumberSeries) -> Self {
+        SeriesTransform {
+            x: series.x,
+            y: series.y,
+            scale: series.scale,
+            angle: series.angle,
+        }
+    }
+}
+
+// Every edit is recorded after it was made, with enough state on both
+// sides to take it back and to do it again.
+pub enum Command {
+    AddShape {
+        index: usize,
+        shape: Shape,
+    },
+    // Sorted by index so they can be put back in order.
+    DeleteShapes {
+        shapes: Vec<(usize, Shape)>,
+    },
+    EditShapes {
+        edits: Vec<(usize, Shape, Shape)>,
+    },
+    TransformSeries {
+        index: usize,
+        before: SeriesTransform,
+        after: SeriesTransform,
+    },
+    SeriesText {
+        index: usize,
+        before: String,
+        after: String,
+    },
+    SeriesEffects {
+        index: usize,
+        before: TextEffects,
+        after: TextEffects,
+    },
+    SeriesLayer {
+        index: usize,
+        before: usize,
+        after: usize,
+    },
+    // New layers only ever go on top, so nothing has to be renumbered.
+    AddLayer {
+        layer: Layer,
+    },
+    EditLayer {
+        index: usize,
+        before: Layer,
+        after: Layer,
+    },
+    MoveLayer {
+        from: usize,
+        to: usize,
+    },
+    // Backdrop pixels that changed: (pixel index, before, after).
+    Pixels {
+        changes: Vec<(usize, u32, u32)>,
+    },
+}
+
+impl Command {
+    // Diffs the backdrop against a copy of its pixels taken before a raster
+    // operation. None if nothing changed.
+    pub fn pixel_diff(before: &[u8], after: &PixelBuffer) -> Option<Command> {
+        let changes: Vec<(usize, u32, u32)> = before
+            .chunks(4)
+            .zip(after.pixels.chunks(4))
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (a, b))| {
+                (
+                    i,
+                    u32::from_le_bytes([a[0], a[1], a[2], a[3]]),
+                    u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                )
+            })
+            .collect();
+
+        if changes.is_empty() {
+            None
+        } else {
+            Some(Command::Pixels { changes })
+        }
+    }
+
+    fn apply(&self, scene: &mut Scene, backdrop: &mut PixelBuffer) {
+        match self {
+            Command::AddShape { index, shape } => {
+                scene.shapes.insert((*index).min(scene.shapes.len()), shape.clone())
+            }
+            Command::DeleteShapes { shapes } => {
+                for (index, _) in shapes.iter().rev() {
+                    if *index < scene.shapes.len() {
+                        scene.shapes.remove(*index);
+                    }
+                }
+            }
+            Command::EditShapes { edits } => {
+                for (index, _, after) in edits {
+                    if let Some(shape) = scene.shapes.get_mut(*index) {
+                        *shape = after.clone();
+                    }
+                }
+            }
+            Command::TransformSeries { index, after, .. } => transform_series(scene, *index, after),
+            Command::SeriesText { index, after, .. } => set_series_text(scene, *index, after),
+            Command::SeriesEffects { index, after, .. } => set_series_effects(scene, *index, after),
+            Command::SeriesLayer { index, after, .. } => set_series_layer(scene, *index, *after),
+            Command::AddLayer { layer } => scene.layers.push(layer.clone()),
+            Command::EditLayer { index, after, .. } => set_layer(scene, *index, after),
+            Command::MoveLayer { from, to } => {
+                scene.move_layer(*from, *to);
+            }
+            Command::Pixels { changes } => {
+                for (index, _, after) in changes {
+                    write_pixel(backdrop, *index, *after);
+                }
+            }
+        }
+    }
+
+    fn revert(&self, scene: &mut Scene, backdrop: &mut PixelBuffer) {
+        match self {
+            Command::AddShape { index, .. } => {
+                if *index < scene.shapes.len() {
+                    scene.shapes.remove(*index);
+                }
+            }
+            Command::DeleteShapes { shapes } => {
+                for (index, shape) in shapes.iter() {
+                    scene.shapes.insert((*index).min(scene.shapes.len()), shape.clone());
+                }
+            }
+            Command::EditShapes { edits } => {
+                for (index, before, _) in edits {
+                    if let Some(shape) = scene.shapes.get_mut(*index) {
+                        *shape = before.clone();
+                    }
+                }
+            }
+            Command::TransformSeries { index, before, .. } => {
+                transform_series(scene, *index, before)
+            }
+            Command::SeriesText { index, before, .. } => set_series_text(scene, *index, before),
+            Command::SeriesEffects { index, before, .. } => {
+                set_series_effects(scene, *index, before)
+            }
+            Command::SeriesLayer { index, before, .. } => set_series_layer(scene, *index, *before),
+            Command::AddLayer { .. } => {
+                scene.layers.pop();
+            }
+            Command::EditLayer { index, before, .. } => set_layer(scene, *index, before),
+            Command::MoveLayer { from, to } => {
+                scene.move_layer(*to, *from);
+            }
+            Command::Pixels { changes } => {
+                for (index, before, _) in changes {
+                    write_pixel(backdrop, *index, *before);
+                }
+            }
+        }
+    }
+}
+
+fn transform_series(scene: &mut Scene, index: usize, transform: &SeriesTransform) {
+    if let Some(series) = scene.series.get_mut(index) {
+        series.set_transform(transform.x, transform.y, transform.scale, transform.angle);
+    }
+}
+
+fn set_series_text(scene: &mut Scene, index: usize, text: &str) {
+    if let Some(series) = scene.series.get_mut(index) {
+        series.set_text(String::from(text));
+    }
+}
+
+fn set_series_effects(scene: &mut Scene, index: usize, effects: &TextEffects) {
+    if let Some(series) = scene.series.get_mut(index) {
+        series.effects = *effects;
+    }
+}
+
+fn set_series_layer(scene: &mut Scene, index: usize, layer: usize) {
+    if let Some(series) = scene.series.get_mut(index) {
+        series.layer = layer;
+    }
+}
+
+fn set_layer(scene: &mut Scene, index: usize, layer: &Layer) {
+    if let Some(target) = scene.layers.get_mut(index) {
+        *target = layer.clone();
+    }
+}
+
+// Straight into the pixel bytes, undo must not be clipped or masked.
+fn write_pixel(target: &mut PixelBuffer, index: usize, color: u32) {
+    let offset = index * 4;
+    if offset + 4 <= target.pixels.len() {
+        target.pixels[offset..offset + 4].copy_from_slice(&color.to_le_bytes());
+    }
+}
+
+pub struct History {
+    undo_stack: Vec<Command>,
+    redo_stack: Vec<Command>,
+    // Oldest commands are dropped beyond this, None keeps everything.
+    pub limit: Option<usize>,
+    // Bumped on every change so others can tell the scene was edited.
+    revision: u64,
+}
+
+impl History {
+    pub fn new(limit: Option<usize>) -> Self {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            limit,
+            revision: 0,
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn record(&mut self, command: Command) {
+        self.undo_stack.push(command);
+        self.redo_stack.clear();
+        self.revision += 1;
+
+        if let Some(limit) = self.limit {
+            if self.undo_stack.len() > limit {
+                let excess = self.undo_stack.len() - limit;
+                self.undo_stack.drain(..excess);
+            }
+        }
+    }
+
+    pub fn undo(&mut self, scene: &mut Scene, backdrop: &mut PixelBuffer) -> bool {
+        match self.undo_stack.pop() {
+            Some(command) => {
+                command.revert(scene, backdrop);
+                self.redo_stack.push(command);
+                self.revision += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, scene: &mut Scene, backdrop: &mut PixelBuffer) -> bool {
+        match self.redo_stack.pop() {
+            Some(command) => {
+                command.apply(scene, backdrop);
+                self.undo_stack.push(command);
+                self.revision += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Line;
+    use crate::scene::Geometry;
+    use sdl2::rect::Point;
+
+    fn line(x: i32) -> Shape {
+        let line = Line::new(vec![Point::new(x, 0), Point::new(x, 10)]);
+        Shape::new(Geometry::Line(line), 0xFF000000)
+    }
+
+    fn xs(scene: &Scene) -> Vec<i32> {
+        scene
+            .shapes
+            .iter()
+            .map(|shape| shape.handles()[0].x)
+            .collect()
+    }
+
+    fn series_scene() -> Scene {
+        let mut scene = Scene::new();
+        scene
+            .series
+            .push(NumberSeries::new(10, 20, String::from("12"), 1.0, 0.0));
+        scene
+    }
+
+    #[test]
+    fn edit_shapes_round_trip() {
+        let mut scene = Scene::new();
+        scene.add(line(0));
+        scene.add(line(5));
+        let mut backdrop = PixelBuffer::new(2, 2, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        let edits = vec![(0, line(0), line(30)), (1, line(5), line(40))];
+        for (index, _, after) in edits.iter() {
+            scene.shapes[*index] = after.clone();
+        }
+        history.record(Command::EditShapes { edits });
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(xs(&scene), vec![0, 5]);
+        assert!(history.redo(&mut scene, &mut backdrop));
+        assert_eq!(xs(&scene), vec![30, 40]);
+    }
+
+    #[test]
+    fn add_and_delete_round_trip() {
+        let mut scene = Scene::new();
+        let mut backdrop = PixelBuffer::new(2, 2, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        for x in [0, 1, 2] {
+            scene.add(line(x));
+            history.record(Command::AddShape {
+                index: scene.shapes.len() - 1,
+                shape: line(x),
+            });
+        }
+        // Like Selection::delete, highest index first.
+        let shapes = vec![(0, line(0)), (2, line(2))];
+        scene.shapes.remove(2);
+        scene.shapes.remove(0);
+        history.record(Command::DeleteShapes { shapes });
+        assert_eq!(xs(&scene), vec![1]);
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(xs(&scene), vec![0, 1, 2]);
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(xs(&scene), vec![0, 1]);
+        assert!(history.redo(&mut scene, &mut backdrop));
+        assert!(history.redo(&mut scene, &mut backdrop));
+        assert_eq!(xs(&scene), vec![1]);
+        assert!(!history.redo(&mut scene, &mut backdrop));
+    }
+
+    #[test]
+    fn series_text_round_trip() {
+        let mut scene = series_scene();
+        let mut backdrop = PixelBuffer::new(2, 2, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        scene.series[0].set_text(String::from("345"));
+        history.record(Command::SeriesText {
+            index: 0,
+            before: String::from("12"),
+            after: String::from("345"),
+        });
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(scene.series[0].number_str, "12");
+        assert_eq!(scene.series[0].numbers.len(), 2);
+        assert!(history.redo(&mut scene, &mut backdrop));
+        assert_eq!(scene.series[0].number_str, "345");
+        assert_eq!(scene.series[0].numbers.len(), 3);
+    }
+
+    #[test]
+    fn transform_series_round_trip() {
+        let mut scene = series_scene();
+        let mut backdrop = PixelBuffer::new(2, 2, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        let before = SeriesTransform::of(&scene.series[0]);
+        scene.series[0].set_transform(50, 60, 2.0, 0.5);
+        let after = SeriesTransform::of(&scene.series[0]);
+        history.record(Command::TransformSeries {
+            index: 0,
+            before,
+            after,
+        });
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        let series = &scene.series[0];
+        assert_eq!(
+            (series.x, series.y, series.scale, series.angle),
+            (10, 20, 1.0, 0.0)
+        );
+        assert_eq!(series.number_str, "12");
+
+        assert!(history.redo(&mut scene, &mut backdrop));
+        let series = &scene.series[0];
+        assert_eq!(
+            (series.x, series.y, series.scale, series.angle),
+            (50, 60, 2.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn layer_commands_round_trip() {
+        let mut scene = series_scene();
+        let mut backdrop = PixelBuffer::new(2, 2, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        scene.layers.push(Layer::new("top"));
+        history.record(Command::AddLayer {
+            layer: Layer::new("top"),
+        });
+        scene.series[0].layer = 1;
+        history.record(Command::SeriesLayer {
+            index: 0,
+            before: 0,
+            after: 1,
+        });
+        let before = scene.layers[1].clone();
+        scene.layers[1].opacity = 0.25;
+        history.record(Command::EditLayer {
+            index: 1,
+            before,
+            after: scene.layers[1].clone(),
+        });
+        scene.move_layer(1, 0);
+        history.record(Command::MoveLayer { from: 1, to: 0 });
+        assert_eq!(scene.series[0].layer, 0);
+        assert_eq!(scene.layers[0].opacity, 0.25);
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(scene.layers[1].name, "top");
+        assert_eq!(scene.series[0].layer, 1);
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(scene.layers[1].opacity, 1.0);
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(scene.series[0].layer, 0);
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(scene.layers.len(), 1);
+
+        while history.redo(&mut scene, &mut backdrop) {}
+        assert_eq!(scene.layers[0].name, "top");
+        assert_eq!(scene.layers[0].opacity, 0.25);
+        assert_eq!(scene.series[0].layer, 0);
+    }
+
+    #[test]
+    fn pixel_diff_round_trip() {
+        let mut scene = Scene::new();
+        let mut backdrop = PixelBuffer::new(3, 3, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        let before = backdrop.pixels.clone();
+        assert!(Command::pixel_diff(&before, &backdrop).is_none());
+
+        backdrop.pixels[4..8].copy_from_slice(&0xFF000000u32.to_le_bytes());
+        backdrop.pixels[32..36].copy_from_slice(&0xFF00FF00u32.to_le_bytes());
+        let after = backdrop.pixels.clone();
+        match Command::pixel_diff(&before, &backdrop) {
+            Some(command) => {
+                if let Command::Pixels { changes } = &command {
+                    assert_eq!(changes.len(), 2);
+                }
+                history.record(command);
+            }
+            None => panic!("expected a diff"),
+        }
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(backdrop.pixels, before);
+        assert!(history.redo(&mut scene, &mut backdrop));
+        assert_eq!(backdrop.pixels, after);
+    }
+
+    #[test]
+    fn limit_drops_the_oldest_commands() {
+        let mut scene = Scene::new();
+        let mut backdrop = PixelBuffer::new(2, 2, 0xFFFFFFFF);
+        let mut history = History::new(Some(2));
+
+        for x in [0, 1, 2] {
+            scene.add(line(x));
+            history.record(Command::AddShape {
+                index: scene.shapes.len() - 1,
+                shape: line(x),
+            });
+        }
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert!(!history.undo(&mut scene, &mut backdrop));
+        // The first shape's command is gone, so it stays.
+        assert_eq!(xs(&scene), vec![0]);
+    }
+
+    #[test]
+    fn recording_clears_redo() {
+        let mut scene = Scene::new();
+        let mut backdrop = PixelBuffer::new(2, 2, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        scene.add(line(0));
+        history.record(Command::AddShape {
+            index: 0,
+            shape: line(0),
+        });
+        assert!(history.undo(&mut scene, &mut backdrop));
+
+        scene.add(line(1));
+        history.record(Command::AddShape {
+            index: 0,
+            shape: line(1),
+        });
+        assert!(!history.redo(&mut scene, &mut backdrop));
+        assert_eq!(xs(&scene), vec![1]);
+    }
+}
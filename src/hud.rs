@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::rwops::RWops;
+use sdl2::ttf::{Font, Sdl2TtfContext};
+use sdl2::video::Window;
+
+// Compiled in, the app runs from any working directory.
+const FONT_DATA: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+const FONT_SIZE: u16 = 14;
+const MESSAGE_LIFETIME: Duration = Duration::from_secs(4);
+const MAX_MESSAGES: usize = 5;
+const PADDING: i32 = 6;
+
+// Messages waiting for the HUD, status can be called from anywhere.
+static PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn status(message: String) {
+    PENDING.lock().unwrap().push(message);
+}
+
+// What the HUD shows besides messages, filled in by the main loop.
+pub struct HudInfo<'a> {
+    pub tool: &'a str,
+    pub cursor: (i32, i32),
+    pub zoom: f32,
+    // Line being typed for an action, see prompt.rs.
+    pub prompt: Option<String>,
+}
+
+pub struct Hud<'ttf> {
+    font: Font<'ttf, 'static>,
+    pub visible: bool,
+    messages: VecDeque<(String, Instant)>,
+    frames: u32,
+    fps: u32,
+    fps_since: Instant,
+}
+
+impl<'ttf> Hud<'ttf> {
+    pub fn new(ttf_context: &'ttf Sdl2TtfContext) -> Self {
+        let font = ttf_context
+            .load_font_from_rwops(
+                RWops::from_bytes(FONT_DATA).expect("Reading font data failed"),
+                FONT_SIZE,
+            )
+            .expect("Loading font failed");
+
+        Hud {
+            font,
+            visible: true,
+            messages: VecDeque::new(),
+            frames: 0,
+            fps: 0,
+            fps_since: Instant::now(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    // Once per frame. While hidden, messages go to the console instead so
+    // none get lost.
+    pub fn update(&mut self) {
+        self.frames += 1;
+        let elapsed = self.fps_since.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = (self.frames as f32 / elapsed.as_secs_f32()).round() as u32;
+            self.frames = 0;
+            self.fps_since = Instant::now();
+        }
+
+        for message in PENDING.lock().unwrap().drain(..) {
+            if self.visible {
+                self.messages.push_back((message, Instant::now()));
+            } else {
+                println!("{}", message);
+            }
+        }
+
+        while self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages
+            .retain(|(_, shown)| shown.elapsed() < MESSAGE_LIFETIME);
+    }
+
+    fn draw_line(
+        &self,
+        canvas: &mut Canvas<Window>,
+        text: &str,
+        x: i32,
+        y: i32,
+        color: Color,
+    ) -> i32 {
+        if text.is_empty() {
+            return self.font.height();
+        }
+
+        let surface = self
+            .font
+            .render(text)
+            .blended(color)
+            .expect("Rendering text failed");
+        let texture_creator = canvas.texture_creator();
+        let texture = texture_creator
+            .create_texture_from_surface(&surface)
+            .expect("Creating text texture failed");
+
+        canvas
+            .copy(
+                &texture,
+                None,
+                Rect::new(x, y, surface.width(), surface.height()),
+            )
+            .expect("Drawing text failed");
+
+        surface.height() as i32
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, info: &HudInfo) {
+        if !self.visible {
+            return;
+        }
+
+        let mut lines = vec![format!(
+            "{} fps  {}  {}, {}  {}%",
+            self.fps,
+            info.tool,
+            info.cursor.0,
+            info.cursor.1,
+            (info.zoom * 100.0).round() as i32
+        )];
+        lines.extend(info.prompt.clone());
+        let white_lines = lines.len();
+        lines.extend(self.messages.iter().map(|(message, _)| message.clone()));
+
+        let width = lines
+            .iter()
+            .filter_map(|line| self.font.size_of(line).ok())
+            .map(|(w, _)| w)
+            .max()
+            .unwrap_or(0);
+        let height = lines.len() as i32 * self.font.height();
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas
+            .fill_rect(Rect::new(
+                0,
+                0,
+                width + 2 * PADDING as u32,
+                (height + 2 * PADDING) as u32,
+            ))
+            .expect("Drawing HUD background failed");
+        canvas.set_blend_mode(BlendMode::None);
+
+        let mut y = PADDING;
+        for (i, line) in lines.iter().enumerate() {
+            let color = if i < white_lines {
+                Color::RGB(255, 255, 255)
+            } else {
+                Color::RGB(255, 220, 120)
+            };
+            y += self.draw_line(canvas, line, PADDING, y, color);
+        }
+    }
+}
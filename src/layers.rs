@@ -0,0 +1,196 @@
+use crate::gamma::Blending;
+use crate::raster::PixelBuffer;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+    Difference,
+}
+
+const BLEND_MODES: [(&str, BlendMode); 6] = [
+    ("normal", BlendMode::Normal),
+    ("multiply", BlendMode::Multiply),
+    ("screen", BlendMode::Screen),
+    ("overlay", BlendMode::Overlay),
+    ("add", BlendMode::Add),
+    ("difference", BlendMode::Difference),
+];
+
+impl BlendMode {
+    pub fn name(&self) -> &'static str {
+        BLEND_MODES
+            .iter()
+            .find(|(_, mode)| mode == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("normal")
+    }
+
+    pub fn from_name(name: &str) -> Option<BlendMode> {
+        BLEND_MODES
+            .iter()
+            .find(|(mode_name, _)| *mode_name == name)
+            .map(|(_, mode)| *mode)
+    }
+
+    pub fn next(&self) -> BlendMode {
+        let index = BLEND_MODES
+            .iter()
+            .position(|(_, mode)| mode == self)
+            .unwrap_or(0);
+        BLEND_MODES[(index + 1) % BLEND_MODES.len()].1
+    }
+
+    // Channels are in 0.0..=1.0, backdrop first.
+    fn blend(&self, backdrop: f32, source: f32) -> f32 {
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => backdrop * source,
+            BlendMode::Screen => backdrop + source - backdrop * source,
+            BlendMode::Overlay => {
+                if backdrop <= 0.5 {
+                    2.0 * backdrop * source
+                } else {
+                    1.0 - 2.0 * (1.0 - backdrop) * (1.0 - source)
+                }
+            }
+            BlendMode::Add => (backdrop + source).min(1.0),
+            BlendMode::Difference => (backdrop - source).abs(),
+        }
+    }
+}
+
+// A scene layer. Shapes and series name theirs by index into
+// Scene::layers, layers are composited bottom to top with index 0 at the
+// bottom.
+#[derive(Clone)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Layer {
+            name: String::from(name),
+            visible: true,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+
+    // Fully opaque normal layers can be drawn straight onto what is below.
+    pub fn is_plain(&self) -> bool {
+        self.opacity >= 1.0 && self.blend_mode == BlendMode::Normal
+    }
+}
+
+fn channel(color: u32, shift: u32) -> f32 {
+    ((color >> shift) & 0xFF) as f32 / 255.0
+}
+
+fn composite_pixel(
+    backdrop: u32,
+    source: u32,
+    opacity: f32,
+    mode: BlendMode,
+    blending: Blending,
+) -> u32 {
+    let source_alpha = channel(source, 24) * opacity.clamp(0.0, 1.0);
+    if source_alpha == 0.0 {
+        return backdrop;
+    }
+
+    let backdrop_alpha = channel(backdrop, 24);
+    let out_alpha = source_alpha + backdrop_alpha * (1.0 - source_alpha);
+
+    let mix = |shift: u32| {
+        let cb = blending.decode(((backdrop >> shift) & 0xFF) as u8);
+        let cs = blending.decode(((source >> shift) & 0xFF) as u8);
+
+        let mixed = (1.0 - backdrop_alpha) * cs + backdrop_alpha * mode.blend(cb, cs);
+        let premultiplied = source_alpha * mixed + (1.0 - source_alpha) * backdrop_alpha * cb;
+
+        blending.encode(premultiplied / out_alpha) as u32
+    };
+
+    let alpha = (out_alpha.clamp(0.0, 1.0) * 255.0).round() as u32;
+    (alpha << 24) | (mix(16) << 16) | (mix(8) << 8) | mix(0)
+}
+
+// Source is a transparent buffer holding what was drawn on the layer.
+pub fn composite_layer(output: &mut PixelBuffer, source: &PixelBuffer, layer: &Layer) {
+    let width = output.width.min(source.width) as i32;
+    let height = output.height.min(source.height) as i32;
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = composite_pixel(
+                output.get(x, y),
+                source.get(x, y),
+                layer.opacity,
+                layer.blend_mode,
+                output.settings.blending,
+            );
+            output.set(x, y, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These only ever mix channels with 0 or 1, which works out the same
+    // whatever the blending.
+    fn composite(backdrop: u32, source: u32, opacity: f32, mode: BlendMode) -> u32 {
+        let linear = composite_pixel(backdrop, source, opacity, mode, Blending::Linear);
+        let raw = composite_pixel(backdrop, source, opacity, mode, Blending::Raw);
+        assert_eq!(raw, linear);
+        linear
+    }
+
+    const RED: u32 = 0xFFC03020;
+    const WHITE: u32 = 0xFFFFFFFF;
+    const BLACK: u32 = 0xFF000000;
+
+    #[test]
+    fn opaque_normal_replaces_backdrop() {
+        assert_eq!(composite(WHITE, RED, 1.0, BlendMode::Normal), RED);
+    }
+
+    #[test]
+    fn transparent_source_keeps_backdrop() {
+        assert_eq!(composite(RED, 0x00000000, 1.0, BlendMode::Normal), RED);
+        assert_eq!(composite(RED, WHITE, 0.0, BlendMode::Multiply), RED);
+    }
+
+    #[test]
+    fn blend_modes_with_neutral_colors() {
+        assert_eq!(composite(WHITE, RED, 1.0, BlendMode::Multiply), RED);
+        assert_eq!(composite(BLACK, RED, 1.0, BlendMode::Screen), RED);
+        assert_eq!(composite(RED, BLACK, 1.0, BlendMode::Add), RED);
+        assert_eq!(composite(RED, RED, 1.0, BlendMode::Difference), BLACK);
+    }
+
+    #[test]
+    fn blend_mode_names_round_trip() {
+        for (name, mode) in BLEND_MODES {
+            assert_eq!(mode.name(), name);
+            assert!(BlendMode::from_name(name) == Some(mode));
+        }
+        assert!(BlendMode::from_name("dodge").is_none());
+
+        let mut mode = BlendMode::Normal;
+        for _ in 0..BLEND_MODES.len() {
+            mode = mode.next();
+        }
+        assert!(mode == BlendMode::Normal);
+        assert!(BlendMode::Normal.next() == BlendMode::Multiply);
+    }
+}
@@ -1,27 +1,75 @@
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Mod;
 use sdl2::pixels::{Color, PixelFormat, PixelFormatEnum};
 use sdl2::rect::Point;
 use std::time::Duration;
 
+use bindings::Action;
+use history::{Command, History, SeriesTransform};
+
+mod anti_aliasing;
+mod autosave;
+mod bezier;
+mod bindings;
+mod camera;
+mod clip;
+mod dither;
+mod effects;
+mod filters;
+mod fitting;
+mod gamma;
+mod glyph_editor;
+mod glyph_file;
 mod graphics;
+mod history;
+mod hud;
+mod layers;
+mod mask;
 mod numbers;
+mod paint;
+mod panel;
+mod prompt;
+mod raster;
+mod scene;
+mod scene_file;
 mod sdl_to_bmp;
+mod selection;
+mod text_edit;
+mod tiles;
+mod tools;
+mod trace;
+mod ui;
 
+// Size of the drawing (the backdrop) and of the window at startup, the
+// window can be resized independently of it.
 const W: u32 = 840;
 const H: u32 = 680;
+const POSTER_SCALE: u32 = 4;
+const PRINT_SCALE: f32 = 4.0;
+const SCENE_PATH: &str = "drawing.scene";
+const ZOOM_STEP: f32 = 1.1;
 
 pub fn main() {
+    // Asked before the window opens so it doesn't sit there unresponsive.
+    let recovered = if autosave::start_session() {
+        autosave::offer_recovery()
+    } else {
+        None
+    };
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
     let window = video_subsystem
         .window("rust-sdl2 demo", W, H)
         .position_centered()
+        .resizable()
         .build()
         .unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
+    let ttf_context = sdl2::ttf::init().unwrap();
+    let mut hud = hud::Hud::new(&ttf_context);
 
     canvas.clear();
     canvas.present();
@@ -33,66 +81,811 @@ pub fn main() {
 
     let mut angle = 0.0;
 
+    let hatch = paint::Paint::Hatch {
+        style: paint::HatchStyle::Lines,
+        angle: 45.0 * 0.017453293,
+        spacing: 8.0,
+        thickness: 2.0,
+        color: 0xFF000000,
+        background: 0x00000000,
+    };
+
+    let title_effects = effects::TextEffects {
+        shadow: Some(effects::DropShadow {
+            offset: (6.0, 6.0),
+            blur_radius: 6.0,
+            color: 0x80000000,
+        }),
+        outline: Some(effects::Outline {
+            width: 3.0,
+            color: 0xFF2060C0,
+        }),
+        glow: None,
+    };
+
+    // Raster work (fills, hatching) lands here, vector shapes are
+    // drawn on top every frame so tool previews don't leave trails.
+    let mut backdrop = raster::PixelBuffer::new(W, H, 0xFFFFFFFF);
+    // Blending and stroke choices, the backdrop and everything rendered
+    // from it follow these.
+    let mut render_settings = raster::RenderSettings::new();
+    let mut scene = scene::Scene::new();
+    let mut tools = tools::ToolState::new();
+    let mut selection = selection::Selection::new();
+    let mut history = History::new(Some(history::DEFAULT_HISTORY_LIMIT));
+    let mut active_series = 0;
+    // New shapes go on this layer.
+    let mut active_layer = 0;
+    let mut dither_settings = dither::DitherSettings::new();
+    let mut filter_chain = filters::FilterChain::new();
+
+    // Glyphs saved from the glyph editor replace the built in ones.
+    if std::path::Path::new(glyph_editor::GLYPH_PATH).exists() {
+        match glyph_file::load_glyphs(glyph_editor::GLYPH_PATH) {
+            Ok(glyphs) => {
+                for glyph in glyphs {
+                    numbers::set_custom_glyph(glyph);
+                }
+            }
+            Err(e) => hud::status(format!(
+                "Failed to load '{}': {}",
+                glyph_editor::GLYPH_PATH,
+                e
+            )),
+        }
+    }
+
+    let bindings = if std::path::Path::new(bindings::BINDINGS_PATH).exists() {
+        match bindings::load_bindings(bindings::BINDINGS_PATH) {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                hud::status(format!(
+                    "Failed to load '{}', using the default bindings: {}",
+                    bindings::BINDINGS_PATH,
+                    e
+                ));
+                bindings::Bindings::new()
+            }
+        }
+    } else {
+        bindings::Bindings::new()
+    };
+
+    scene.series.push(numbers::NumberSeries::new(
+        100,
+        200,
+        String::from("6789"),
+        0.7,
+        45.0 * 0.017453293,
+    ));
+    scene.series.push(numbers::NumberSeries::new(
+        430,
+        200,
+        String::from("2245"),
+        0.7,
+        120.0 * 0.017453293,
+    ));
+
+    if let Some((recovered_scene, recovered_backdrop)) = recovered {
+        scene = recovered_scene;
+        backdrop = fit_backdrop(recovered_backdrop, render_settings);
+    }
+
+    let mut autosave = autosave::Autosave::new(autosave::DEFAULT_AUTOSAVE_INTERVAL);
+    let mut glyph_editor: Option<glyph_editor::GlyphEditor> = None;
+    let mut camera = camera::Camera::new(W, H);
+    // Last known mouse position in window pixels, wheel events don't carry it.
+    let mut mouse = Point::new(0, 0);
+    let mut ui = ui::Ui::new();
+    let mut panel = panel::SeriesPanel::new();
+    // Series being typed into with the text tool.
+    let mut text_edit: Option<text_edit::TextEdit> = None;
+    // Set by the screenshot action, taken once the frame is drawn.
+    let mut screenshot = false;
+    // Only tracing asks for a typed line so far. A new prompt waits for the
+    // end of the frame's events, so the text input from the key that asked
+    // for it doesn't end up in it.
+    let mut prompt: Option<prompt::Prompt> = None;
+    let mut new_prompt: Option<prompt::Prompt> = None;
+
     'running: loop {
         canvas.set_draw_color(Color::RGB(0, 0, 0));
 
         for event in event_pump.poll_iter() {
+            // Any click ends typing, one on a series starts it again below.
+            if let Event::MouseButtonDown { .. } = event {
+                if let Some(edit) = text_edit.take() {
+                    edit.finish(&mut scene, &mut history);
+                }
+            }
+
+            if let Some(active) = prompt.as_mut() {
+                match active.event(&event) {
+                    prompt::Entry::Ignored => {}
+                    prompt::Entry::Typing => continue,
+                    prompt::Entry::Submitted(text) => {
+                        prompt = None;
+                        trace_glyph(&text, &mut scene);
+                        continue;
+                    }
+                    prompt::Entry::Cancelled => {
+                        prompt = None;
+                        continue;
+                    }
+                }
+            }
+
+            let panel_area = panel::SeriesPanel::area(camera.width, camera.height);
+            if glyph_editor.is_none() && ui.handle_event(&event, panel_area) {
+                continue;
+            }
+
+            // While typing every key goes to the series.
+            if let Some(edit) = text_edit.as_mut() {
+                match event {
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        keymod,
+                        ..
+                    } => {
+                        if edit.key_down(&mut scene, keycode, keymod) {
+                            text_edit.take().unwrap().finish(&mut scene, &mut history);
+                        }
+                        continue;
+                    }
+                    Event::TextInput { ref text, .. } => {
+                        edit.text_input(&mut scene, text);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                Event::MouseButtonDown { x, y, .. } => {
-                    println!("Clicked at {x}, {y}");
-                    graphics::flood_fill(
-                        Point::new(x, y),
-                        Color::RGB(255, 0, 0)
-                            .to_u32(&PixelFormat::try_from(PixelFormatEnum::ARGB8888).unwrap()),
-                        &mut canvas,
-                    )
-                }
-                Event::KeyDown {
-                    keycode: Some(keycode),
+                Event::Quit { .. } => break 'running,
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(width, height),
                     ..
-                } => println!("{}", keycode),
+                } => {
+                    camera.width = width.max(1) as u32;
+                    camera.height = height.max(1) as u32;
+                    continue;
+                }
                 _ => {}
             }
-        }
 
-        let mut number_series =
-            numbers::NumberSeries::new(100, 200, String::from("6789"), 0.7, 45.0 * 0.017453293);
-        let mut number_series_2 =
-            numbers::NumberSeries::new(430, 200, String::from("2245"), 0.7, 120.0 * 0.017453293);
+            let action = bindings.event_action(&event);
+
+            // The glyph editor has its own keys, only a few actions reach
+            // past it.
+            if let Some(editor) = glyph_editor.as_mut() {
+                if !matches!(
+                    action,
+                    Some(Action::Quit | Action::Hud | Action::GlyphEditor)
+                ) {
+                    glyph_editor_event(editor, &event, &bindings, &mut scene);
+                    continue;
+                }
+            }
+
+            match event {
+                Event::MouseButtonDown { x, y, .. } => mouse = Point::new(x, y),
+                Event::MouseMotion { x, y, .. } => {
+                    mouse = Point::new(x, y);
+                    if camera.is_panning() {
+                        camera.pan_to(mouse);
+                    } else {
+                        let point = camera.to_scene(mouse);
+                        tools.mouse_move(point);
+                        selection.mouse_move(&mut scene, point, camera.zoom);
+                    }
+                }
+                Event::MouseButtonUp {
+                    mouse_btn, x, y, ..
+                } => {
+                    mouse = Point::new(x, y);
+                    match bindings.action(bindings::Input::Mouse(mouse_btn)) {
+                        Some(Action::Pan) => camera.end_pan(),
+                        Some(Action::UseTool) => {
+                            if let Some(command) = selection.mouse_up(&scene) {
+                                history.record(command);
+                            }
+                            if let Some(shape) = tools.mouse_up(camera.to_scene(mouse)) {
+                                add_shape(&mut scene, &mut history, shape, active_layer);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+
+            let action = match action {
+                Some(action) => action,
+                None => continue,
+            };
+
+            match action {
+                Action::Quit => break 'running,
+                Action::Hud => hud.toggle(),
+                Action::GlyphEditor => {
+                    glyph_editor = match glyph_editor {
+                        Some(mut editor) => {
+                            if editor.close() {
+                                None
+                            } else {
+                                Some(editor)
+                            }
+                        }
+                        None => {
+                            tools.cancel();
+                            selection.clear();
+                            Some(glyph_editor::GlyphEditor::new('0'))
+                        }
+                    };
+                }
+                Action::Screenshot => screenshot = true,
+                Action::ZoomIn => camera.zoom_at(mouse, ZOOM_STEP),
+                Action::ZoomOut => camera.zoom_at(mouse, 1.0 / ZOOM_STEP),
+                Action::Pan => camera.start_pan(mouse),
+                Action::Cancel => tools.cancel(),
+                Action::UseTool => match tools.tool {
+                    tools::Tool::Fill => {
+                        let point = camera.to_scene(mouse);
+                        hud::status(format!("Clicked at {}, {}", point.x, point.y));
+                        let fill_color = Color::RGB(255, 0, 0)
+                            .to_u32(&PixelFormat::try_from(PixelFormatEnum::ARGB8888).unwrap());
+
+                        // Boundaries come from the backdrop and the scene, the
+                        // fill itself goes into the backdrop.
+                        let frame = camera::scene_frame(&scene, &backdrop);
+                        if !frame.contains(point.x, point.y) {
+                            hud::status(String::from("Clicked outside the drawing"));
+                        } else if frame.get(point.x, point.y) == fill_color {
+                            hud::status(String::from("Cannot flood fill region alredy painted"));
+                        } else {
+                            let before = backdrop.pixels.clone();
+                            for p in raster::flood_region(&frame, point) {
+                                backdrop.set(p.x, p.y, fill_color);
+                            }
+                            record_pixels(&mut history, &before, &backdrop);
+                        }
+                    }
+                    tools::Tool::Text => {
+                        let point = camera.to_scene(mouse);
+                        let hit = scene
+                            .series
+                            .iter()
+                            .enumerate()
+                            .find_map(|(i, series)| Some((i, series.caret_at(point)?)));
+                        if let Some((index, caret)) = hit {
+                            active_series = index;
+                            text_edit = Some(text_edit::TextEdit::new(&scene, index, caret));
+                        }
+                    }
+                    tools::Tool::Select => {
+                        let additive = sdl_context
+                            .keyboard()
+                            .mod_state()
+                            .intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                        selection.mouse_down(&scene, camera.to_scene(mouse), additive, camera.zoom);
+                    }
+                    _ => {
+                        if let Some(shape) = tools.mouse_down(camera.to_scene(mouse)) {
+                            add_shape(&mut scene, &mut history, shape, active_layer);
+                        }
+                    }
+                },
+                Action::FitView => match camera::content_bounds(&scene) {
+                    Some(bounds) => camera.fit_to(bounds),
+                    None => camera.reset(),
+                },
+                Action::ResetView => camera.reset(),
+                Action::Delete => {
+                    if let Some(command) = selection.delete(&mut scene) {
+                        history.record(command);
+                    }
+                }
+                Action::Undo => {
+                    selection.clear();
+                    if !history.undo(&mut scene, &mut backdrop) {
+                        hud::status(String::from("Nothing to undo"));
+                    }
+                }
+                Action::Redo => {
+                    selection.clear();
+                    if !history.redo(&mut scene, &mut backdrop) {
+                        hud::status(String::from("Nothing to redo"));
+                    }
+                }
+                Action::SaveScene => {
+                    if let Err(e) = scene_file::save_scene(&scene, Some(&backdrop), SCENE_PATH) {
+                        hud::status(format!("Failed to save '{}': {}", SCENE_PATH, e));
+                    }
+                }
+                Action::LoadScene => match scene_file::load_scene(SCENE_PATH) {
+                    Ok((loaded, loaded_backdrop)) => {
+                        scene = loaded;
+                        backdrop = fit_backdrop(loaded_backdrop, render_settings);
+                        history = History::new(Some(history::DEFAULT_HISTORY_LIMIT));
+                        autosave.reset(history.revision());
+                        selection.clear();
+                        tools.cancel();
+                        active_series = 0;
+                        active_layer = 0;
+                    }
+                    Err(e) => hud::status(format!("Failed to load '{}': {}", SCENE_PATH, e)),
+                },
+                Action::NextSeries => {
+                    active_series = (active_series + 1) % scene.series.len().max(1);
+                    hud::status(format!("Active series: {}", active_series));
+                }
+                Action::GridSnap => {
+                    selection.snap.grid = match selection.snap.grid {
+                        Some(_) => None,
+                        None => Some(10),
+                    };
+                    hud::status(format!("Grid snapping: {:?}", selection.snap.grid));
+                }
+                Action::EndpointSnap => {
+                    selection.snap.to_endpoints = !selection.snap.to_endpoints;
+                    hud::status(format!(
+                        "Endpoint snapping: {}",
+                        selection.snap.to_endpoints
+                    ));
+                }
+                Action::Poster => save_poster(&scene),
+                Action::Hatch => {
+                    let before = backdrop.pixels.clone();
+                    for series in scene.series.iter() {
+                        series.fill(&mut backdrop, &hatch);
+                    }
+                    record_pixels(&mut history, &before, &backdrop);
+                }
+                // Turns the title effects on and off for the active series.
+                Action::Effects => {
+                    if let Some(series) = scene.series.get_mut(active_series) {
+                        let before = series.effects;
+                        series.effects = if before.is_empty() {
+                            title_effects
+                        } else {
+                            effects::TextEffects::none()
+                        };
+                        history.record(Command::SeriesEffects {
+                            index: active_series,
+                            before,
+                            after: series.effects,
+                        });
+                    }
+                }
+                Action::Gamma => {
+                    render_settings.blending = render_settings.blending.toggled();
+                    backdrop.settings = render_settings;
+                    hud::status(format!(
+                        "Linear light blending: {}",
+                        render_settings.blending == gamma::Blending::Linear
+                    ));
+                }
+                Action::SmoothStrokes => {
+                    render_settings.smooth_strokes = !render_settings.smooth_strokes;
+                    backdrop.settings = render_settings;
+                    hud::status(format!(
+                        "Smooth strokes: {}",
+                        render_settings.smooth_strokes
+                    ));
+                }
+                Action::Dither => save_dithered(&mut canvas, &dither_settings),
+                Action::DitherMethod => {
+                    dither_settings.next_method();
+                    hud::status(format!("Dither: {}", dither_settings.describe()));
+                }
+                Action::DitherPalette => {
+                    dither_settings.next_palette();
+                    hud::status(format!("Dither: {}", dither_settings.describe()));
+                }
+                Action::FilterNext => {
+                    filter_chain.next_preset();
+                    hud::status(format!("Next filter: {}", filter_chain.preset().describe()));
+                }
+                Action::FilterAdd => {
+                    filter_chain.add();
+                    hud::status(format!("Filters: {}", filter_chain.describe()));
+                }
+                Action::FilterRemove => {
+                    filter_chain.remove_last();
+                    hud::status(format!("Filters: {}", filter_chain.describe()));
+                }
+                Action::Trace => {
+                    new_prompt = Some(prompt::Prompt::new("Trace <character> <image>"))
+                }
+                Action::ToggleFill => {
+                    if let Some(index) = scene.shapes.len().checked_sub(1) {
+                        let before = scene.shapes[index].clone();
+                        let shape = &mut scene.shapes[index];
+                        shape.fill = next_fill(shape, &hatch);
+                        history.record(Command::EditShapes {
+                            edits: vec![(index, before, shape.clone())],
+                        });
+                    }
+                }
+                Action::Print => {
+                    let print = camera::render_offscreen(&scene, &backdrop, PRINT_SCALE);
+                    match sdl_to_bmp::save_pixels_to_png(
+                        &print.pixels,
+                        print.width,
+                        print.height,
+                        "print.png",
+                    ) {
+                        Ok(()) => hud::status(String::from("Image saved as 'print.png'")),
+                        Err(e) => hud::status(format!("Failed to save 'print.png': {}", e)),
+                    }
+                }
+                // Exports are the size of the drawing, whatever the window is.
+                Action::Export => {
+                    match scene.export(&backdrop, 1.0, &filter_chain.filters, "scene.png") {
+                        Ok(()) => hud::status(String::from("Image saved as 'scene.png'")),
+                        Err(e) => hud::status(format!("Failed to save 'scene.png': {}", e)),
+                    }
+                }
+                Action::ExportLayers => {
+                    match scene.export_layers(&backdrop, 1.0, &filter_chain.filters, "layer") {
+                        Ok(()) => hud::status(format!("Exported {} layers", scene.layers.len())),
+                        Err(e) => hud::status(format!("Failed to export layers: {}", e)),
+                    }
+                }
+                Action::Tool(tool) => {
+                    selection.clear();
+                    tools.select(tool)
+                }
+                action => {
+                    if let Some((dx, dy, factor, turn)) = series_step(action) {
+                        transform_series(
+                            &mut scene,
+                            &mut history,
+                            active_series,
+                            dx,
+                            dy,
+                            factor,
+                            turn,
+                        );
+                    }
+                }
+            }
+        }
 
         angle = angle + 1.0;
 
-        number_series.draw(&mut canvas, false);
+        if let Some(editor) = &glyph_editor {
+            editor.draw(&mut canvas);
+        } else {
+            camera.draw_backdrop(&backdrop, &mut canvas);
+
+            camera.draw_scene(&scene, render_settings, &mut canvas);
+
+            tools.draw_preview(&camera, &mut canvas);
+
+            selection.draw(&scene, &camera, &mut canvas);
+
+            if let Some(edit) = &text_edit {
+                edit.draw(&scene, &camera, &mut canvas);
+            }
+
+            panel.begin(&mut canvas);
+            panel.series_controls(
+                &mut ui,
+                &mut canvas,
+                &mut scene,
+                &mut history,
+                &mut active_series,
+            );
+            panel.layer_controls(
+                &mut ui,
+                &mut canvas,
+                &mut scene,
+                &mut history,
+                &mut active_layer,
+                active_series,
+            );
+            panel.tool_settings(&mut ui, &mut canvas, &mut tools.color, &mut selection.snap);
+        }
+
+        if new_prompt.is_some() {
+            prompt = new_prompt.take();
+        }
+
+        hud.update();
+        let cursor = camera.to_scene(mouse);
+        hud.draw(
+            &mut canvas,
+            &hud::HudInfo {
+                tool: match glyph_editor {
+                    Some(_) => "glyph editor",
+                    None => tools.tool.name(),
+                },
+                cursor: (cursor.x, cursor.y),
+                zoom: camera.zoom,
+                prompt: prompt.as_ref().map(prompt::Prompt::line),
+            },
+        );
 
-        number_series_2.draw(&mut canvas, false);
+        ui.end_frame();
 
         canvas.present();
 
-        if event_pump
-            .keyboard_state()
-            .is_scancode_pressed(sdl2::keyboard::Scancode::S)
-        {
-            let texture_creator = canvas.texture_creator();
-
-            let mut text = texture_creator
-                .create_texture(
-                    sdl2::pixels::PixelFormatEnum::ARGB8888,
-                    sdl2::render::TextureAccess::Streaming,
-                    W,
-                    H,
-                )
-                .unwrap();
-
-            sdl_to_bmp::save_canvas_to_bmp(&mut text, &mut canvas)
-                .expect("Failed to save BMP file");
-            println!("Canvas saved as 'output.bmp'");
+        if screenshot {
+            screenshot = false;
+            match sdl_to_bmp::save_canvas_to_bmp(&mut canvas, &filter_chain.filters) {
+                Ok(()) => hud::status(String::from("Image saved as 'output.bmp'")),
+                Err(e) => hud::status(format!("Failed to save 'output.bmp': {}", e)),
+            }
         }
 
+        autosave.tick(&scene, &backdrop, history.revision());
+
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
+
+    autosave::end_session();
+}
+
+// Loaded backdrops of another size keep their top left corner at the scene
+// origin, like the drawing, and are padded or cropped on the right and
+// bottom.
+fn fit_backdrop(
+    loaded: Option<raster::PixelBuffer>,
+    settings: raster::RenderSettings,
+) -> raster::PixelBuffer {
+    let mut backdrop = match loaded {
+        Some(buffer) if buffer.width == W && buffer.height == H => buffer,
+        Some(buffer) => {
+            hud::status(format!(
+                "Backdrop is {}x{}, fitted to the {}x{} drawing",
+                buffer.width, buffer.height, W, H
+            ));
+            buffer.with_size(W, H, 0xFFFFFFFF)
+        }
+        None => raster::PixelBuffer::new(W, H, 0xFFFFFFFF),
+    };
+    backdrop.settings = settings;
+    backdrop
+}
+
+fn glyph_editor_event(
+    editor: &mut glyph_editor::GlyphEditor,
+    event: &Event,
+    bindings: &bindings::Bindings,
+    scene: &mut scene::Scene,
+) {
+    match bindings.glyph_editor_action(event) {
+        Some(Action::GlyphSave) => match editor.save(glyph_editor::GLYPH_PATH) {
+            // Saved glyphs are what create_digit hands out now, lay the
+            // text out again to pick them up.
+            Ok(()) => {
+                for series in scene.series.iter_mut() {
+                    series.set_transform(series.x, series.y, series.scale, series.angle);
+                }
+                hud::status(format!("Glyphs saved to '{}'", glyph_editor::GLYPH_PATH));
+            }
+            Err(e) => hud::status(format!(
+                "Failed to save '{}': {}",
+                glyph_editor::GLYPH_PATH,
+                e
+            )),
+        },
+        Some(Action::GlyphLines) => editor.set_segment(glyph_editor::Segment::Line),
+        Some(Action::GlyphCurves) => editor.set_segment(glyph_editor::Segment::Cubic),
+        Some(Action::GlyphDelete) => editor.delete_selected(),
+        _ => match *event {
+            Event::MouseButtonDown { .. }
+                if bindings.event_action(event) == Some(Action::Cancel) =>
+            {
+                editor.cancel()
+            }
+            Event::MouseButtonDown { x, y, .. } => editor.mouse_down(Point::new(x, y)),
+            Event::MouseMotion { x, y, .. } => editor.mouse_move(Point::new(x, y)),
+            Event::MouseButtonUp { .. } => editor.mouse_up(),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => editor.key_down(keycode),
+            _ => {}
+        },
+    }
+}
+
+fn add_shape(
+    scene: &mut scene::Scene,
+    history: &mut History,
+    mut shape: scene::Shape,
+    layer: usize,
+) {
+    shape.layer = layer;
+    history.record(Command::AddShape {
+        index: scene.shapes.len(),
+        shape: shape.clone(),
+    });
+    scene.add(shape);
+}
+
+fn record_pixels(history: &mut History, before: &[u8], backdrop: &raster::PixelBuffer) {
+    if let Some(command) = Command::pixel_diff(before, backdrop) {
+        history.record(command);
+    }
+}
+
+// Steps a shape's fill through none, flat gray, a gradient across the
+// shape, the hatch and a checker pattern.
+fn next_fill(shape: &scene::Shape, hatch: &paint::Paint) -> Option<paint::Paint> {
+    let bounds = shape.bounding_box();
+    match shape.fill {
+        None => Some(paint::Paint::Solid(0xFFC0C0C0)),
+        Some(paint::Paint::Solid(_)) => Some(paint::Paint::LinearGradient {
+            start: (bounds.left() as f32, bounds.top() as f32),
+            end: (bounds.right() as f32, bounds.bottom() as f32),
+            stops: vec![
+                paint::ColorStop::new(0.0, 0xFFFFFFFF),
+                paint::ColorStop::new(1.0, 0xFF808080),
+            ],
+            spread: paint::Spread::Pad,
+        }),
+        Some(paint::Paint::LinearGradient { .. }) => Some(hatch.clone()),
+        Some(paint::Paint::Hatch { .. }) => Some(paint::Paint::Pattern(
+            paint::PatternImage::checker(4, 0xFFC0C0C0, 0xFFFFFFFF),
+        )),
+        Some(_) => None,
+    }
+}
+
+// Nudges for the active series: (dx, dy, scale factor, angle step).
+fn series_step(action: Action) -> Option<(i32, i32, f32, f32)> {
+    match action {
+        Action::SeriesLeft => Some((-10, 0, 1.0, 0.0)),
+        Action::SeriesRight => Some((10, 0, 1.0, 0.0)),
+        Action::SeriesUp => Some((0, -10, 1.0, 0.0)),
+        Action::SeriesDown => Some((0, 10, 1.0, 0.0)),
+        Action::SeriesTurnLeft => Some((0, 0, 1.0, -5.0 * 0.017453293)),
+        Action::SeriesTurnRight => Some((0, 0, 1.0, 5.0 * 0.017453293)),
+        Action::SeriesGrow => Some((0, 0, 1.1, 0.0)),
+        Action::SeriesShrink => Some((0, 0, 1.0 / 1.1, 0.0)),
+        _ => None,
+    }
+}
+
+fn transform_series(
+    scene: &mut scene::Scene,
+    history: &mut History,
+    index: usize,
+    dx: i32,
+    dy: i32,
+    factor: f32,
+    turn: f32,
+) {
+    let series = match scene.series.get_mut(index) {
+        Some(series) => series,
+        None => return,
+    };
+
+    let before = SeriesTransform {
+        x: series.x,
+        y: series.y,
+        scale: series.scale,
+        angle: series.angle,
+    };
+    let after = SeriesTransform {
+        x: before.x + dx,
+        y: before.y + dy,
+        scale: before.scale * factor,
+        angle: before.angle + turn,
+    };
+
+    series.set_transform(after.x, after.y, after.scale, after.angle);
+    history.record(Command::TransformSeries {
+        index,
+        before,
+        after,
+    });
+}
+
+// Input is "<character> <image path>". The traced glyph replaces the
+// character's, is saved with the other custom glyphs and shows up in every
+// series straight away.
+fn trace_glyph(input: &str, scene: &mut scene::Scene) {
+    let mut chars = input.trim_start().chars();
+    let number = match chars.next() {
+        Some(number) => number,
+        None => {
+            hud::status(String::from("Trace needs a character and an image"));
+            return;
+        }
+    };
+    let path = chars.as_str().trim();
+    if path.is_empty() {
+        hud::status(format!("No image given to trace '{}' from", number));
+        return;
+    }
+
+    match trace::trace_image(path, number, 128, 1.5) {
+        // trace_pixels already said there was nothing to trace.
+        Ok(glyph) if glyph.lines.is_empty() && glyph.bezier_curves.is_empty() => {}
+        Ok(glyph) => {
+            hud::status(format!(
+                "Traced '{}' from '{}': {} lines and {} curves",
+                number,
+                path,
+                glyph.lines.len(),
+                glyph.bezier_curves.len()
+            ));
+            numbers::set_custom_glyph(glyph);
+            for series in scene.series.iter_mut() {
+                series.set_transform(series.x, series.y, series.scale, series.angle);
+            }
+
+            let glyphs = numbers::custom_glyphs();
+            if let Err(e) = glyph_file::save_glyphs(&glyphs, glyph_editor::GLYPH_PATH) {
+                hud::status(format!(
+                    "Failed to save '{}': {}",
+                    glyph_editor::GLYPH_PATH,
+                    e
+                ));
+            }
+        }
+        Err(e) => hud::status(format!("Failed to trace '{}': {}", path, e)),
+    }
+}
+
+// The window reduced to the palette and dither method picked in
+// DitherSettings, written as an indexed BMP and PNG named after the bit
+// depth.
+fn save_dithered(
+    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+    settings: &dither::DitherSettings,
+) {
+    let read = canvas
+        .output_size()
+        .and_then(|size| Ok((size, canvas.read_pixels(None, PixelFormatEnum::ARGB8888)?)));
+    let ((width, height), pixels) = match read {
+        Ok(read) => read,
+        Err(e) => {
+            hud::status(format!("Failed to read the window: {}", e));
+            return;
+        }
+    };
+
+    let image = settings.reduce(&pixels, width, height);
+
+    let bmp_path = format!("output_{}bit.bmp", settings.bits());
+    match sdl_to_bmp::save_indexed_bmp(&image, &bmp_path) {
+        Ok(()) => hud::status(format!("Image saved as '{}'", bmp_path)),
+        Err(e) => hud::status(format!("Failed to save '{}': {}", bmp_path, e)),
+    }
+    let png_path = format!("output_{}bit.png", settings.bits());
+    match sdl_to_bmp::save_indexed_png(&image, &png_path) {
+        Ok(()) => hud::status(format!("Image saved as '{}'", png_path)),
+        Err(e) => hud::status(format!("Failed to save '{}': {}", png_path, e)),
+    }
+}
+
+// The scene at POSTER_SCALE times its size, rendered in tiles across all
+// cores. Like the tiled renderer it draws outlines only.
+fn save_poster(scene: &scene::Scene) {
+    let width = W * POSTER_SCALE;
+    let height = H * POSTER_SCALE;
+
+    let mut camera = camera::Camera::new(width, height);
+    camera.zoom = POSTER_SCALE as f32;
+    let commands = camera.view(scene).draw_commands();
+
+    let poster = tiles::render_tiled(
+        &commands,
+        width,
+        height,
+        0xFFFFFFFF,
+        tiles::DEFAULT_TILE_SIZE,
+        tiles::default_thread_count(),
+    );
+
+    match sdl_to_bmp::save_pixels_to_bmp(&poster.pixels, width, height, "poster.bmp") {
+        Ok(()) => hud::status(String::from("Image saved as 'poster.bmp'")),
+        Err(e) => hud::status(format!("Failed to save 'poster.bmp': {}", e)),
+    }
 }
@@ -0,0 +1,287 @@
+use std::f32::consts::PI;
+
+use crate::clip;
+use crate::numbers::{Number, NumberSeries};
+use crate::raster::Primitive;
+
+const SUBSAMPLES: usize = 4;
+
+pub type Edge = ((f32, f32), (f32, f32));
+
+// Per pixel coverage in 0..=255, 255 meaning fully inside the clip path.
+pub struct CoverageMask {
+    pub width: u32,
+    pub height: u32,
+    pub coverage: Vec<u8>,
+}
+
+impl CoverageMask {
+    pub fn new(width: u32, height: u32, value: u8) -> Self {
+        CoverageMask {
+            width,
+            height,
+            coverage: vec![value; (width * height) as usize],
+        }
+    }
+
+    // Fills the area enclosed by the edges with the even-odd rule. The edges
+    // don't need to be ordered into contours, only to close up overall.
+    pub fn from_edges(edges: &[Edge], width: u32, height: u32) -> Self {
+        let mut accumulated = vec![0.0f32; (width * height) as usize];
+
+        let min_y = edges
+            .iter()
+            .map(|(a, b)| a.1.min(b.1))
+            .fold(f32::MAX, f32::min)
+            .floor()
+            .max(0.0) as i32;
+        let max_y = edges
+            .iter()
+            .map(|(a, b)| a.1.max(b.1))
+            .fold(f32::MIN, f32::max)
+            .ceil()
+            .min(height as f32 - 1.0) as i32;
+
+        let weight = 1.0 / SUBSAMPLES as f32;
+
+        for y in min_y..=max_y {
+            let row = (y as u32 * width) as usize;
+
+            for sample in 0..SUBSAMPLES {
+                let scan_y = y as f32 + (sample as f32 + 0.5) * weight;
+                let mut crossings: Vec<f32> = Vec::new();
+
+                for (a, b) in edges.iter() {
+                    if (a.1 <= scan_y && b.1 > scan_y) || (b.1 <= scan_y && a.1 > scan_y) {
+                        crossings.push(a.0 + (scan_y - a.1) / (b.1 - a.1) * (b.0 - a.0));
+                    }
+                }
+
+                crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                for pair in crossings.chunks(2) {
+                    if let [start, end] = pair {
+                        let start = start.max(0.0);
+                        let end = end.min(width as f32);
+
+                        let mut x = start.floor() as i32;
+                        while (x as f32) < end {
+                            let overlap = end.min(x as f32 + 1.0) - start.max(x as f32);
+                            accumulated[row + x as usize] += overlap * weight;
+                            x += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        CoverageMask {
+            width,
+            height,
+            coverage: accumulated
+                .iter()
+                .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect(),
+        }
+    }
+
+    pub fn from_number(number: &Number, width: u32, height: u32) -> Self {
+        CoverageMask::from_edges(&number_edges(number), width, height)
+    }
+
+    pub fn from_number_series(series: &NumberSeries, width: u32, height: u32) -> Self {
+        let edges: Vec<Edge> = series.numbers.iter().flat_map(number_edges).collect();
+        CoverageMask::from_edges(&edges, width, height)
+    }
+
+    pub fn from_primitives(primitives: &[Primitive], width: u32, height: u32) -> Self {
+        let edges: Vec<Edge> = primitives.iter().flat_map(primitive_edges).collect();
+        CoverageMask::from_edges(&edges, width, height)
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return 0;
+        }
+
+        self.coverage[(y as u32 * self.width + x as u32) as usize]
+    }
+
+    pub fn intersect(&mut self, other: &CoverageMask) {
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                let index = (y as u32 * self.width + x as u32) as usize;
+                let combined = self.coverage[index] as u32 * other.get(x, y) as u32 / 255;
+                self.coverage[index] = combined as u8;
+            }
+        }
+    }
+
+    pub fn invert(&mut self) {
+        for c in self.coverage.iter_mut() {
+            *c = 255 - *c;
+        }
+    }
+}
+
+// Flattened outline of a glyph. Exact duplicate segments are dropped since
+// they would cancel each other out under even-odd.
+pub fn number_edges(number: &Number) -> Vec<Edge> {
+    let mut edges: Vec<Edge> = Vec::new();
+
+    for line in number.lines.iter() {
+        if let [a, b, ..] = line.controll_points.as_slice() {
+            edges.push(((a.x as f32, a.y as f32), (b.x as f32, b.y as f32)));
+        }
+    }
+
+    for curve in number.bezier_curves.iter() {
+        if let [p_1, p_2, p_3, p_4, ..] = curve.controll_points.as_slice() {
+            let points = clip::flatten_cubic_bezier(*p_1, *p_2, *p_3, *p_4);
+            for pair in points.windows(2) {
+                edges.push((pair[0], pair[1]));
+            }
+        }
+    }
+
+    let mut unique: Vec<Edge> = Vec::with_capacity(edges.len());
+    for (a, b) in edges {
+        let edge = if a <= b { (a, b) } else { (b, a) };
+        if !unique.contains(&edge) {
+            unique.push(edge);
+        }
+    }
+
+    unique
+}
+
+pub fn primitive_edges(primitive: &Primitive) -> Vec<Edge> {
+    let to_f32 = |p: &sdl2::rect::Point| (p.x as f32, p.y as f32);
+
+    let outline: Vec<(f32, f32)> = match primitive {
+        Primitive::Point(_) => Vec::new(),
+        Primitive::Line(a, b) => vec![to_f32(a), to_f32(b)],
+        Primitive::Circle(center, radius) => {
+            let segments = 64;
+            (0..=segments)
+                .map(|i| {
+                    let angle = i as f32 / segments as f32 * 2.0 * PI;
+                    (
+                        center.x as f32 + *radius as f32 * angle.cos(),
+                        center.y as f32 + *radius as f32 * angle.sin(),
+                    )
+                })
+                .collect()
+        }
+        Primitive::QuadraticBezier([p_1, p_2, p_3]) => {
+            clip::flatten_quadratic_bezier(*p_1, *p_2, *p_3)
+        }
+        Primitive::CubicBezier([p_1, p_2, p_3, p_4]) => {
+            clip::flatten_cubic_bezier(*p_1, *p_2, *p_3, *p_4)
+        }
+        Primitive::FilledPolygon(points) => {
+            let mut outline: Vec<(f32, f32)> = points.iter().map(to_f32).collect();
+            if let Some(first) = outline.first().copied() {
+                outline.push(first);
+            }
+            outline
+        }
+    };
+
+    outline.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+// Nested clip paths. Each pushed mask is intersected with the one below it,
+// so the top of the stack is always the effective clip.
+pub struct MaskStack {
+    masks: Vec<CoverageMask>,
+}
+
+impl MaskStack {
+    pub fn new() -> Self {
+        MaskStack { masks: Vec::new() }
+    }
+
+    pub fn push(&mut self, mut mask: CoverageMask) {
+        if let Some(top) = self.masks.last() {
+            mask.intersect(top);
+        }
+
+        self.masks.push(mask);
+    }
+
+    pub fn pop(&mut self) -> Option<CoverageMask> {
+        self.masks.pop()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    pub fn coverage(&self, x: i32, y: i32) -> u8 {
+        match self.masks.last() {
+            Some(mask) => mask.get(x, y),
+            None => 255,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(left: f32, top: f32, size: f32) -> Vec<Edge> {
+        let (right, bottom) = (left + size, top + size);
+        vec![
+            ((left, top), (right, top)),
+            ((right, top), (right, bottom)),
+            ((right, bottom), (left, bottom)),
+            ((left, bottom), (left, top)),
+        ]
+    }
+
+    #[test]
+    fn even_odd_leaves_the_hole_empty() {
+        let mut edges = square(2.0, 2.0, 16.0);
+        edges.extend(square(7.0, 7.0, 6.0));
+        let mask = CoverageMask::from_edges(&edges, 20, 20);
+
+        assert_eq!(mask.get(0, 0), 0);
+        assert_eq!(mask.get(4, 4), 255);
+        assert_eq!(mask.get(10, 10), 0);
+        assert_eq!(mask.get(16, 10), 255);
+        assert_eq!(mask.get(19, 19), 0);
+
+        let covered = mask.coverage.iter().filter(|c| **c == 255).count();
+        assert_eq!(covered, 16 * 16 - 6 * 6);
+        assert!(mask.coverage.iter().all(|c| *c == 0 || *c == 255));
+    }
+
+    #[test]
+    fn partial_pixels_get_partial_coverage() {
+        let mask = CoverageMask::from_edges(&square(2.5, 2.0, 4.0), 10, 10);
+        assert_eq!(mask.get(2, 3), 128);
+        assert_eq!(mask.get(4, 3), 255);
+        assert_eq!(mask.get(6, 3), 128);
+    }
+
+    #[test]
+    fn stack_intersects_with_the_mask_below() {
+        let mut stack = MaskStack::new();
+        assert_eq!(stack.coverage(3, 3), 255);
+
+        stack.push(CoverageMask::from_edges(&square(0.0, 0.0, 6.0), 10, 10));
+        stack.push(CoverageMask::from_edges(&square(4.0, 4.0, 6.0), 10, 10));
+        assert_eq!(stack.coverage(5, 5), 255);
+        assert_eq!(stack.coverage(2, 2), 0);
+        assert_eq!(stack.coverage(8, 8), 0);
+
+        stack.pop();
+        assert_eq!(stack.coverage(2, 2), 255);
+        assert_eq!(stack.coverage(8, 8), 0);
+
+        stack.pop();
+        assert!(stack.is_empty());
+        assert_eq!(stack.coverage(8, 8), 255);
+    }
+}
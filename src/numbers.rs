@@ -1,29 +1,42 @@
+use crate::effects::TextEffects;
 use crate::graphics::{self, translate_number, CubicBezierCurve, Line};
+use crate::hud;
 use sdl2::rect::Point;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
+use std::sync::Mutex;
 
-const DEFAULT_NUM_WIDTH: i32 = 80;
-const DEFAULT_NUM_HEIGHT: i32 = 120;
+pub const DEFAULT_NUM_WIDTH: i32 = 80;
+pub const DEFAULT_NUM_HEIGHT: i32 = 120;
 
+// Glyphs loaded from a glyph file or saved by the glyph editor, they take
+// the place of the built in create_digit_N ones.
+static CUSTOM_GLYPHS: Mutex<Vec<Number>> = Mutex::new(Vec::new());
+
+#[derive(Clone)]
 pub struct NumberSeries {
     pub x: i32,
     pub y: i32,
     pub number_str: String,
+    pub scale: f32,
+    pub angle: f32,
+    pub effects: TextEffects,
+    // Index into Scene::layers, like Shape::layer.
+    pub layer: usize,
     pub numbers: Vec<Number>,
 }
 
 impl NumberSeries {
     pub fn new(x: i32, y: i32, number_str: String, scale: f32, angle: f32) -> Self {
-        if number_str.is_empty() {
-            panic!("Empty number string!!!!");
-        }
-
         let mut numbers: Vec<Number> = Vec::new();
 
+        // Characters without a glyph leave a gap, so the glyphs stay where
+        // char_offset expects them.
         let num_x = DEFAULT_NUM_WIDTH;
         for (i, c) in number_str.chars().enumerate() {
-            numbers.push(create_digit(c, num_x * i as i32, y).unwrap()); // TODO: at least treat the fucking error
+            if let Some(number) = create_digit(c, num_x * i as i32, y) {
+                numbers.push(number);
+            }
         }
 
         for (i, number) in numbers.iter_mut().enumerate() {
@@ -42,17 +55,77 @@ impl NumberSeries {
             x: x,
             y: y,
             number_str: number_str,
+            scale: scale,
+            angle: angle,
+            effects: TextEffects::none(),
+            layer: 0,
             numbers: numbers,
         }
     }
     
-    pub fn draw(&mut self, canvas: &mut Canvas<Window>, fill: bool) {
-        for num in self.numbers.iter_mut() {
+    // Lays the glyphs out again for a new position, scale or angle.
+    pub fn set_transform(&mut self, x: i32, y: i32, scale: f32, angle: f32) {
+        let (effects, layer) = (self.effects, self.layer);
+        *self = NumberSeries::new(x, y, self.number_str.clone(), scale, angle);
+        self.effects = effects;
+        self.layer = layer;
+    }
+
+    pub fn set_text(&mut self, number_str: String) {
+        let (effects, layer) = (self.effects, self.layer);
+        *self = NumberSeries::new(self.x, self.y, number_str, self.scale, self.angle);
+        self.effects = effects;
+        self.layer = layer;
+    }
+
+    // Glyph space (DEFAULT_NUM_WIDTH per character, y down from the top of
+    // the em box) to scene coordinates, the same scale and rotation about
+    // (x, y) that new applies to the glyphs.
+    pub fn to_scene(&self, u: f32, v: f32) -> Point {
+        let (sin, cos) = self.angle.sin_cos();
+        let (u, v) = (u * self.scale, v * self.scale);
+        Point::new(
+            self.x + (u * cos - v * sin).round() as i32,
+            self.y + (u * sin + v * cos).round() as i32,
+        )
+    }
+
+    pub fn to_glyph_space(&self, point: Point) -> (f32, f32) {
+        let (sin, cos) = self.angle.sin_cos();
+        let dx = (point.x - self.x) as f32;
+        let dy = (point.y - self.y) as f32;
+        let scale = if self.scale == 0.0 { 1.0 } else { self.scale };
+        ((dx * cos + dy * sin) / scale, (dy * cos - dx * sin) / scale)
+    }
+
+    // Left edge of the character at index in glyph space.
+    pub fn char_offset(index: usize) -> f32 {
+        (DEFAULT_NUM_WIDTH * index as i32) as f32
+    }
+
+    // Caret index for a click on the series, None if it missed. Empty
+    // series still take clicks on the first character cell.
+    pub fn caret_at(&self, point: Point) -> Option<usize> {
+        let (u, v) = self.to_glyph_space(point);
+        let count = self.number_str.chars().count();
+        let width = NumberSeries::char_offset(count.max(1));
+
+        if u < 0.0 || u > width || v < 0.0 || v > DEFAULT_NUM_HEIGHT as f32 {
+            return None;
+        }
+
+        let index = (u / DEFAULT_NUM_WIDTH as f32).round() as usize;
+        Some(index.min(count))
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, fill: bool) {
+        for num in self.numbers.iter() {
             num.draw(canvas, fill);
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Number {
     pub number: char,
     pub w: i32,
@@ -87,22 +160,51 @@ impl Number {
         }
     }
 
-    pub fn draw(&mut self, canvas: &mut Canvas<Window>, fill: bool) {
-        for line in self.lines.iter_mut() {
+    pub fn draw(&self, canvas: &mut Canvas<Window>, fill: bool) {
+        for line in self.lines.iter() {
             line.draw(canvas);
         }
 
-        for bezier_curve in self.bezier_curves.iter_mut() {
+        for bezier_curve in self.bezier_curves.iter() {
             bezier_curve.draw(canvas);
         }
 
         if fill {
-            graphics::flood_fill(Point::new(self.x, self.y), 0, canvas);
+            if let Err(e) = graphics::flood_fill(Point::new(self.x, self.y), 0, canvas) {
+                hud::status(format!("Failed to fill '{}': {}", self.number, e));
+            }
         }
     }
 }
 
+pub fn has_glyph(number: char) -> bool {
+    number.is_ascii_digit() || CUSTOM_GLYPHS.lock().unwrap().iter().any(|g| g.number == number)
+}
+
+// Custom glyphs are kept at the origin like the built in ones.
+pub fn set_custom_glyph(glyph: Number) {
+    let mut glyphs = CUSTOM_GLYPHS.lock().unwrap();
+    glyphs.retain(|g| g.number != glyph.number);
+    glyphs.push(glyph);
+}
+
+pub fn custom_glyphs() -> Vec<Number> {
+    CUSTOM_GLYPHS.lock().unwrap().clone()
+}
+
 pub fn create_digit(number: char, x: i32, y: i32) -> Option<Number> {
+    let custom = CUSTOM_GLYPHS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|g| g.number == number)
+        .cloned();
+    if let Some(mut glyph) = custom {
+        glyph.x = x;
+        glyph.y = y;
+        return Some(glyph);
+    }
+
     match number {
         '0' => Some(create_digit_0(x, y)),
         '1' => Some(create_digit_1(x, y)),
@@ -115,7 +217,7 @@ pub fn create_digit(number: char, x: i32, y: i32) -> Option<Number> {
         '8' => Some(create_digit_8(x, y)),
         '9' => Some(create_digit_9(x, y)),
         _ => {
-            println!("Number not recognized: {}", number);
+            hud::status(format!("Number not recognized: {}", number));
             return None;
         }
     }
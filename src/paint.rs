@@ -0,0 +1,583 @@
+use std::f32::consts::PI;
+
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::gamma::Blending;
+use crate::mask::CoverageMask;
+use crate::numbers::NumberSeries;
+use crate::raster::{PixelBuffer, Primitive};
+
+#[derive(Clone, Copy)]
+pub struct ColorStop {
+    pub offset: f32,
+    pub color: u32,
+}
+
+impl ColorStop {
+    pub fn new(offset: f32, color: u32) -> Self {
+        ColorStop { offset, color }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Spread {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+const SPREADS: [(&str, Spread); 3] = [
+    ("pad", Spread::Pad),
+    ("repeat", Spread::Repeat),
+    ("reflect", Spread::Reflect),
+];
+
+impl Spread {
+    pub fn name(&self) -> &'static str {
+        SPREADS
+            .iter()
+            .find(|(_, spread)| spread == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("pad")
+    }
+
+    pub fn from_name(name: &str) -> Option<Spread> {
+        SPREADS
+            .iter()
+            .find(|(spread_name, _)| *spread_name == name)
+            .map(|(_, spread)| *spread)
+    }
+
+    fn apply(&self, t: f32) -> f32 {
+        match self {
+            Spread::Pad => t.clamp(0.0, 1.0),
+            Spread::Repeat => t - t.floor(),
+            Spread::Reflect => {
+                let t = t.rem_euclid(2.0);
+                if t > 1.0 {
+                    2.0 - t
+                } else {
+                    t
+                }
+            }
+        }
+    }
+}
+
+// Tileable bitmap, pixels stored as ARGB like the rest of the paints.
+#[derive(Clone)]
+pub struct PatternImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u32>,
+}
+
+impl PatternImage {
+    pub fn load(path: &str) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.to_rgba8();
+        let (width, height) = image.dimensions();
+
+        let pixels = image
+            .pixels()
+            .map(|p| {
+                let [r, g, b, a] = p.0;
+                ((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+            })
+            .collect();
+
+        Ok(PatternImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn from_buffer(buffer: &PixelBuffer) -> Self {
+        let mut pixels = Vec::with_capacity((buffer.width * buffer.height) as usize);
+        for y in 0..buffer.height as i32 {
+            for x in 0..buffer.width as i32 {
+                pixels.push(buffer.get(x, y));
+            }
+        }
+
+        PatternImage {
+            width: buffer.width,
+            height: buffer.height,
+            pixels,
+        }
+    }
+
+    // Squares of size pixels, alternating between the two colors.
+    pub fn checker(size: u32, first: u32, second: u32) -> Self {
+        let size = size.max(1);
+        let side = size * 2;
+        let pixels = (0..side * side)
+            .map(|i| {
+                if (i % side < size) == (i / side < size) {
+                    first
+                } else {
+                    second
+                }
+            })
+            .collect();
+
+        PatternImage {
+            width: side,
+            height: side,
+            pixels,
+        }
+    }
+
+    pub fn get_wrapped(&self, x: f32, y: f32) -> u32 {
+        if self.width == 0 || self.height == 0 {
+            return 0;
+        }
+
+        let px = (x.floor() as i64).rem_euclid(self.width as i64) as u32;
+        let py = (y.floor() as i64).rem_euclid(self.height as i64) as u32;
+        self.pixels[(py * self.width + px) as usize]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HatchStyle {
+    Lines,
+    CrossHatch,
+    Dots,
+}
+
+const HATCH_STYLES: [(&str, HatchStyle); 3] = [
+    ("lines", HatchStyle::Lines),
+    ("cross", HatchStyle::CrossHatch),
+    ("dots", HatchStyle::Dots),
+];
+
+impl HatchStyle {
+    pub fn name(&self) -> &'static str {
+        HATCH_STYLES
+            .iter()
+            .find(|(_, style)| style == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("lines")
+    }
+
+    pub fn from_name(name: &str) -> Option<HatchStyle> {
+        HATCH_STYLES
+            .iter()
+            .find(|(style_name, _)| *style_name == name)
+            .map(|(_, style)| *style)
+    }
+}
+
+// Colors are ARGB and alpha is honored when filling, so a fully transparent
+// color (like the gaps of a hatch without background) leaves the target as is.
+#[derive(Clone)]
+pub enum Paint {
+    Solid(u32),
+    LinearGradient {
+        start: (f32, f32),
+        end: (f32, f32),
+        stops: Vec<ColorStop>,
+        spread: Spread,
+    },
+    RadialGradient {
+        center: (f32, f32),
+        radius: f32,
+        stops: Vec<ColorStop>,
+        spread: Spread,
+    },
+    // Sweeps clockwise from start_angle (radians) around center.
+    ConicGradient {
+        center: (f32, f32),
+        start_angle: f32,
+        stops: Vec<ColorStop>,
+        spread: Spread,
+    },
+    Pattern(PatternImage),
+    // Spacing and thickness are in paint space units, so the hatch scales
+    // and rotates with the glyph it fills.
+    Hatch {
+        style: HatchStyle,
+        angle: f32,
+        spacing: f32,
+        thickness: f32,
+        color: u32,
+        background: u32,
+    },
+}
+
+impl Paint {
+    // Color at a point given in the paint's own coordinate space.
+    pub fn color_at(&self, x: f32, y: f32, blending: Blending) -> u32 {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::LinearGradient {
+                start,
+                end,
+                stops,
+                spread,
+            } => {
+                let dx = end.0 - start.0;
+                let dy = end.1 - start.1;
+                let length_squared = dx * dx + dy * dy;
+                let t = if length_squared == 0.0 {
+                    0.0
+                } else {
+                    ((x - start.0) * dx + (y - start.1) * dy) / length_squared
+                };
+
+                color_from_stops(stops, spread.apply(t), blending)
+            }
+            Paint::RadialGradient {
+                center,
+                radius,
+                stops,
+                spread,
+            } => {
+                let distance = ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt();
+                let t = if *radius == 0.0 {
+                    1.0
+                } else {
+                    distance / radius
+                };
+
+                color_from_stops(stops, spread.apply(t), blending)
+            }
+            Paint::ConicGradient {
+                center,
+                start_angle,
+                stops,
+                spread,
+            } => {
+                let angle = (y - center.1).atan2(x - center.0) - start_angle;
+                let t = angle.rem_euclid(2.0 * PI) / (2.0 * PI);
+
+                color_from_stops(stops, spread.apply(t), blending)
+            }
+            Paint::Pattern(image) => image.get_wrapped(x, y),
+            Paint::Hatch {
+                style,
+                angle,
+                spacing,
+                thickness,
+                color,
+                background,
+            } => {
+                if hatch_covers(*style, *angle, *spacing, *thickness, x, y) {
+                    *color
+                } else {
+                    *background
+                }
+            }
+        }
+    }
+}
+
+fn hatch_covers(style: HatchStyle, angle: f32, spacing: f32, thickness: f32, x: f32, y: f32) -> bool {
+    if spacing <= 0.0 {
+        return true;
+    }
+
+    let (sin, cos) = angle.sin_cos();
+    let across = x * cos + y * sin;
+    let along = -x * sin + y * cos;
+
+    let distance_to_line = |d: f32| {
+        let m = d.rem_euclid(spacing);
+        m.min(spacing - m)
+    };
+
+    match style {
+        HatchStyle::Lines => distance_to_line(across) <= thickness / 2.0,
+        HatchStyle::CrossHatch => {
+            distance_to_line(across) <= thickness / 2.0
+                || distance_to_line(along) <= thickness / 2.0
+        }
+        HatchStyle::Dots => {
+            let dx = distance_to_line(across);
+            let dy = distance_to_line(along);
+            (dx * dx + dy * dy).sqrt() <= thickness / 2.0
+        }
+    }
+}
+
+pub fn color_from_stops(stops: &[ColorStop], t: f32, blending: Blending) -> u32 {
+    match stops {
+        [] => 0,
+        [only] => only.color,
+        [first, .., last] => {
+            if t <= first.offset {
+                return first.color;
+            }
+            if t >= last.offset {
+                return last.color;
+            }
+
+            for pair in stops.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                if t >= a.offset && t <= b.offset {
+                    let span = b.offset - a.offset;
+                    let local = if span == 0.0 {
+                        0.0
+                    } else {
+                        (t - a.offset) / span
+                    };
+                    return blending.mix_colors(a.color, b.color, local);
+                }
+            }
+
+            last.color
+        }
+    }
+}
+
+// Maps between window coordinates and the coordinate space a paint is
+// defined in. For glyphs this is the em box of the digit, so paints follow
+// the series' translation, scale and rotation.
+#[derive(Clone, Copy)]
+pub struct PaintSpace {
+    pub origin: (f32, f32),
+    pub offset: (f32, f32),
+    pub scale: f32,
+    pub angle: f32,
+}
+
+impl PaintSpace {
+    pub fn identity() -> Self {
+        PaintSpace {
+            origin: (0.0, 0.0),
+            offset: (0.0, 0.0),
+            scale: 1.0,
+            angle: 0.0,
+        }
+    }
+
+    pub fn to_local(&self, x: f32, y: f32) -> (f32, f32) {
+        let dx = x - self.origin.0;
+        let dy = y - self.origin.1;
+
+        let (sin, cos) = (-self.angle).sin_cos();
+        let rx = dx * cos - dy * sin;
+        let ry = dx * sin + dy * cos;
+
+        let scale = if self.scale == 0.0 { 1.0 } else { self.scale };
+        (rx / scale - self.offset.0, ry / scale - self.offset.1)
+    }
+
+    pub fn to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        let sx = (x + self.offset.0) * self.scale;
+        let sy = (y + self.offset.1) * self.scale;
+
+        let (sin, cos) = self.angle.sin_cos();
+        (
+            sx * cos - sy * sin + self.origin.0,
+            sx * sin + sy * cos + self.origin.1,
+        )
+    }
+}
+
+pub fn fill_mask(target: &mut PixelBuffer, mask: &CoverageMask, paint: &Paint, space: &PaintSpace) {
+    for y in 0..mask.height.min(target.height) as i32 {
+        for x in 0..mask.width.min(target.width) as i32 {
+            let coverage = mask.get(x, y);
+            if coverage == 0 {
+                continue;
+            }
+
+            let (local_x, local_y) = space.to_local(x as f32 + 0.5, y as f32 + 0.5);
+            let color = paint.color_at(local_x, local_y, target.settings.blending);
+
+            let coverage = (coverage as u32 * (color >> 24) / 255) as u8;
+            if coverage == 0 {
+                continue;
+            }
+            let color = color | 0xFF000000;
+
+            let color = if coverage == 255 {
+                color
+            } else {
+                target.blend(x, y, color, coverage)
+            };
+
+            target.set(x, y, color);
+        }
+    }
+}
+
+pub fn fill_primitives(target: &mut PixelBuffer, primitives: &[Primitive], paint: &Paint) {
+    let mask = CoverageMask::from_primitives(primitives, target.width, target.height);
+    fill_mask(target, &mask, paint, &PaintSpace::identity());
+}
+
+impl NumberSeries {
+    // Each digit gets its own paint space, so a gradient defined over the em
+    // box repeats per digit and turns with the series.
+    pub fn fill(&self, target: &mut PixelBuffer, paint: &Paint) {
+        for (i, number) in self.numbers.iter().enumerate() {
+            let mask = CoverageMask::from_number(number, target.width, target.height);
+            fill_mask(target, &mask, paint, &self.paint_space(i));
+        }
+    }
+
+    pub fn paint_space(&self, index: usize) -> PaintSpace {
+        PaintSpace {
+            origin: (self.x as f32, self.y as f32),
+            offset: (self.numbers[index].x as f32, 0.0),
+            scale: self.scale,
+            angle: self.angle,
+        }
+    }
+
+    pub fn draw_filled(&self, canvas: &mut Canvas<Window>, paint: &Paint) {
+        let mut buffer = PixelBuffer::from_canvas(canvas);
+        self.fill(&mut buffer, paint);
+        buffer.copy_to_canvas(canvas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamma::Blending::Linear;
+
+    fn black_to_white(spread: Spread) -> Paint {
+        Paint::LinearGradient {
+            start: (0.0, 0.0),
+            end: (10.0, 0.0),
+            stops: vec![
+                ColorStop::new(0.0, 0xFF000000),
+                ColorStop::new(1.0, 0xFFFFFFFF),
+            ],
+            spread,
+        }
+    }
+
+    #[test]
+    fn stops_are_interpolated_in_between() {
+        let stops = [
+            ColorStop::new(0.2, 0xFFFF0000),
+            ColorStop::new(0.5, 0xFF00FF00),
+            ColorStop::new(0.8, 0xFF0000FF),
+        ];
+        assert_eq!(color_from_stops(&stops, 0.0, Linear), 0xFFFF0000);
+        assert_eq!(color_from_stops(&stops, 0.2, Linear), 0xFFFF0000);
+        assert_eq!(color_from_stops(&stops, 0.5, Linear), 0xFF00FF00);
+        assert_eq!(color_from_stops(&stops, 1.0, Linear), 0xFF0000FF);
+
+        // Between green and blue, no red left.
+        let [b, g, r, a] = color_from_stops(&stops, 0.65, Linear).to_le_bytes();
+        assert_eq!((a, r), (0xFF, 0));
+        assert!(g > 0 && g < 0xFF && b > 0 && b < 0xFF);
+
+        assert_eq!(color_from_stops(&[], 0.5, Linear), 0);
+        assert_eq!(color_from_stops(&stops[..1], 0.9, Linear), 0xFFFF0000);
+    }
+
+    #[test]
+    fn spreads_outside_the_gradient() {
+        assert_eq!(Spread::Pad.apply(-0.5), 0.0);
+        assert_eq!(Spread::Pad.apply(1.5), 1.0);
+        assert_eq!(Spread::Repeat.apply(1.25), 0.25);
+        assert_eq!(Spread::Repeat.apply(-0.25), 0.75);
+        assert_eq!(Spread::Reflect.apply(1.25), 0.75);
+        assert_eq!(Spread::Reflect.apply(-0.25), 0.25);
+        assert_eq!(Spread::Reflect.apply(2.25), 0.25);
+
+        let pad = black_to_white(Spread::Pad);
+        assert_eq!(pad.color_at(-5.0, 0.0, Linear), 0xFF000000);
+        assert_eq!(pad.color_at(25.0, 0.0, Linear), 0xFFFFFFFF);
+
+        let repeat = black_to_white(Spread::Repeat);
+        assert_eq!(
+            repeat.color_at(12.5, 0.0, Linear),
+            repeat.color_at(2.5, 0.0, Linear)
+        );
+
+        let reflect = black_to_white(Spread::Reflect);
+        assert_eq!(
+            reflect.color_at(12.5, 0.0, Linear),
+            reflect.color_at(7.5, 0.0, Linear)
+        );
+        assert_eq!(
+            reflect.color_at(-2.5, 0.0, Linear),
+            reflect.color_at(2.5, 0.0, Linear)
+        );
+    }
+
+    #[test]
+    fn radial_and_conic_gradients() {
+        let stops = vec![
+            ColorStop::new(0.0, 0xFF000000),
+            ColorStop::new(1.0, 0xFFFFFFFF),
+        ];
+        let radial = Paint::RadialGradient {
+            center: (5.0, 5.0),
+            radius: 4.0,
+            stops: stops.clone(),
+            spread: Spread::Pad,
+        };
+        assert_eq!(radial.color_at(5.0, 5.0, Linear), 0xFF000000);
+        assert_eq!(radial.color_at(5.0, 20.0, Linear), 0xFFFFFFFF);
+        assert_eq!(radial.color_at(7.0, 5.0, Linear), radial.color_at(5.0, 3.0, Linear));
+
+        let conic = Paint::ConicGradient {
+            center: (0.0, 0.0),
+            start_angle: 0.0,
+            stops,
+            spread: Spread::Pad,
+        };
+        assert_eq!(conic.color_at(1.0, 0.0, Linear), 0xFF000000);
+        assert_eq!(conic.color_at(-1.0, 0.0, Linear), conic.color_at(-2.0, 0.0, Linear));
+    }
+    #[test]
+    fn hatch_lines_repeat_at_the_spacing() {
+        let hatch = |style| Paint::Hatch {
+            style,
+            angle: 0.0,
+            spacing: 10.0,
+            thickness: 2.0,
+            color: 0xFF000000,
+            background: 0xFFFFFFFF,
+        };
+
+        // Lines run along y at x = 0, 10, 20... and 1 unit either side.
+        let lines = hatch(HatchStyle::Lines);
+        for x in [-20.0, -10.0, 0.0, 0.9, 9.5, 30.0] {
+            assert_eq!(lines.color_at(x, 3.0, Linear), 0xFF000000, "x = {}", x);
+        }
+        for x in [-5.0, 1.5, 5.0, 8.5, 25.0] {
+            assert_eq!(lines.color_at(x, 3.0, Linear), 0xFFFFFFFF, "x = {}", x);
+        }
+
+        let cross = hatch(HatchStyle::CrossHatch);
+        assert_eq!(cross.color_at(5.0, 20.0, Linear), 0xFF000000);
+        assert_eq!(cross.color_at(5.0, 15.0, Linear), 0xFFFFFFFF);
+
+        let dots = hatch(HatchStyle::Dots);
+        assert_eq!(dots.color_at(10.0, -10.0, Linear), 0xFF000000);
+        assert_eq!(dots.color_at(10.0, 5.0, Linear), 0xFFFFFFFF);
+    }
+
+    #[test]
+    fn patterns_tile_at_negative_coordinates() {
+        let image = PatternImage {
+            width: 2,
+            height: 3,
+            pixels: vec![1, 2, 3, 4, 5, 6],
+        };
+        assert_eq!(image.get_wrapped(0.0, 0.0), 1);
+        assert_eq!(image.get_wrapped(3.5, 4.0), 4);
+        assert_eq!(image.get_wrapped(-1.0, 0.0), 2);
+        assert_eq!(image.get_wrapped(-0.5, -0.5), 6);
+        assert_eq!(image.get_wrapped(-2.0, -3.0), 1);
+        assert_eq!(image.get_wrapped(-3.0, -4.0), 6);
+
+        let checker = PatternImage::checker(2, 7, 8);
+        assert_eq!((checker.width, checker.height), (4, 4));
+        assert_eq!(checker.get_wrapped(1.0, 1.0), 7);
+        assert_eq!(checker.get_wrapped(2.0, 1.0), 8);
+        assert_eq!(checker.get_wrapped(-1.0, 1.0), 8);
+        assert_eq!(checker.get_wrapped(-1.0, -1.0), 7);
+    }
+}
@@ -0,0 +1,414 @@
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::graphics;
+use crate::history::{Command, History, SeriesTransform};
+use crate::hud;
+use crate::layers::Layer;
+use crate::numbers;
+use crate::scene::Scene;
+use crate::selection::Snap;
+use crate::ui::{self, Ui};
+
+pub const PANEL_WIDTH: u32 = 180;
+const MARGIN: i32 = 10;
+const ROW: i32 = 40;
+const BUTTON: u32 = 24;
+const READOUT_HEIGHT: i32 = 14;
+const READOUT_WIDTH: i32 = 40;
+const SWATCH: u32 = 20;
+const GRID_SPACING: i32 = 10;
+const PALETTE: [(&str, u32); 6] = [
+    ("black", 0xFF000000),
+    ("red", 0xFFD02020),
+    ("green", 0xFF20A040),
+    ("blue", 0xFF2050D0),
+    ("orange", 0xFFE08020),
+    ("grey", 0xFF808080),
+];
+
+const TEXT_ID: &str = "series text";
+
+fn draw_arrow(canvas: &mut Canvas<Window>, rect: Rect, left: bool) {
+    let center = rect.center();
+    let dx = if left { 5 } else { -5 };
+    let tip = Point::new(center.x - dx, center.y);
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    graphics::draw_line(tip, Point::new(center.x + dx, center.y - 6), canvas);
+    graphics::draw_line(tip, Point::new(center.x + dx, center.y + 6), canvas);
+}
+
+fn draw_vertical_arrow(canvas: &mut Canvas<Window>, rect: Rect, up: bool) {
+    let center = rect.center();
+    let dy = if up { 5 } else { -5 };
+    let tip = Point::new(center.x, center.y - dy);
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    graphics::draw_line(tip, Point::new(center.x - 6, center.y + dy), canvas);
+    graphics::draw_line(tip, Point::new(center.x + 6, center.y + dy), canvas);
+}
+
+fn draw_plus(canvas: &mut Canvas<Window>, rect: Rect) {
+    let center = rect.center();
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    graphics::draw_line(
+        Point::new(center.x - 6, center.y),
+        Point::new(center.x + 6, center.y),
+        canvas,
+    );
+    graphics::draw_line(
+        Point::new(center.x, center.y - 6),
+        Point::new(center.x, center.y + 6),
+        canvas,
+    );
+}
+
+// Side panel editing the active NumberSeries, the active layer and a few
+// tool settings.
+// Sections are laid out top to bottom from begin, slider drags and text
+// edits each end up as one undo step.
+pub struct SeriesPanel {
+    text: String,
+    // Series and its transform from before the current slider drag.
+    drag_start: Option<(usize, SeriesTransform)>,
+    // Same for the layer opacity slider.
+    layer_drag_start: Option<(usize, Layer)>,
+    // Layout cursor.
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+impl SeriesPanel {
+    pub fn new() -> Self {
+        SeriesPanel {
+            text: String::new(),
+            drag_start: None,
+            layer_drag_start: None,
+            x: 0,
+            y: 0,
+            width: 0,
+        }
+    }
+
+    // Along the right edge of the window.
+    pub fn area(window_width: u32, window_height: u32) -> Rect {
+        Rect::new(
+            window_width as i32 - PANEL_WIDTH as i32,
+            0,
+            PANEL_WIDTH,
+            window_height.max(1),
+        )
+    }
+
+    pub fn begin(&mut self, canvas: &mut Canvas<Window>) {
+        let (width, height) = canvas.output_size().expect("Canvas size query failed");
+        let area = SeriesPanel::area(width, height);
+
+        canvas.set_draw_color(Color::RGB(245, 245, 245));
+        canvas.fill_rect(area).expect("Filling panel failed");
+        canvas.set_draw_color(Color::RGB(90, 90, 90));
+        graphics::draw_line(area.top_left(), area.bottom_left(), canvas);
+
+        self.x = area.x() + MARGIN;
+        self.y = MARGIN;
+        self.width = area.width() as i32 - 2 * MARGIN;
+    }
+
+    pub fn series_controls(
+        &mut self,
+        ui: &mut Ui,
+        canvas: &mut Canvas<Window>,
+        scene: &mut Scene,
+        history: &mut History,
+        active_series: &mut usize,
+    ) {
+        let (x, width) = (self.x, self.width);
+        let count = scene.series.len();
+
+        if count > 0 {
+            let prev = Rect::new(x, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "previous series", prev) && self.drag_start.is_none() {
+                *active_series = (*active_series + count - 1) % count;
+            }
+            draw_arrow(canvas, prev, true);
+
+            let next = Rect::new(x + width - BUTTON as i32, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "next series", next) && self.drag_start.is_none() {
+                *active_series = (*active_series + 1) % count;
+            }
+            draw_arrow(canvas, next, false);
+
+            *active_series = (*active_series).min(count - 1);
+            let label = active_series.to_string();
+            let label_x = x + (width - ui::number_width(&label, 20)) / 2;
+            ui::draw_number(canvas, &label, label_x, self.y + 2, 20);
+            self.y += ROW;
+
+            let index = *active_series;
+            let series = &mut scene.series[index];
+
+            let field = Rect::new(x, self.y, width as u32, 30);
+            if ui.text_field(canvas, TEXT_ID, field, &mut self.text, numbers::has_glyph) {
+                if self.text.is_empty() {
+                    hud::status(String::from("Series text cannot be empty"));
+                } else if self.text != series.number_str {
+                    let before = series.number_str.clone();
+                    series.set_text(self.text.clone());
+                    history.record(Command::SeriesText {
+                        index,
+                        before,
+                        after: self.text.clone(),
+                    });
+                }
+            }
+            // Follows the series unless it is being typed into.
+            if ui.focus != Some(TEXT_ID) {
+                self.text = series.number_str.clone();
+            }
+            self.y += ROW;
+
+            let slider_width = (width - READOUT_WIDTH - 6) as u32;
+            let readout_x = x + width - READOUT_WIDTH;
+
+            let mut scale = series.scale;
+            let track = Rect::new(x, self.y, slider_width, 16);
+            if ui.slider(canvas, "series scale", track, 0.1, 2.0, &mut scale) {
+                self.start_drag(index, SeriesTransform::of(series));
+                series.set_transform(series.x, series.y, scale, series.angle);
+            }
+            let percent = ((series.scale * 100.0).round() as i32).to_string();
+            ui::draw_number(canvas, &percent, readout_x, self.y + 1, READOUT_HEIGHT);
+            self.y += ROW;
+
+            let mut degrees = series.angle.to_degrees().rem_euclid(360.0);
+            let track = Rect::new(x, self.y, slider_width, 16);
+            if ui.slider(canvas, "series angle", track, 0.0, 359.0, &mut degrees) {
+                self.start_drag(index, SeriesTransform::of(series));
+                let angle = degrees.round().to_radians();
+                series.set_transform(series.x, series.y, series.scale, angle);
+            }
+            let readout = (degrees.round() as i32).to_string();
+            ui::draw_number(canvas, &readout, readout_x, self.y + 1, READOUT_HEIGHT);
+            self.y += ROW;
+        }
+
+        if !ui.is_busy() {
+            self.finish_drag(scene, history);
+        }
+    }
+
+    pub fn layer_controls(
+        &mut self,
+        ui: &mut Ui,
+        canvas: &mut Canvas<Window>,
+        scene: &mut Scene,
+        history: &mut History,
+        active_layer: &mut usize,
+        active_series: usize,
+    ) {
+        let (x, width) = (self.x, self.width);
+        let count = scene.layers.len();
+        let dragging = self.layer_drag_start.is_some();
+
+        if count > 0 {
+            let prev = Rect::new(x, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "previous layer", prev) && !dragging {
+                *active_layer = (*active_layer + count - 1) % count;
+            }
+            draw_arrow(canvas, prev, true);
+
+            let next = Rect::new(x + width - BUTTON as i32, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "next layer", next) && !dragging {
+                *active_layer = (*active_layer + 1) % count;
+            }
+            draw_arrow(canvas, next, false);
+
+            *active_layer = (*active_layer).min(count - 1);
+            let label = active_layer.to_string();
+            let label_x = x + (width - ui::number_width(&label, 20)) / 2;
+            ui::draw_number(canvas, &label, label_x, self.y + 2, 20);
+            self.y += ROW;
+
+            let index = *active_layer;
+            let before = scene.layers[index].clone();
+
+            let mut visible = before.visible;
+            let rect = Rect::new(x, self.y, 16, 16);
+            if ui.checkbox(canvas, "layer visible", rect, &mut visible) {
+                scene.layers[index].visible = visible;
+                history.record(Command::EditLayer {
+                    index,
+                    before: before.clone(),
+                    after: scene.layers[index].clone(),
+                });
+            }
+            // An eye: a circle with a pupil.
+            canvas.set_draw_color(Color::RGB(90, 90, 90));
+            graphics::draw_circle(Point::new(x + 30, self.y + 8), 6, canvas);
+            graphics::draw_circle(Point::new(x + 30, self.y + 8), 2, canvas);
+
+            // A series is always on some layer, so this can only be ticked.
+            if let Some(series) = scene.series.get_mut(active_series) {
+                let mut here = series.layer == index;
+                let rect = Rect::new(x + width / 2, self.y, 16, 16);
+                if ui.checkbox(canvas, "series on layer", rect, &mut here) && here {
+                    history.record(Command::SeriesLayer {
+                        index: active_series,
+                        before: series.layer,
+                        after: index,
+                    });
+                    series.layer = index;
+                }
+                let label = active_series.to_string();
+                let label_x = x + width / 2 + 24;
+                ui::draw_number(canvas, &label, label_x, self.y + 1, READOUT_HEIGHT);
+            }
+            self.y += ROW;
+
+            let step = BUTTON as i32 + 6;
+            let down = Rect::new(x, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "layer down", down) && !dragging && index > 0 {
+                scene.move_layer(index, index - 1);
+                history.record(Command::MoveLayer {
+                    from: index,
+                    to: index - 1,
+                });
+                *active_layer = index - 1;
+            }
+            draw_vertical_arrow(canvas, down, false);
+
+            let up = Rect::new(x + step, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "layer up", up) && !dragging && index + 1 < count {
+                scene.move_layer(index, index + 1);
+                history.record(Command::MoveLayer {
+                    from: index,
+                    to: index + 1,
+                });
+                *active_layer = index + 1;
+            }
+            draw_vertical_arrow(canvas, up, true);
+
+            let add = Rect::new(x + 2 * step, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "add layer", add) && !dragging {
+                let layer = Layer::new(&format!("layer {}", count));
+                scene.layers.push(layer.clone());
+                history.record(Command::AddLayer { layer });
+                *active_layer = count;
+            }
+            draw_plus(canvas, add);
+
+            // Cycles through the blend modes, the name goes to the HUD.
+            let blend = Rect::new(x + width - BUTTON as i32, self.y, BUTTON, BUTTON);
+            if ui.button(canvas, "blend mode", blend) && !dragging {
+                if let Some(layer) = scene.layers.get_mut(*active_layer) {
+                    let before = layer.clone();
+                    layer.blend_mode = layer.blend_mode.next();
+                    hud::status(format!("Blend mode {}", layer.blend_mode.name()));
+                    history.record(Command::EditLayer {
+                        index: *active_layer,
+                        before,
+                        after: layer.clone(),
+                    });
+                }
+            }
+            // Two overlapping circles for blending.
+            let center = blend.center();
+            canvas.set_draw_color(Color::RGB(90, 90, 90));
+            graphics::draw_circle(Point::new(center.x - 3, center.y), 6, canvas);
+            graphics::draw_circle(Point::new(center.x + 3, center.y), 6, canvas);
+            self.y += ROW;
+
+            let index = *active_layer;
+            let layer = &mut scene.layers[index];
+            let slider_width = (width - READOUT_WIDTH - 6) as u32;
+            let mut opacity = layer.opacity;
+            let track = Rect::new(x, self.y, slider_width, 16);
+            if ui.slider(canvas, "layer opacity", track, 0.0, 1.0, &mut opacity) {
+                if self.layer_drag_start.is_none() {
+                    self.layer_drag_start = Some((index, layer.clone()));
+                }
+                layer.opacity = opacity;
+            }
+            let percent = ((layer.opacity * 100.0).round() as i32).to_string();
+            let readout_x = x + width - READOUT_WIDTH;
+            ui::draw_number(canvas, &percent, readout_x, self.y + 1, READOUT_HEIGHT);
+            self.y += ROW;
+        }
+
+        if !ui.is_busy() {
+            if let Some((index, before)) = self.layer_drag_start.take() {
+                if let Some(layer) = scene.layers.get(index) {
+                    history.record(Command::EditLayer {
+                        index,
+                        before,
+                        after: layer.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn tool_settings(
+        &mut self,
+        ui: &mut Ui,
+        canvas: &mut Canvas<Window>,
+        color: &mut u32,
+        snap: &mut Snap,
+    ) {
+        let (x, y, width) = (self.x, self.y, self.width);
+
+        let mut grid = snap.grid.is_some();
+        if ui.checkbox(canvas, "grid snap", Rect::new(x, y, 16, 16), &mut grid) {
+            snap.grid = if grid { Some(GRID_SPACING) } else { None };
+        }
+        // A small grid next to the box says what it is for.
+        canvas.set_draw_color(Color::RGB(90, 90, 90));
+        for i in 0..3 {
+            let offset = i * 6;
+            graphics::draw_line(
+                Point::new(x + 24 + offset, y + 2),
+                Point::new(x + 24 + offset, y + 14),
+                canvas,
+            );
+            graphics::draw_line(
+                Point::new(x + 24, y + 2 + offset),
+                Point::new(x + 36, y + 2 + offset),
+                canvas,
+            );
+        }
+
+        let endpoints_x = x + width / 2;
+        let endpoints = Rect::new(endpoints_x, y, 16, 16);
+        ui.checkbox(canvas, "endpoint snap", endpoints, &mut snap.to_endpoints);
+        graphics::draw_target(Point::new(endpoints_x + 30, y + 8), canvas);
+        self.y += ROW;
+
+        for (i, (id, swatch)) in PALETTE.iter().enumerate() {
+            let rect = Rect::new(x + i as i32 * (SWATCH as i32 + 6), self.y, SWATCH, SWATCH);
+            if ui.color_swatch(canvas, id, rect, *swatch, *color == *swatch) {
+                *color = *swatch;
+            }
+        }
+        self.y += ROW;
+    }
+
+    fn start_drag(&mut self, index: usize, before: SeriesTransform) {
+        if self.drag_start.is_none() {
+            self.drag_start = Some((index, before));
+        }
+    }
+
+    fn finish_drag(&mut self, scene: &Scene, history: &mut History) {
+        if let Some((index, before)) = self.drag_start.take() {
+            if let Some(series) = scene.series.get(index) {
+                history.record(Command::TransformSeries {
+                    index,
+                    before,
+                    after: SeriesTransform::of(series),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+pub enum Entry {
+    // Not a key or text event, it goes on to the rest of the app.
+    Ignored,
+    Typing,
+    Submitted(String),
+    Cancelled,
+}
+
+// A line of text for an action that needs more than a key press. The HUD
+// shows it until Return submits it or Escape drops it.
+pub struct Prompt {
+    pub label: &'static str,
+    pub text: String,
+}
+
+impl Prompt {
+    pub fn new(label: &'static str) -> Self {
+        Prompt {
+            label,
+            text: String::new(),
+        }
+    }
+
+    pub fn line(&self) -> String {
+        format!("{}: {}_", self.label, self.text)
+    }
+
+    pub fn event(&mut self, event: &Event) -> Entry {
+        match *event {
+            Event::TextInput { ref text, .. } => {
+                self.text.push_str(text);
+                Entry::Typing
+            }
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::Return | Keycode::KpEnter => Entry::Submitted(self.text.clone()),
+                Keycode::Escape => Entry::Cancelled,
+                Keycode::Backspace => {
+                    self.text.pop();
+                    Entry::Typing
+                }
+                _ => Entry::Typing,
+            },
+            Event::KeyUp { .. } => Entry::Typing,
+            _ => Entry::Ignored,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+
+    fn text(text: &str) -> Event {
+        Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: String::from(text),
+        }
+    }
+
+    fn key(keycode: Keycode) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    #[test]
+    fn typing_then_return_submits_the_text() {
+        let mut prompt = Prompt::new("Trace");
+        prompt.event(&text("A a.pn"));
+        prompt.event(&text("x"));
+        prompt.event(&key(Keycode::Backspace));
+        prompt.event(&text("g"));
+        assert_eq!(prompt.line(), "Trace: A a.png_");
+
+        match prompt.event(&key(Keycode::Return)) {
+            Entry::Submitted(text) => assert_eq!(text, "A a.png"),
+            _ => panic!("expected the text"),
+        }
+        let escape = prompt.event(&key(Keycode::Escape));
+        assert!(matches!(escape, Entry::Cancelled));
+        let quit = prompt.event(&Event::Quit { timestamp: 0 });
+        assert!(matches!(quit, Entry::Ignored));
+    }
+}
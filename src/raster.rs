@@ -0,0 +1,425 @@
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::anti_aliasing;
+use crate::gamma::Blending;
+use crate::graphics::{self, CubicBezierCurve, Line, Rectangle};
+use crate::mask::{CoverageMask, MaskStack};
+use crate::numbers::{Number, NumberSeries};
+
+// How a buffer gets drawn into. Scratch buffers rendered for a target take
+// its settings.
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    pub blending: Blending,
+    // Lines, curves and circles are drawn with Wu lines. Off by default,
+    // the soft edges are boundaries the flood fill stops at a pixel early.
+    pub smooth_strokes: bool,
+}
+
+impl RenderSettings {
+    pub fn new() -> Self {
+        RenderSettings {
+            blending: Blending::Linear,
+            smooth_strokes: false,
+        }
+    }
+}
+
+// Offscreen ARGB8888 buffer, same byte layout as canvas.read_pixels so
+// graphics::get_color can decode it.
+pub struct PixelBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub clip_rect: Option<Rect>,
+    pub masks: MaskStack,
+    pub settings: RenderSettings,
+}
+
+impl PixelBuffer {
+    pub fn new(width: u32, height: u32, background: u32) -> Self {
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for _ in 0..(width * height) {
+            pixels.extend_from_slice(&background.to_le_bytes());
+        }
+
+        PixelBuffer {
+            width,
+            height,
+            pixels,
+            clip_rect: None,
+            masks: MaskStack::new(),
+            settings: RenderSettings::new(),
+        }
+    }
+
+    pub fn from_canvas(canvas: &Canvas<Window>) -> Self {
+        let (width, height) = canvas.output_size().unwrap();
+
+        let pixels = canvas
+            .read_pixels(None, sdl2::pixels::PixelFormatEnum::ARGB8888)
+            .expect("Read pixels failes");
+
+        PixelBuffer {
+            width,
+            height,
+            pixels,
+            clip_rect: None,
+            masks: MaskStack::new(),
+            settings: RenderSettings::new(),
+        }
+    }
+
+    pub fn copy_to_canvas(&self, canvas: &mut Canvas<Window>) {
+        self.copy_to_canvas_rect(canvas, None);
+    }
+
+    // Stretched to dst, the whole canvas if None.
+    pub fn copy_to_canvas_rect(&self, canvas: &mut Canvas<Window>, dst: Option<Rect>) {
+        let texture_creator = canvas.texture_creator();
+
+        let mut texture = texture_creator
+            .create_texture_streaming(
+                sdl2::pixels::PixelFormatEnum::ARGB8888,
+                self.width,
+                self.height,
+            )
+            .unwrap();
+        texture.set_blend_mode(sdl2::render::BlendMode::None);
+
+        texture
+            .update(None, &self.pixels, (self.width * 4) as usize)
+            .expect("Texture update failed");
+        canvas.copy(&texture, None, dst).unwrap();
+    }
+
+    pub fn set_clip_rect(&mut self, clip_rect: Option<Rect>) {
+        self.clip_rect = clip_rect;
+    }
+
+    // None when the clip rect lies outside the buffer.
+    pub fn clip_bounds(&self) -> Option<Rect> {
+        let output = Rect::new(0, 0, self.width, self.height);
+
+        match self.clip_rect {
+            Some(clip_rect) => clip_rect.intersection(output),
+            None => Some(output),
+        }
+    }
+
+    pub fn push_mask(&mut self, mask: CoverageMask) {
+        self.masks.push(mask);
+    }
+
+    pub fn pop_mask(&mut self) -> Option<CoverageMask> {
+        self.masks.pop()
+    }
+
+    // A width by height copy with this buffer in the top left corner,
+    // cropped where it is bigger and padded with background on the right
+    // and bottom where it is smaller.
+    pub fn with_size(&self, width: u32, height: u32, background: u32) -> PixelBuffer {
+        let mut target = PixelBuffer::new(width, height, background);
+        target.settings = self.settings;
+
+        for y in 0..self.height.min(height) as i32 {
+            for x in 0..self.width.min(width) as i32 {
+                target.set(x, y, self.get(x, y));
+            }
+        }
+
+        target
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> u32 {
+        graphics::get_color(Point::new(x, y), self.width, &self.pixels)
+    }
+
+    // The pixel at x, y with color over it at coverage, not written back.
+    pub fn blend(&self, x: i32, y: i32, color: u32, coverage: u8) -> u32 {
+        let t = coverage as f32 / 255.0;
+        self.settings.blending.mix_colors(self.get(x, y), color, t)
+    }
+
+    // Writes are gated by the current mask: uncovered pixels are left alone
+    // and partially covered ones are blended.
+    pub fn set(&mut self, x: i32, y: i32, color: u32) {
+        if !self.contains(x, y) {
+            return;
+        }
+
+        let color = match self.masks.coverage(x, y) {
+            0 => return,
+            255 => color,
+            coverage => self.blend(x, y, color, coverage),
+        };
+
+        let index = ((y as u32 * self.width + x as u32) * 4) as usize;
+        self.pixels[index..index + 4].copy_from_slice(&color.to_le_bytes());
+    }
+}
+
+// Pixels 4-connected to start that share its color, inside the clip bounds.
+// The same walk as graphics::flood_fill, minus the per pixel canvas reads.
+pub fn flood_region(source: &PixelBuffer, start: Point) -> Vec<Point> {
+    let bounds = match source.clip_bounds() {
+        Some(bounds) if bounds.contains_point(start) => bounds,
+        _ => return Vec::new(),
+    };
+
+    let target_color = source.get(start.x, start.y);
+    let mut visited = vec![false; (source.width * source.height) as usize];
+    let mut region = Vec::new();
+    let mut stack = vec![start];
+
+    while let Some(p) = stack.pop() {
+        if !bounds.contains_point(p) {
+            continue;
+        }
+
+        let index = (p.y as u32 * source.width + p.x as u32) as usize;
+        if visited[index] || source.get(p.x, p.y) != target_color {
+            continue;
+        }
+        visited[index] = true;
+        region.push(p);
+
+        stack.push(Point::new(p.x + 1, p.y));
+        stack.push(Point::new(p.x - 1, p.y));
+        stack.push(Point::new(p.x, p.y + 1));
+        stack.push(Point::new(p.x, p.y - 1));
+    }
+
+    region
+}
+
+pub enum Primitive {
+    Point(Point),
+    Line(Point, Point),
+    Circle(Point, i32),
+    QuadraticBezier([Point; 3]),
+    CubicBezier([Point; 4]),
+    FilledPolygon(Vec<Point>),
+}
+
+impl Primitive {
+    // Every pixel plotted by rasterize lies inside this box, which is what
+    // lets the tiled renderer bin primitives safely.
+    pub fn bounding_box(&self) -> Rect {
+        match self {
+            Primitive::Point(p) => Rect::new(p.x, p.y, 1, 1),
+            Primitive::Line(p_1, p_2) => bounding_box_of(&[*p_1, *p_2]),
+            Primitive::Circle(center, radius) => {
+                let r = radius.abs();
+                Rect::new(
+                    center.x - r,
+                    center.y - r,
+                    (2 * r + 1) as u32,
+                    (2 * r + 1) as u32,
+                )
+            }
+            Primitive::QuadraticBezier(points) => bounding_box_of(points),
+            Primitive::CubicBezier(points) => bounding_box_of(points),
+            Primitive::FilledPolygon(points) => bounding_box_of(points),
+        }
+    }
+
+    pub fn rasterize(&self, bounds: Rect, plot: &mut impl FnMut(i32, i32)) {
+        match self {
+            Primitive::Point(p) => {
+                if bounds.contains_point(*p) {
+                    plot(p.x, p.y)
+                }
+            }
+            Primitive::Line(p_1, p_2) => graphics::plot_clipped_line(*p_1, *p_2, bounds, plot),
+            Primitive::Circle(center, radius) => {
+                graphics::plot_circle(*center, *radius, bounds, plot)
+            }
+            Primitive::QuadraticBezier([p_1, p_2, p_3]) => {
+                graphics::plot_quadratic_bezier(*p_1, *p_2, *p_3, bounds, plot)
+            }
+            Primitive::CubicBezier([p_1, p_2, p_3, p_4]) => {
+                graphics::plot_cubic_bezier(*p_1, *p_2, *p_3, *p_4, bounds, plot)
+            }
+            Primitive::FilledPolygon(points) => {
+                let points: Vec<(f32, f32)> =
+                    points.iter().map(|p| (p.x as f32, p.y as f32)).collect();
+                graphics::plot_filled_polygon(&points, bounds, plot)
+            }
+        }
+    }
+}
+
+pub fn bounding_box_of(points: &[Point]) -> Rect {
+    let min_x = points.iter().map(|p| p.x).min().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.y).min().unwrap_or(0);
+    let max_x = points.iter().map(|p| p.x).max().unwrap_or(0);
+    let max_y = points.iter().map(|p| p.y).max().unwrap_or(0);
+
+    Rect::new(
+        min_x,
+        min_y,
+        (max_x - min_x + 1) as u32,
+        (max_y - min_y + 1) as u32,
+    )
+}
+
+pub struct DrawCommand {
+    pub primitive: Primitive,
+    pub color: u32,
+}
+
+impl DrawCommand {
+    pub fn new(primitive: Primitive, color: u32) -> Self {
+        DrawCommand { primitive, color }
+    }
+}
+
+impl Line {
+    pub fn primitives(&self) -> Vec<Primitive> {
+        match self.controll_points.as_slice() {
+            [first, second, ..] => vec![Primitive::Line(*first, *second)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl CubicBezierCurve {
+    pub fn primitives(&self) -> Vec<Primitive> {
+        match self.controll_points.as_slice() {
+            [p_1, p_2, p_3, p_4, ..] => vec![Primitive::CubicBezier([*p_1, *p_2, *p_3, *p_4])],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Rectangle {
+    pub fn primitives(&self) -> Vec<Primitive> {
+        match self.controll_points.as_slice() {
+            [first, second, ..] => vec![
+                Primitive::Line(*first, Point::new(first.x, second.y)),
+                Primitive::Line(*first, Point::new(second.x, first.y)),
+                Primitive::Line(Point::new(first.x, second.y), *second),
+                Primitive::Line(*second, Point::new(second.x, first.y)),
+            ],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Number {
+    pub fn primitives(&self) -> Vec<Primitive> {
+        let mut primitives = Vec::new();
+
+        for line in self.lines.iter() {
+            primitives.extend(line.primitives());
+        }
+
+        for curve in self.bezier_curves.iter() {
+            primitives.extend(curve.primitives());
+        }
+
+        primitives
+    }
+}
+
+impl NumberSeries {
+    pub fn draw_commands(&self, color: u32) -> Vec<DrawCommand> {
+        self.numbers
+            .iter()
+            .flat_map(|number| number.primitives())
+            .map(|primitive| DrawCommand::new(primitive, color))
+            .collect()
+    }
+}
+
+pub fn render(commands: &[DrawCommand], target: &mut PixelBuffer) {
+    if target.settings.smooth_strokes {
+        anti_aliasing::render_smooth(commands, target);
+    } else {
+        render_plain(commands, target);
+    }
+}
+
+pub fn render_plain(commands: &[DrawCommand], target: &mut PixelBuffer) {
+    let bounds = match target.clip_bounds() {
+        Some(bounds) => bounds,
+        None => return,
+    };
+
+    for command in commands {
+        let color = command.color;
+        command
+            .primitive
+            .rasterize(bounds, &mut |x, y| target.set(x, y, color));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_size_pads_and_crops_on_the_right_and_bottom() {
+        let mut small = PixelBuffer::new(2, 2, 0xFF000000);
+        small.set(1, 1, 0xFFFF0000);
+        let padded = small.with_size(6, 4, 0xFFFFFFFF);
+        assert_eq!((padded.width, padded.height), (6, 4));
+        assert_eq!(padded.get(0, 0), 0xFF000000);
+        assert_eq!(padded.get(1, 1), 0xFFFF0000);
+        assert_eq!(padded.get(2, 1), 0xFFFFFFFF);
+        assert_eq!(padded.get(1, 2), 0xFFFFFFFF);
+        assert_eq!(padded.get(5, 3), 0xFFFFFFFF);
+
+        let cropped = padded.with_size(2, 2, 0);
+        assert_eq!(cropped.pixels, small.pixels);
+    }
+
+    #[test]
+    fn clip_rect_outside_buffer_draws_nothing() {
+        let mut buffer = PixelBuffer::new(20, 20, 0xFFFFFFFF);
+        buffer.set_clip_rect(Some(Rect::new(40, 40, 10, 10)));
+        assert_eq!(buffer.clip_bounds(), None);
+
+        let commands = [
+            DrawCommand::new(
+                Primitive::Line(Point::new(0, 0), Point::new(19, 19)),
+                0xFF000000,
+            ),
+            DrawCommand::new(Primitive::Point(Point::new(0, 0)), 0xFF000000),
+        ];
+        render(&commands, &mut buffer);
+        assert!(buffer.pixels == PixelBuffer::new(20, 20, 0xFFFFFFFF).pixels);
+        assert!(flood_region(&buffer, Point::new(0, 0)).is_empty());
+    }
+
+    #[test]
+    fn circle_around_bounds_plots_nothing() {
+        let mut plotted = 0;
+        graphics::plot_circle(
+            Point::new(10, 10),
+            100_000,
+            Rect::new(0, 0, 20, 20),
+            &mut |_, _| plotted += 1,
+        );
+        assert_eq!(plotted, 0);
+    }
+
+    #[test]
+    fn circle_crossing_bounds_plots_inside_only() {
+        let bounds = Rect::new(0, 0, 20, 20);
+        let mut plotted = Vec::new();
+        graphics::plot_circle(Point::new(0, 10), 8, bounds, &mut |x, y| {
+            plotted.push(Point::new(x, y))
+        });
+        assert!(!plotted.is_empty());
+        assert!(plotted.iter().all(|p| bounds.contains_point(*p)));
+        assert!(plotted.contains(&Point::new(8, 10)));
+    }
+}
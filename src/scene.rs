@@ -0,0 +1,455 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::camera;
+use crate::filters::Filter;
+use crate::fitting;
+use crate::graphics::{self, CubicBezierCurve, Line, Rectangle};
+use crate::layers::{self, Layer};
+use crate::numbers::NumberSeries;
+use crate::paint::{self, Paint};
+use crate::raster::{self, DrawCommand, PixelBuffer, Primitive, RenderSettings};
+use crate::sdl_to_bmp;
+
+// Strokes ending closer than this to where they started are closed.
+const CLOSE_DISTANCE: i32 = 10;
+
+#[derive(Clone)]
+pub enum Geometry {
+    Line(Line),
+    Rectangle(Rectangle),
+    Circle { center: Point, radius: i32 },
+    QuadraticBezier([Point; 3]),
+    CubicBezier(CubicBezierCurve),
+    // A chain of cubics, each starting where the previous one ended.
+    Path {
+        curves: Vec<CubicBezierCurve>,
+        closed: bool,
+    },
+}
+
+#[derive(Clone)]
+pub struct Shape {
+    pub geometry: Geometry,
+    pub color: u32,
+    pub fill: Option<Paint>,
+    // Index into Scene::layers.
+    pub layer: usize,
+}
+
+impl Shape {
+    pub fn new(geometry: Geometry, color: u32) -> Self {
+        Shape {
+            geometry,
+            color,
+            fill: None,
+            layer: 0,
+        }
+    }
+
+    // Fitted from freehand samples, see fitting::fit_stroke.
+    pub fn from_stroke(samples: &[Point], tolerance: f32, color: u32) -> Option<Self> {
+        let samples: Vec<(f32, f32)> = samples.iter().map(|p| (p.x as f32, p.y as f32)).collect();
+
+        let curves: Vec<CubicBezierCurve> = fitting::fit_stroke(&samples, tolerance)
+            .iter()
+            .map(CubicBezierCurve::from_cubic)
+            .collect();
+
+        let first = curves.first()?.controll_points[0];
+        let last = curves.last()?.controll_points[3];
+        let closed = curves.len() > 1
+            && (first.x - last.x).abs() <= CLOSE_DISTANCE
+            && (first.y - last.y).abs() <= CLOSE_DISTANCE;
+
+        Some(Shape::new(Geometry::Path { curves, closed }, color))
+    }
+
+    pub fn primitives(&self) -> Vec<Primitive> {
+        match &self.geometry {
+            Geometry::Line(line) => line.primitives(),
+            Geometry::Rectangle(rectangle) => rectangle.primitives(),
+            Geometry::Circle { center, radius } => vec![Primitive::Circle(*center, *radius)],
+            Geometry::QuadraticBezier(points) => vec![Primitive::QuadraticBezier(*points)],
+            Geometry::CubicBezier(curve) => curve.primitives(),
+            Geometry::Path { curves, closed } => {
+                let mut primitives: Vec<Primitive> =
+                    curves.iter().flat_map(|curve| curve.primitives()).collect();
+
+                if *closed {
+                    let first = curves.first().map(|curve| curve.controll_points[0]);
+                    let last = curves.last().map(|curve| curve.controll_points[3]);
+                    if let (Some(first), Some(last)) = (first, last) {
+                        if first != last {
+                            primitives.push(Primitive::Line(last, first));
+                        }
+                    }
+                }
+
+                primitives
+            }
+        }
+    }
+
+    // Only closed shapes get their fill painted.
+    pub fn is_closed(&self) -> bool {
+        match &self.geometry {
+            Geometry::Rectangle(_) | Geometry::Circle { .. } => true,
+            Geometry::Path { closed, .. } => *closed,
+            _ => false,
+        }
+    }
+
+    // Outline only, fills need a PixelBuffer (see render).
+    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+        let bounds = match graphics::clip_bounds(canvas) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let [b, g, r, a] = self.color.to_le_bytes();
+        canvas.set_draw_color(Color::RGBA(r, g, b, a));
+
+        for primitive in self.primitives() {
+            primitive.rasterize(bounds, &mut |x, y| {
+                canvas
+                    .draw_point(Point::new(x, y))
+                    .expect("Draw point failed");
+            });
+        }
+    }
+
+    pub fn render(&self, target: &mut PixelBuffer) {
+        let primitives = self.primitives();
+
+        if let Some(fill) = &self.fill {
+            if self.is_closed() {
+                paint::fill_primitives(target, &primitives, fill);
+            }
+        }
+
+        let commands: Vec<DrawCommand> = primitives
+            .into_iter()
+            .map(|primitive| DrawCommand::new(primitive, self.color))
+            .collect();
+        raster::render(&commands, target);
+    }
+}
+
+pub struct Scene {
+    pub shapes: Vec<Shape>,
+    pub series: Vec<NumberSeries>,
+    pub layers: Vec<Layer>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            shapes: Vec::new(),
+            series: Vec::new(),
+            layers: vec![Layer::new("default")],
+        }
+    }
+
+    pub fn add(&mut self, shape: Shape) {
+        self.shapes.push(shape);
+    }
+
+    // Moves the layer at from to to, shifting the ones in between, and
+    // renumbers the shapes and series on all of them. False if either
+    // index is out of range.
+    pub fn move_layer(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.layers.len() || to >= self.layers.len() {
+            return false;
+        }
+
+        let layer = self.layers.remove(from);
+        self.layers.insert(to, layer);
+
+        let remap = |index: usize| {
+            if index == from {
+                to
+            } else if from < to && index > from && index <= to {
+                index - 1
+            } else if to < from && index >= to && index < from {
+                index + 1
+            } else {
+                index
+            }
+        };
+        for shape in self.shapes.iter_mut() {
+            shape.layer = remap(shape.layer);
+        }
+        for series in self.series.iter_mut() {
+            series.layer = remap(series.layer);
+        }
+
+        true
+    }
+
+    // Drawing slots bottom to top: one per visible layer, then one past
+    // the last layer for whatever is on a layer that no longer exists,
+    // which stays visible.
+    fn visible_slots(&self) -> Vec<usize> {
+        (0..=self.layers.len())
+            .filter(|slot| self.layers.get(*slot).map_or(true, |layer| layer.visible))
+            .collect()
+    }
+
+    fn in_slot(&self, layer: usize, slot: usize) -> bool {
+        layer == slot || (slot == self.layers.len() && layer > slot)
+    }
+
+    fn series_in(&self, slot: usize) -> impl Iterator<Item = &NumberSeries> {
+        self.series
+            .iter()
+            .filter(move |series| self.in_slot(series.layer, slot))
+    }
+
+    fn shapes_in(&self, slot: usize) -> impl Iterator<Item = &Shape> {
+        self.shapes
+            .iter()
+            .filter(move |shape| self.in_slot(shape.layer, slot))
+    }
+
+    // Layers that need blending are drawn into a transparent buffer of
+    // their own first, plain ones straight onto the target.
+    pub fn render(&self, target: &mut PixelBuffer) {
+        for slot in self.visible_slots() {
+            match self.layers.get(slot) {
+                Some(layer) if !layer.is_plain() => {
+                    let mut buffer = PixelBuffer::new(target.width, target.height, 0x00000000);
+                    buffer.settings = target.settings;
+                    self.render_layer(slot, &mut buffer);
+                    layers::composite_layer(target, &buffer, layer);
+                }
+                _ => self.render_layer(slot, target),
+            }
+        }
+    }
+
+    // Series go under the shapes on the same layer, each on top of its own
+    // effects.
+    pub fn render_layer(&self, slot: usize, target: &mut PixelBuffer) {
+        for series in self.series_in(slot) {
+            series.render_effects(target);
+            raster::render(&series.draw_commands(0xFF000000), target);
+        }
+        for shape in self.shapes_in(slot) {
+            shape.render(target);
+        }
+    }
+
+    // Outlines only, in drawing order, for the tiled renderer.
+    pub fn draw_commands(&self) -> Vec<DrawCommand> {
+        let mut commands: Vec<DrawCommand> = Vec::new();
+
+        for slot in self.visible_slots() {
+            for series in self.series_in(slot) {
+                commands.extend(series.draw_commands(0xFF000000));
+            }
+            for shape in self.shapes_in(slot) {
+                commands.extend(
+                    shape
+                        .primitives()
+                        .into_iter()
+                        .map(|primitive| DrawCommand::new(primitive, shape.color)),
+                );
+            }
+        }
+
+        commands
+    }
+
+    // Outlines go straight to the canvas. Fills, blended layers, series
+    // effects and smooth strokes need a PixelBuffer, so those scenes take a
+    // round trip through one.
+    pub fn draw(&self, canvas: &mut Canvas<Window>, settings: RenderSettings) {
+        let blended = self.layers.iter().any(|layer| !layer.is_plain());
+        let filled = self.shapes.iter().any(|shape| shape.fill.is_some());
+        let effects = self.series.iter().any(|series| !series.effects.is_empty());
+        if blended || filled || effects || settings.smooth_strokes {
+            let mut buffer = PixelBuffer::from_canvas(canvas);
+            buffer.settings = settings;
+            self.render(&mut buffer);
+            buffer.copy_to_canvas(canvas);
+            return;
+        }
+
+        for slot in self.visible_slots() {
+            canvas.set_draw_color(Color::RGB(0, 0, 0));
+            for series in self.series_in(slot) {
+                series.draw(canvas, false);
+            }
+            for shape in self.shapes_in(slot) {
+                shape.draw(canvas);
+            }
+        }
+    }
+
+    // The drawing over the backdrop at scale times its size.
+    pub fn export(
+        &self,
+        backdrop: &PixelBuffer,
+        scale: f32,
+        filters: &[Filter],
+        path: &str,
+    ) -> Result<(), image::ImageError> {
+        let mut buffer = camera::render_offscreen(self, backdrop, scale);
+        buffer.apply_filters(filters);
+        save_png(&buffer, path)
+    }
+
+    // The backdrop as <prefix>_backdrop.png and every layer in its own
+    // transparent PNG, named <prefix>_<index>_<layer name>.png. Hidden
+    // layers are written too.
+    pub fn export_layers(
+        &self,
+        backdrop: &PixelBuffer,
+        scale: f32,
+        filters: &[Filter],
+        prefix: &str,
+    ) -> Result<(), image::ImageError> {
+        let mut buffer = camera::scale_backdrop(backdrop, scale);
+        buffer.apply_filters(filters);
+        save_png(&buffer, &format!("{}_backdrop.png", prefix))?;
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            let mut buffer = camera::render_layer_offscreen(self, index, backdrop, scale);
+            buffer.apply_filters(filters);
+            save_png(&buffer, &format!("{}_{}_{}.png", prefix, index, layer.name))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn save_png(buffer: &PixelBuffer, path: &str) -> Result<(), image::ImageError> {
+    sdl_to_bmp::save_pixels_to_png(&buffer.pixels, buffer.width, buffer.height, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene_with_line(layer: Layer) -> Scene {
+        let mut scene = Scene::new();
+        scene.layers.push(layer);
+        let line = Line::new(vec![Point::new(0, 5), Point::new(9, 5)]);
+        let mut shape = Shape::new(Geometry::Line(line), 0xFF000000);
+        shape.layer = 1;
+        scene.add(shape);
+        scene
+    }
+
+    fn rendered(scene: &Scene) -> PixelBuffer {
+        let mut target = PixelBuffer::new(10, 10, 0xFFFFFFFF);
+        scene.render(&mut target);
+        target
+    }
+
+    #[test]
+    fn hidden_and_transparent_layers_draw_nothing() {
+        let mut hidden = Layer::new("hidden");
+        hidden.visible = false;
+        let mut transparent = Layer::new("transparent");
+        transparent.opacity = 0.0;
+
+        for layer in [hidden, transparent] {
+            let target = rendered(&scene_with_line(layer));
+            assert_eq!(target.get(4, 5), 0xFFFFFFFF);
+        }
+    }
+
+    #[test]
+    fn blended_layer_is_composited() {
+        let target = rendered(&scene_with_line(Layer::new("plain")));
+        assert_eq!(target.get(4, 5), 0xFF000000);
+
+        let mut half = Layer::new("half");
+        half.opacity = 0.5;
+        let color = rendered(&scene_with_line(half)).get(4, 5);
+        assert!(color != 0xFF000000 && color != 0xFFFFFFFF);
+        assert_eq!(color >> 24, 0xFF);
+    }
+
+    #[test]
+    fn moving_a_layer_renumbers_what_is_on_it() {
+        let mut scene = Scene::new();
+        for name in ["a", "b", "c"] {
+            scene.layers.push(Layer::new(name));
+        }
+        for layer in 0..5 {
+            let line = Line::new(vec![Point::new(0, 0), Point::new(1, 1)]);
+            let mut shape = Shape::new(Geometry::Line(line), 0xFF000000);
+            shape.layer = layer;
+            scene.add(shape);
+        }
+        let mut series = NumberSeries::new(0, 0, String::from("1"), 1.0, 0.0);
+        series.layer = 3;
+        scene.series.push(series);
+
+        // Layers are 0 default, 1 a, 2 b, 3 c.
+        assert!(scene.move_layer(3, 1));
+        let names: Vec<&str> = scene
+            .layers
+            .iter()
+            .map(|layer| layer.name.as_str())
+            .collect();
+        assert_eq!(names[1..], ["c", "a", "b"]);
+        let shape_layers: Vec<usize> = scene.shapes.iter().map(|shape| shape.layer).collect();
+        assert_eq!(shape_layers, [0, 2, 3, 1, 4]);
+        assert_eq!(scene.series[0].layer, 1);
+
+        assert!(scene.move_layer(1, 3));
+        let shape_layers: Vec<usize> = scene.shapes.iter().map(|shape| shape.layer).collect();
+        assert_eq!(shape_layers, [0, 1, 2, 3, 4]);
+        assert_eq!(scene.series[0].layer, 3);
+
+        assert!(!scene.move_layer(0, 4));
+    }
+
+    #[test]
+    fn series_are_drawn_inside_their_layer() {
+        let mut scene = Scene::new();
+        let mut hidden = Layer::new("hidden");
+        hidden.visible = false;
+        scene.layers.push(hidden);
+        let mut series = NumberSeries::new(10, 10, String::from("1"), 1.0, 0.0);
+        series.layer = 1;
+        scene.series.push(series);
+
+        let blank = PixelBuffer::new(100, 100, 0xFFFFFFFF);
+        let mut target = PixelBuffer::new(100, 100, 0xFFFFFFFF);
+        scene.render(&mut target);
+        assert!(target.pixels == blank.pixels);
+
+        scene.layers[1].visible = true;
+        scene.render(&mut target);
+        assert!(target.pixels != blank.pixels);
+    }
+
+    #[test]
+    fn shapes_are_filled_with_any_paint() {
+        let mut scene = Scene::new();
+        let rectangle = Rectangle::new(vec![Point::new(0, 0), Point::new(9, 9)]);
+        let mut shape = Shape::new(Geometry::Rectangle(rectangle), 0xFF000000);
+        shape.fill = Some(Paint::LinearGradient {
+            start: (0.0, 0.0),
+            end: (10.0, 0.0),
+            stops: vec![
+                paint::ColorStop::new(0.0, 0xFFFF0000),
+                paint::ColorStop::new(1.0, 0xFF0000FF),
+            ],
+            spread: paint::Spread::Pad,
+        });
+        scene.add(shape);
+
+        let target = rendered(&scene);
+        let left = target.get(2, 5);
+        let right = target.get(7, 5);
+        assert!((left >> 16) & 0xFF > (right >> 16) & 0xFF);
+        assert!(left & 0xFF < right & 0xFF);
+    }
+}
@@ -0,0 +1,811 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use sdl2::rect::Point;
+
+use crate::effects::{DropShadow, Glow, Outline, TextEffects};
+use crate::graphics::{CubicBezierCurve, Line, Rectangle};
+use crate::hud;
+use crate::layers::{BlendMode, Layer};
+use crate::numbers::NumberSeries;
+use crate::paint::{ColorStop, HatchStyle, Paint, PatternImage, Spread};
+use crate::raster::PixelBuffer;
+use crate::scene::{Geometry, Scene, Shape};
+use crate::sdl_to_bmp;
+
+// Plain text, one record per line:
+//
+//   rust-sdl scene <version>
+//   layer <visible 0|1> <opacity> <blend mode> <name>
+//   series <layer> <x> <y> <scale> <angle> <text>
+//   shadow <dx> <dy> <blur radius> <color>
+//   outline <width> <color>
+//   glow <radius> <color>
+//   shape <layer> <color> <fill|-> <kind> <coordinates...>
+//   backdrop <png next to the scene file>
+//
+// A fill is a color on its own, or one of
+//   linear <spread> <x0> <y0> <x1> <y1> <stop count> <offset> <color>...
+//   radial <spread> <x> <y> <radius> <stop count> <offset> <color>...
+//   conic <spread> <x> <y> <start angle> <stop count> <offset> <color>...
+//   hatch <style> <angle> <spacing> <thickness> <color> <background>
+//   pattern <width> <height> <color>...
+//
+// Effects records belong to the series above them. Layer names, series
+// text and the backdrop file name run to the end of the line, so they may
+// contain spaces.
+//
+// Colors are ARGB in hex. Version 2 was the first release, its layers have
+// no opacity or blend mode. Version 3 series have no layer column, version
+// 4 ones no effects.
+const HEADER: &str = "rust-sdl scene";
+pub const SCENE_VERSION: u32 = 5;
+
+pub fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line + 1, message),
+    )
+}
+
+pub fn parse<T: FromStr>(token: Option<&str>, line: usize) -> io::Result<T> {
+    token
+        .and_then(|token| token.parse().ok())
+        .ok_or_else(|| invalid(line, "missing or malformed value"))
+}
+
+fn parse_color(token: Option<&str>, line: usize) -> io::Result<u32> {
+    token
+        .and_then(|token| u32::from_str_radix(token, 16).ok())
+        .ok_or_else(|| invalid(line, "malformed color"))
+}
+
+pub fn parse_points<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    count: usize,
+    line: usize,
+) -> io::Result<Vec<Point>> {
+    (0..count)
+        .map(|_| {
+            Ok(Point::new(
+                parse(tokens.next(), line)?,
+                parse(tokens.next(), line)?,
+            ))
+        })
+        .collect()
+}
+
+// Everything after the first `fields` space separated fields, as written.
+fn rest_of_line(line: &str, fields: usize) -> &str {
+    line.splitn(fields + 1, ' ').nth(fields).unwrap_or_default()
+}
+
+pub fn write_points(out: &mut String, points: &[Point]) {
+    for point in points {
+        out.push_str(&format!(" {} {}", point.x, point.y));
+    }
+}
+
+fn geometry_to_string(geometry: &Geometry) -> String {
+    let mut out = String::new();
+
+    match geometry {
+        Geometry::Line(line) => {
+            out.push_str("line");
+            write_points(&mut out, &line.controll_points);
+        }
+        Geometry::Rectangle(rectangle) => {
+            out.push_str("rectangle");
+            write_points(&mut out, &rectangle.controll_points);
+        }
+        Geometry::Circle { center, radius } => {
+            out.push_str(&format!("circle {} {} {}", center.x, center.y, radius));
+        }
+        Geometry::QuadraticBezier(points) => {
+            out.push_str("quadratic");
+            write_points(&mut out, points);
+        }
+        Geometry::CubicBezier(curve) => {
+            out.push_str("cubic");
+            write_points(&mut out, &curve.controll_points);
+        }
+        Geometry::Path { curves, closed } => {
+            out.push_str(&format!(
+                "path {} {}",
+                if *closed { "closed" } else { "open" },
+                curves.len()
+            ));
+            for curve in curves {
+                write_points(&mut out, &curve.controll_points);
+            }
+        }
+    }
+
+    out
+}
+
+fn parse_geometry<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    line: usize,
+) -> io::Result<Geometry> {
+    match tokens.next() {
+        Some("line") => Ok(Geometry::Line(Line::new(parse_points(tokens, 2, line)?))),
+        Some("rectangle") => Ok(Geometry::Rectangle(Rectangle::new(parse_points(
+            tokens, 2, line,
+        )?))),
+        Some("circle") => Ok(Geometry::Circle {
+            center: Point::new(parse(tokens.next(), line)?, parse(tokens.next(), line)?),
+            radius: parse(tokens.next(), line)?,
+        }),
+        Some("quadratic") => {
+            let points = parse_points(tokens, 3, line)?;
+            Ok(Geometry::QuadraticBezier([points[0], points[1], points[2]]))
+        }
+        Some("cubic") => Ok(Geometry::CubicBezier(CubicBezierCurve::new(parse_points(
+            tokens, 4, line,
+        )?))),
+        Some("path") => {
+            let closed = match tokens.next() {
+                Some("closed") => true,
+                Some("open") => false,
+                _ => return Err(invalid(line, "path must be open or closed")),
+            };
+            let count: usize = parse(tokens.next(), line)?;
+            let curves = (0..count)
+                .map(|_| Ok(CubicBezierCurve::new(parse_points(tokens, 4, line)?)))
+                .collect::<io::Result<Vec<CubicBezierCurve>>>()?;
+            Ok(Geometry::Path { curves, closed })
+        }
+        Some(kind) => Err(invalid(line, &format!("unknown shape '{}'", kind))),
+        None => Err(invalid(line, "missing shape kind")),
+    }
+}
+
+fn write_stops(out: &mut String, stops: &[ColorStop]) {
+    out.push_str(&format!(" {}", stops.len()));
+    for stop in stops {
+        out.push_str(&format!(" {} {:08X}", stop.offset, stop.color));
+    }
+}
+
+fn paint_to_string(paint: &Paint) -> String {
+    let mut out = String::new();
+
+    match paint {
+        Paint::Solid(color) => out.push_str(&format!("{:08X}", color)),
+        Paint::LinearGradient {
+            start,
+            end,
+            stops,
+            spread,
+        } => {
+            out.push_str(&format!(
+                "linear {} {} {} {} {}",
+                spread.name(),
+                start.0,
+                start.1,
+                end.0,
+                end.1
+            ));
+            write_stops(&mut out, stops);
+        }
+        Paint::RadialGradient {
+            center,
+            radius,
+            stops,
+            spread,
+        } => {
+            out.push_str(&format!(
+                "radial {} {} {} {}",
+                spread.name(),
+                center.0,
+                center.1,
+                radius
+            ));
+            write_stops(&mut out, stops);
+        }
+        Paint::ConicGradient {
+            center,
+            start_angle,
+            stops,
+            spread,
+        } => {
+            out.push_str(&format!(
+                "conic {} {} {} {}",
+                spread.name(),
+                center.0,
+                center.1,
+                start_angle
+            ));
+            write_stops(&mut out, stops);
+        }
+        Paint::Hatch {
+            style,
+            angle,
+            spacing,
+            thickness,
+            color,
+            background,
+        } => out.push_str(&format!(
+            "hatch {} {} {} {} {:08X} {:08X}",
+            style.name(),
+            angle,
+            spacing,
+            thickness,
+            color,
+            background
+        )),
+        Paint::Pattern(image) => {
+            out.push_str(&format!("pattern {} {}", image.width, image.height));
+            for pixel in image.pixels.iter() {
+                out.push_str(&format!(" {:08X}", pixel));
+            }
+        }
+    }
+
+    out
+}
+
+fn parse_spread(token: Option<&str>, line: usize) -> io::Result<Spread> {
+    token
+        .and_then(Spread::from_name)
+        .ok_or_else(|| invalid(line, "unknown gradient spread"))
+}
+
+fn parse_stops<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    line: usize,
+) -> io::Result<Vec<ColorStop>> {
+    let count: usize = parse(tokens.next(), line)?;
+    (0..count)
+        .map(|_| {
+            Ok(ColorStop::new(
+                parse(tokens.next(), line)?,
+                parse_color(tokens.next(), line)?,
+            ))
+        })
+        .collect()
+}
+
+fn parse_paint<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    line: usize,
+) -> io::Result<Option<Paint>> {
+    let paint = match tokens.next() {
+        Some("-") => return Ok(None),
+        Some("linear") => {
+            let spread = parse_spread(tokens.next(), line)?;
+            let start = (parse(tokens.next(), line)?, parse(tokens.next(), line)?);
+            let end = (parse(tokens.next(), line)?, parse(tokens.next(), line)?);
+            Paint::LinearGradient {
+                start,
+                end,
+                stops: parse_stops(tokens, line)?,
+                spread,
+            }
+        }
+        Some("radial") => {
+            let spread = parse_spread(tokens.next(), line)?;
+            let center = (parse(tokens.next(), line)?, parse(tokens.next(), line)?);
+            Paint::RadialGradient {
+                center,
+                radius: parse(tokens.next(), line)?,
+                stops: parse_stops(tokens, line)?,
+                spread,
+            }
+        }
+        Some("conic") => {
+            let spread = parse_spread(tokens.next(), line)?;
+            let center = (parse(tokens.next(), line)?, parse(tokens.next(), line)?);
+            Paint::ConicGradient {
+                center,
+                start_angle: parse(tokens.next(), line)?,
+                stops: parse_stops(tokens, line)?,
+                spread,
+            }
+        }
+        Some("hatch") => Paint::Hatch {
+            style: tokens
+                .next()
+                .and_then(HatchStyle::from_name)
+                .ok_or_else(|| invalid(line, "unknown hatch style"))?,
+            angle: parse(tokens.next(), line)?,
+            spacing: parse(tokens.next(), line)?,
+            thickness: parse(tokens.next(), line)?,
+            color: parse_color(tokens.next(), line)?,
+            background: parse_color(tokens.next(), line)?,
+        },
+        Some("pattern") => {
+            let width: u32 = parse(tokens.next(), line)?;
+            let height: u32 = parse(tokens.next(), line)?;
+            let pixels = (0..width * height)
+                .map(|_| parse_color(tokens.next(), line))
+                .collect::<io::Result<Vec<u32>>>()?;
+            Paint::Pattern(PatternImage {
+                width,
+                height,
+                pixels,
+            })
+        }
+        token => Paint::Solid(parse_color(token, line)?),
+    };
+
+    Ok(Some(paint))
+}
+
+fn effects_to_string(effects: &TextEffects) -> String {
+    let mut out = String::new();
+    if let Some(shadow) = effects.shadow {
+        out.push_str(&format!(
+            "shadow {} {} {} {:08X}\n",
+            shadow.offset.0, shadow.offset.1, shadow.blur_radius, shadow.color
+        ));
+    }
+    if let Some(outline) = effects.outline {
+        out.push_str(&format!(
+            "outline {} {:08X}\n",
+            outline.width, outline.color
+        ));
+    }
+    if let Some(glow) = effects.glow {
+        out.push_str(&format!("glow {} {:08X}\n", glow.radius, glow.color));
+    }
+    out
+}
+
+// The file contents, with a backdrop record naming the PNG if there is one.
+pub fn scene_to_string(scene: &Scene, backdrop_file: Option<&str>) -> String {
+    let mut out = format!("{} {}\n", HEADER, SCENE_VERSION);
+
+    for layer in scene.layers.iter() {
+        out.push_str(&format!(
+            "layer {} {} {} {}\n",
+            layer.visible as u8,
+            layer.opacity,
+            layer.blend_mode.name(),
+            layer.name
+        ));
+    }
+
+    for series in scene.series.iter() {
+        out.push_str(&format!(
+            "series {} {} {} {} {} {}\n",
+            series.layer, series.x, series.y, series.scale, series.angle, series.number_str
+        ));
+        out.push_str(&effects_to_string(&series.effects));
+    }
+
+    for shape in scene.shapes.iter() {
+        let fill = match &shape.fill {
+            Some(fill) => paint_to_string(fill),
+            None => String::from("-"),
+        };
+        out.push_str(&format!(
+            "shape {} {:08X} {} {}\n",
+            shape.layer,
+            shape.color,
+            fill,
+            geometry_to_string(&shape.geometry)
+        ));
+    }
+
+    if let Some(file_name) = backdrop_file {
+        out.push_str(&format!("backdrop {}\n", file_name));
+    }
+
+    out
+}
+
+pub fn save_backdrop(backdrop: &PixelBuffer, path: &str) -> io::Result<()> {
+    sdl_to_bmp::save_pixels_to_png(&backdrop.pixels, backdrop.width, backdrop.height, path)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+}
+
+// The backdrop goes into a PNG next to the scene file, named after it.
+pub fn save_scene(scene: &Scene, backdrop: Option<&PixelBuffer>, path: &str) -> io::Result<()> {
+    let image_path = Path::new(path).with_extension("png");
+    let file_name = image_path.file_name().unwrap_or_default().to_string_lossy();
+
+    if let Some(backdrop) = backdrop {
+        save_backdrop(backdrop, &image_path.to_string_lossy())?;
+    }
+    let out = scene_to_string(scene, backdrop.map(|_| file_name.as_ref()));
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(out.as_bytes())?;
+    writer.flush()?;
+
+    hud::status(format!("Scene saved as '{}'", path));
+
+    Ok(())
+}
+
+// Brings older files up to the current version one step at a time. Lines
+// keep the number they have in the file so errors point at the original.
+fn migrate(version: u32, lines: Vec<(usize, String)>) -> io::Result<Vec<(usize, String)>> {
+    match version {
+        SCENE_VERSION => Ok(lines),
+        2 => {
+            let migrated = lines
+                .into_iter()
+                .map(|(i, line)| match line.strip_prefix("layer ") {
+                    Some(rest) => match rest.split_once(' ') {
+                        Some((visible, name)) => {
+                            (i, format!("layer {} 1 normal {}", visible, name))
+                        }
+                        None => (i, format!("layer {} 1 normal", rest)),
+                    },
+                    None => (i, line),
+                })
+                .collect();
+            migrate(3, migrated)
+        }
+        3 => {
+            let migrated = lines
+                .into_iter()
+                .map(|(i, line)| match line.strip_prefix("series ") {
+                    Some(rest) => (i, format!("series 0 {}", rest)),
+                    None => (i, line),
+                })
+                .collect();
+            migrate(4, migrated)
+        }
+        // Effects records were added, nothing else changed.
+        4 => migrate(5, lines),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported scene version {}", version),
+        )),
+    }
+}
+
+pub fn load_scene(path: &str) -> io::Result<(Scene, Option<PixelBuffer>)> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+
+    let version: u32 = match lines.next().and_then(|header| header.strip_prefix(HEADER)) {
+        Some(version) => parse(Some(version.trim()), 0)?,
+        None => return Err(invalid(0, "not a scene file")),
+    };
+
+    let lines = migrate(
+        version,
+        lines
+            .enumerate()
+            .map(|(i, line)| (i + 1, String::from(line)))
+            .collect(),
+    )?;
+
+    let mut scene = Scene::new();
+    scene.layers.clear();
+    let mut backdrop = None;
+
+    for (i, line) in lines.iter() {
+        let i = *i;
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            None => continue,
+            Some("layer") => {
+                let visible: u8 = parse(tokens.next(), i)?;
+                let opacity: f32 = parse(tokens.next(), i)?;
+                let blend_mode = match tokens.next().and_then(BlendMode::from_name) {
+                    Some(blend_mode) => blend_mode,
+                    None => return Err(invalid(i, "unknown blend mode")),
+                };
+                let mut layer = Layer::new(rest_of_line(line, 4));
+                layer.visible = visible != 0;
+                layer.opacity = opacity.clamp(0.0, 1.0);
+                layer.blend_mode = blend_mode;
+                scene.layers.push(layer);
+            }
+            Some("series") => {
+                let layer = parse(tokens.next(), i)?;
+                let x = parse(tokens.next(), i)?;
+                let y = parse(tokens.next(), i)?;
+                let scale = parse(tokens.next(), i)?;
+                let angle = parse(tokens.next(), i)?;
+                let text = String::from(rest_of_line(line, 6));
+                let mut series = NumberSeries::new(x, y, text, scale, angle);
+                series.layer = layer;
+                scene.series.push(series);
+            }
+            Some(record @ ("shadow" | "outline" | "glow")) => {
+                let series = match scene.series.last_mut() {
+                    Some(series) => series,
+                    None => return Err(invalid(i, "effect without a series")),
+                };
+                match record {
+                    "shadow" => {
+                        series.effects.shadow = Some(DropShadow {
+                            offset: (parse(tokens.next(), i)?, parse(tokens.next(), i)?),
+                            blur_radius: parse(tokens.next(), i)?,
+                            color: parse_color(tokens.next(), i)?,
+                        })
+                    }
+                    "outline" => {
+                        series.effects.outline = Some(Outline {
+                            width: parse(tokens.next(), i)?,
+                            color: parse_color(tokens.next(), i)?,
+                        })
+                    }
+                    _ => {
+                        series.effects.glow = Some(Glow {
+                            radius: parse(tokens.next(), i)?,
+                            color: parse_color(tokens.next(), i)?,
+                        })
+                    }
+                }
+            }
+            Some("shape") => {
+                let layer = parse(tokens.next(), i)?;
+                let color = parse_color(tokens.next(), i)?;
+                let fill = parse_paint(&mut tokens, i)?;
+                let geometry = parse_geometry(&mut tokens, i)?;
+
+                let mut shape = Shape::new(geometry, color);
+                shape.fill = fill;
+                shape.layer = layer;
+                scene.shapes.push(shape);
+            }
+            Some("backdrop") => {
+                let file_name = rest_of_line(line, 1);
+                if file_name.is_empty() {
+                    return Err(invalid(i, "missing backdrop file name"));
+                }
+                let image_path = Path::new(path).with_file_name(file_name);
+                match PatternImage::load(&image_path.to_string_lossy()) {
+                    Ok(image) => {
+                        let mut buffer = PixelBuffer::new(image.width, image.height, 0);
+                        buffer.pixels = image.pixels.iter().flat_map(|p| p.to_le_bytes()).collect();
+                        backdrop = Some(buffer);
+                    }
+                    Err(e) => hud::status(format!(
+                        "Failed to load backdrop '{}': {}",
+                        image_path.display(),
+                        e
+                    )),
+                }
+            }
+            Some(record) => hud::status(format!(
+                "Skipping unknown record '{}' on line {}",
+                record,
+                i + 1
+            )),
+        }
+    }
+
+    if scene.layers.is_empty() {
+        scene.layers.push(Layer::new("default"));
+    }
+
+    hud::status(format!("Scene loaded from '{}'", path));
+
+    Ok((scene, backdrop))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test gets its own directory, the backdrop is written next to
+    // the scene file.
+    fn scene_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("rust-sdl-scene-test-{}", name));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("test.scene").to_string_lossy().into_owned()
+    }
+
+    fn load_text(name: &str, text: &str) -> io::Result<(Scene, Option<PixelBuffer>)> {
+        let path = scene_path(name);
+        fs::write(&path, text).unwrap();
+        load_scene(&path)
+    }
+
+    fn shape_lines(scene: &Scene) -> Vec<String> {
+        scene
+            .shapes
+            .iter()
+            .map(|shape| {
+                format!(
+                    "{} {:08X} {} {}",
+                    shape.layer,
+                    shape.color,
+                    shape.fill.as_ref().map(paint_to_string).unwrap_or_default(),
+                    geometry_to_string(&shape.geometry)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut scene = Scene::new();
+        let mut ink = Layer::new("ink and paint");
+        ink.visible = false;
+        ink.opacity = 0.25;
+        ink.blend_mode = BlendMode::Multiply;
+        scene.layers.push(ink);
+
+        let mut series = NumberSeries::new(10, 20, String::from("12  3"), 1.5, 0.25);
+        series.layer = 1;
+        series.effects.shadow = Some(DropShadow {
+            offset: (6.0, -2.5),
+            blur_radius: 4.0,
+            color: 0x80000000,
+        });
+        series.effects.glow = Some(Glow {
+            radius: 3.0,
+            color: 0xFFFFD000,
+        });
+        scene.series.push(series);
+        scene
+            .series
+            .push(NumberSeries::new(0, 0, String::from("4"), 1.0, 0.0));
+
+        let p = Point::new;
+        let geometries = vec![
+            Geometry::Line(Line::new(vec![p(0, 0), p(10, 5)])),
+            Geometry::Rectangle(Rectangle::new(vec![p(-3, 4), p(20, 30)])),
+            Geometry::Circle {
+                center: p(50, 60),
+                radius: 7,
+            },
+            Geometry::QuadraticBezier([p(0, 0), p(5, 10), p(10, 0)]),
+            Geometry::CubicBezier(CubicBezierCurve::new(vec![
+                p(0, 0),
+                p(1, 2),
+                p(3, 4),
+                p(5, 6),
+            ])),
+            Geometry::Path {
+                curves: vec![
+                    CubicBezierCurve::new(vec![p(0, 0), p(1, 1), p(2, 2), p(3, 3)]),
+                    CubicBezierCurve::new(vec![p(3, 3), p(4, 4), p(5, 5), p(0, 0)]),
+                ],
+                closed: true,
+            },
+        ];
+        for (i, geometry) in geometries.into_iter().enumerate() {
+            let mut shape = Shape::new(geometry, 0xFF102030);
+            shape.layer = i % 2;
+            shape.fill = match i {
+                1 => Some(Paint::Solid(0x80FF0000)),
+                2 => Some(Paint::RadialGradient {
+                    center: (50.0, 60.0),
+                    radius: 7.5,
+                    stops: vec![
+                        ColorStop::new(0.0, 0xFFFFFFFF),
+                        ColorStop::new(0.25, 0x80FF0000),
+                        ColorStop::new(1.0, 0xFF000000),
+                    ],
+                    spread: Spread::Reflect,
+                }),
+                5 => Some(Paint::Hatch {
+                    style: HatchStyle::CrossHatch,
+                    angle: 0.785,
+                    spacing: 8.0,
+                    thickness: 1.5,
+                    color: 0xFF000000,
+                    background: 0x00000000,
+                }),
+                _ => None,
+            };
+            scene.add(shape);
+        }
+
+        let mut backdrop = PixelBuffer::new(3, 2, 0xFFFFFFFF);
+        backdrop.pixels[4..8].copy_from_slice(&0xFF336699u32.to_le_bytes());
+
+        let path = scene_path("round-trip");
+        save_scene(&scene, Some(&backdrop), &path).unwrap();
+        let (loaded, loaded_backdrop) = load_scene(&path).unwrap();
+
+        assert_eq!(loaded.layers.len(), 2);
+        let layer = &loaded.layers[1];
+        assert_eq!(layer.name, "ink and paint");
+        assert!(!layer.visible);
+        assert_eq!(layer.opacity, 0.25);
+        assert!(layer.blend_mode == BlendMode::Multiply);
+
+        assert_eq!(loaded.series.len(), 2);
+        let series = &loaded.series[0];
+        assert_eq!(series.number_str, "12  3");
+        assert_eq!(
+            (series.x, series.y, series.scale, series.angle),
+            (10, 20, 1.5, 0.25)
+        );
+        assert_eq!(series.layer, 1);
+        assert_eq!(
+            effects_to_string(&series.effects),
+            effects_to_string(&scene.series[0].effects)
+        );
+        assert!(series.effects.outline.is_none());
+        assert!(loaded.series[1].effects.is_empty());
+
+        assert_eq!(shape_lines(&loaded), shape_lines(&scene));
+
+        let loaded_backdrop = loaded_backdrop.unwrap();
+        assert_eq!((loaded_backdrop.width, loaded_backdrop.height), (3, 2));
+        assert_eq!(loaded_backdrop.pixels, backdrop.pixels);
+    }
+
+    #[test]
+    fn version_2_layers_get_opacity_and_blend_mode() {
+        let (scene, _) = load_text(
+            "v2",
+            "rust-sdl scene 2\n\
+             layer 1 default\n\
+             layer 0 top layer\n\
+             series 5 6 1 0 42\n\
+             shape 1 FF000000 - line 0 0 10 10\n",
+        )
+        .unwrap();
+
+        assert_eq!(scene.series[0].layer, 0);
+        assert_eq!(scene.series[0].x, 5);
+
+        assert_eq!(scene.layers.len(), 2);
+        assert_eq!(scene.layers[1].name, "top layer");
+        assert!(!scene.layers[1].visible);
+        assert_eq!(scene.layers[1].opacity, 1.0);
+        assert!(scene.layers[1].blend_mode == BlendMode::Normal);
+        assert_eq!(scene.shapes[0].layer, 1);
+    }
+
+    #[test]
+    fn errors_point_at_the_original_line() {
+        let v2 = "rust-sdl scene 2\n\
+                  layer 1 default\n\
+                  series 5 6 1 0 42\n\
+                  shape 0 FF000000 - line 0 0 x 10\n";
+        let error = load_text("v2-error", v2).err().unwrap();
+        assert!(error.to_string().starts_with("line 4:"), "{}", error);
+
+        let v3 = "rust-sdl scene 3\n\
+                  \n\
+                  layer 1 1 normal default\n\
+                  shape 0 FF000000 - line 0 0 x 10\n";
+        let error = load_text("v3-error", v3).err().unwrap();
+        assert!(error.to_string().starts_with("line 4:"), "{}", error);
+    }
+
+    #[test]
+    fn version_1_is_not_a_scene_version() {
+        let error = load_text("v1", "rust-sdl scene 1\n").err().unwrap();
+        assert_eq!(error.to_string(), "unsupported scene version 1");
+    }
+
+    #[test]
+    fn names_run_to_the_end_of_the_line() {
+        let mut scene = Scene::new();
+        scene.layers[0].name = String::from("back  layer ");
+        scene
+            .series
+            .push(NumberSeries::new(0, 0, String::from(" 4 2"), 1.0, 0.0));
+
+        let text = scene_to_string(&scene, Some("my backdrop.png"));
+        assert!(text.ends_with("backdrop my backdrop.png\n"), "{}", text);
+
+        let (loaded, _) = load_text("spaces", &text).unwrap();
+        assert_eq!(loaded.layers[0].name, "back  layer ");
+        assert_eq!(loaded.series[0].number_str, " 4 2");
+    }
+
+    #[test]
+    fn missing_backdrop_still_loads_the_scene() {
+        let (scene, backdrop) = load_text(
+            "missing-backdrop",
+            "rust-sdl scene 3\n\
+             layer 1 1 normal default\n\
+             shape 0 FF000000 - line 0 0 10 10\n\
+             backdrop missing.png\n",
+        )
+        .unwrap();
+
+        assert_eq!(scene.shapes.len(), 1);
+        assert!(backdrop.is_none());
+    }
+}
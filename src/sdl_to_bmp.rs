@@ -1,53 +1,249 @@
 use image::bmp::BmpEncoder;
+use image::png::PngEncoder;
 use image::{ImageBuffer, Pixel, Rgba};
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{self, BufWriter, Write};
 
-use sdl2::render::{Canvas, Texture};
+use sdl2::render::Canvas;
 use sdl2::video::{Window, WindowContext};
 
+use crate::dither::IndexedImage;
+use crate::filters::{self, Filter};
+
+// The canvas is whatever size the window currently has.
 pub fn save_canvas_to_bmp(
-    texture: &mut Texture,
     canvas: &mut Canvas<Window>,
+    filters: &[Filter],
 ) -> Result<(), image::ImageError> {
-    let query = texture.query();
-
-    let width = query.width;
-    let height = query.height;
+    let sdl_error = |e: String| io::Error::new(io::ErrorKind::Other, e);
+    let (width, height) = canvas.output_size().map_err(sdl_error)?;
 
-    let canvas_pixels = canvas
+    let mut canvas_pixels = canvas
         .read_pixels(None, sdl2::pixels::PixelFormatEnum::ARGB8888)
-        .expect("Read pixels failes");
+        .map_err(sdl_error)?;
 
+    filters::apply_filters(&mut canvas_pixels, width, height, filters);
+
+    save_pixels_to_bmp(&canvas_pixels, width, height, "output.bmp")
+}
+
+fn pixels_to_image(pixels: &Vec<u8>, width: u32, height: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
     let mut image_buffer = ImageBuffer::<Rgba<u8>, _>::new(width, height);
 
     for y in 0..height {
         for x in 0..width {
             let index = ((y * width + x) * 4) as usize;
             let pixel = Rgba([
-                canvas_pixels[index + 2],
-                canvas_pixels[index + 1],
-                canvas_pixels[index],
-                canvas_pixels[index + 3],
+                pixels[index + 2],
+                pixels[index + 1],
+                pixels[index],
+                pixels[index + 3],
             ]);
             image_buffer.put_pixel(x, y, pixel);
         }
     }
 
-    let output_file = File::create("output.bmp").expect("Failed to create BMP file");
+    image_buffer
+}
+
+pub fn save_pixels_to_bmp(
+    pixels: &Vec<u8>,
+    width: u32,
+    height: u32,
+    path: &str,
+) -> Result<(), image::ImageError> {
+    let image_buffer = pixels_to_image(pixels, width, height);
+
+    let output_file = File::create(path)?;
     let mut buf_writer = BufWriter::new(output_file);
     let mut encoder = BmpEncoder::new(&mut buf_writer);
 
-    encoder
-        .encode(
-            &image_buffer,
-            width as u32,
-            height as u32,
-            image::ColorType::Rgba8,
-        )
-        .expect("Failed to encode BMP image");
+    encoder.encode(
+        &image_buffer,
+        width as u32,
+        height as u32,
+        image::ColorType::Rgba8,
+    )?;
+
+    Ok(())
+}
+
+pub fn save_pixels_to_png(
+    pixels: &Vec<u8>,
+    width: u32,
+    height: u32,
+    path: &str,
+) -> Result<(), image::ImageError> {
+    let image_buffer = pixels_to_image(pixels, width, height);
+
+    let output_file = File::create(path)?;
+    let buf_writer = BufWriter::new(output_file);
+    let encoder = PngEncoder::new(buf_writer);
 
-    println!("Image saved as 'output.bmp'");
+    encoder.encode(&image_buffer, width, height, image::ColorType::Rgba8)?;
 
     Ok(())
 }
+
+// Packs palette indices into rows, most significant bits first. Rows are
+// padded to whole bytes and then to `align` bytes.
+fn pack_rows(image: &IndexedImage, bits: u8, align: usize) -> Vec<Vec<u8>> {
+    let per_byte = (8 / bits) as usize;
+    let row_bytes = (image.width as usize + per_byte - 1) / per_byte;
+    let row_bytes = (row_bytes + align - 1) / align * align;
+
+    (0..image.height as usize)
+        .map(|y| {
+            let mut row = vec![0u8; row_bytes];
+            for x in 0..image.width as usize {
+                let index = image.indices[y * image.width as usize + x];
+                let shift = 8 - bits as usize * (x % per_byte + 1);
+                row[x / per_byte] |= index << shift;
+            }
+            row
+        })
+        .collect()
+}
+
+// BMP has no 2-bit format, those images are written with 4 bits per pixel.
+pub fn save_indexed_bmp(image: &IndexedImage, path: &str) -> io::Result<()> {
+    let bits = if image.bits == 2 { 4 } else { image.bits };
+    let rows = pack_rows(image, bits, 4);
+    let row_bytes = rows.first().map(|row| row.len()).unwrap_or(0);
+
+    let palette_bytes = image.palette.len() * 4;
+    let data_offset = 14 + 40 + palette_bytes;
+    let file_size = data_offset + row_bytes * rows.len();
+
+    let output_file = File::create(path)?;
+    let mut w = BufWriter::new(output_file);
+
+    w.write_all(b"BM")?;
+    w.write_all(&(file_size as u32).to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&(data_offset as u32).to_le_bytes())?;
+
+    w.write_all(&40u32.to_le_bytes())?;
+    w.write_all(&(image.width as i32).to_le_bytes())?;
+    w.write_all(&(image.height as i32).to_le_bytes())?;
+    w.write_all(&1u16.to_le_bytes())?;
+    w.write_all(&(bits as u16).to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&((row_bytes * rows.len()) as u32).to_le_bytes())?;
+    w.write_all(&2835i32.to_le_bytes())?;
+    w.write_all(&2835i32.to_le_bytes())?;
+    w.write_all(&(image.palette.len() as u32).to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+
+    for color in &image.palette {
+        w.write_all(&[*color as u8, (*color >> 8) as u8, (*color >> 16) as u8, 0])?;
+    }
+
+    // Bottom-up rows.
+    for row in rows.iter().rev() {
+        w.write_all(row)?;
+    }
+
+    w.flush()?;
+
+    Ok(())
+}
+
+pub fn save_indexed_png(image: &IndexedImage, path: &str) -> io::Result<()> {
+    let depth = match image.bits {
+        1 => png::BitDepth::One,
+        2 => png::BitDepth::Two,
+        4 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+
+    let palette: Vec<u8> = image
+        .palette
+        .iter()
+        .flat_map(|color| [(*color >> 16) as u8, (*color >> 8) as u8, *color as u8])
+        .collect();
+    let data: Vec<u8> = pack_rows(image, image.bits, 1).concat();
+
+    let output_file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(output_file), image.width, image.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_palette(palette);
+
+    let to_io = |e: png::EncodingError| io::Error::new(io::ErrorKind::Other, e);
+    let mut writer = encoder.write_header().map_err(to_io)?;
+    writer.write_image_data(&data).map_err(to_io)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Five pixels per row with alternating indices, five rows tall.
+    fn stripes(bits: u8) -> IndexedImage {
+        let top = ((1u16 << bits) - 1) as u8;
+        IndexedImage {
+            width: 5,
+            height: 5,
+            bits,
+            palette: (0..=top as u32).collect(),
+            indices: (0..25).map(|i| if i % 2 == 0 { top } else { 0 }).collect(),
+        }
+    }
+
+    #[test]
+    fn rows_pack_most_significant_bits_first() {
+        assert_eq!(pack_rows(&stripes(1), 1, 1)[0], vec![0b1010_1000]);
+        assert_eq!(pack_rows(&stripes(4), 4, 1)[0], vec![0xF0, 0xF0, 0xF0]);
+        assert_eq!(pack_rows(&stripes(8), 8, 1)[0], vec![255, 0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn bmp_rows_are_padded_to_four_bytes() {
+        for bits in [1, 4, 8] {
+            let rows = pack_rows(&stripes(bits), bits, 4);
+            assert_eq!(rows.len(), 5);
+            assert!(rows.iter().all(|row| row.len() % 4 == 0));
+        }
+        assert_eq!(pack_rows(&stripes(1), 1, 4)[0].len(), 4);
+        assert_eq!(pack_rows(&stripes(4), 4, 4)[0].len(), 4);
+        assert_eq!(pack_rows(&stripes(8), 8, 4)[0].len(), 8);
+    }
+
+    #[test]
+    fn png_rows_are_padded_to_whole_bytes() {
+        assert_eq!(pack_rows(&stripes(1), 1, 1)[0].len(), 1);
+        assert_eq!(pack_rows(&stripes(4), 4, 1)[0].len(), 3);
+        assert_eq!(pack_rows(&stripes(8), 8, 1)[0].len(), 5);
+    }
+
+    #[test]
+    fn indexed_files_have_the_padded_size() {
+        let dir = std::env::temp_dir();
+        for bits in [1, 4, 8] {
+            let image = stripes(bits);
+
+            let bmp = dir.join(format!("rust-sdl-test-{}bit.bmp", bits));
+            save_indexed_bmp(&image, bmp.to_str().unwrap()).unwrap();
+            let bytes = std::fs::read(&bmp).unwrap();
+            let row_bytes = pack_rows(&image, bits, 4)[0].len();
+            assert_eq!(
+                bytes.len(),
+                14 + 40 + image.palette.len() * 4 + row_bytes * 5
+            );
+            std::fs::remove_file(&bmp).unwrap();
+
+            let png = dir.join(format!("rust-sdl-test-{}bit.png", bits));
+            save_indexed_png(&image, png.to_str().unwrap()).unwrap();
+            let mut decoder = png::Decoder::new(File::open(&png).unwrap());
+            decoder.set_transformations(png::Transformations::IDENTITY);
+            let (info, mut reader) = decoder.read_info().unwrap();
+            let mut data = vec![0; info.buffer_size()];
+            reader.next_frame(&mut data).unwrap();
+            assert_eq!(data, pack_rows(&image, bits, 1).concat());
+            std::fs::remove_file(&png).unwrap();
+        }
+    }
+}
@@ -0,0 +1,588 @@
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::bezier::{self, Vec2};
+use crate::camera::Camera;
+use crate::graphics;
+use crate::history::Command;
+use crate::raster::{self, Primitive};
+use crate::scene::{Geometry, Scene, Shape};
+
+// Clicks closer than this to an outline pick the shape, closer than
+// HANDLE_RADIUS to a handle grab the handle. Both are in screen pixels so
+// they stay the same size at any zoom.
+const HIT_DISTANCE: f32 = 6.0;
+const HANDLE_RADIUS: f32 = 6.0;
+
+impl Shape {
+    // Editable points. Path handles are shared between neighbouring curves,
+    // circles expose their center and a point on the rim.
+    pub fn handles(&self) -> Vec<Point> {
+        match &self.geometry {
+            Geometry::Line(line) => line.controll_points.clone(),
+            Geometry::Rectangle(rectangle) => rectangle.controll_points.clone(),
+            Geometry::Circle { center, radius } => {
+                vec![*center, Point::new(center.x + radius, center.y)]
+            }
+            Geometry::QuadraticBezier(points) => points.to_vec(),
+            Geometry::CubicBezier(curve) => curve.controll_points.clone(),
+            Geometry::Path { curves, .. } => {
+                let mut handles: Vec<Point> =
+                    curves.first().map(|curve| curve.controll_points[0]).into_iter().collect();
+                for curve in curves.iter() {
+                    handles.extend_from_slice(&curve.controll_points[1..4]);
+                }
+                handles
+            }
+        }
+    }
+
+    // Handles other shapes snap to: where the outline starts and ends.
+    pub fn endpoints(&self) -> Vec<Point> {
+        match &self.geometry {
+            Geometry::Circle { .. } => Vec::new(),
+            Geometry::Rectangle(rectangle) => match rectangle.controll_points.as_slice() {
+                [a, b, ..] => vec![*a, Point::new(a.x, b.y), *b, Point::new(b.x, a.y)],
+                _ => Vec::new(),
+            },
+            _ => {
+                let handles = self.handles();
+                match (handles.first(), handles.last()) {
+                    (Some(first), Some(last)) => vec![*first, *last],
+                    _ => Vec::new(),
+                }
+            }
+        }
+    }
+
+    pub fn move_handle(&mut self, index: usize, to: Point) {
+        match &mut self.geometry {
+            Geometry::Line(line) => set_point(&mut line.controll_points, index, to),
+            Geometry::Rectangle(rectangle) => set_point(&mut rectangle.controll_points, index, to),
+            Geometry::Circle { center, radius } => {
+                if index == 0 {
+                    *center = to;
+                } else {
+                    let dx = (to.x - center.x) as f32;
+                    let dy = (to.y - center.y) as f32;
+                    *radius = (dx * dx + dy * dy).sqrt().round() as i32;
+                }
+            }
+            Geometry::QuadraticBezier(points) => set_point(points, index, to),
+            Geometry::CubicBezier(curve) => set_point(&mut curve.controll_points, index, to),
+            Geometry::Path { curves, .. } => {
+                if index == 0 {
+                    if let Some(curve) = curves.first_mut() {
+                        curve.controll_points[0] = to;
+                    }
+                    return;
+                }
+
+                let curve = (index - 1) / 3;
+                let point = (index - 1) % 3 + 1;
+                if curve >= curves.len() {
+                    return;
+                }
+
+                curves[curve].controll_points[point] = to;
+                if point == 3 && curve + 1 < curves.len() {
+                    curves[curve + 1].controll_points[0] = to;
+                }
+            }
+        }
+    }
+
+    pub fn translate(&mut self, dx: i32, dy: i32) {
+        let offset = |p: &mut Point| graphics::translate(p, dx, dy);
+
+        match &mut self.geometry {
+            Geometry::Line(line) => line.controll_points.iter_mut().for_each(offset),
+            Geometry::Rectangle(rectangle) => rectangle.controll_points.iter_mut().for_each(offset),
+            Geometry::Circle { center, .. } => offset(center),
+            Geometry::QuadraticBezier(points) => points.iter_mut().for_each(offset),
+            Geometry::CubicBezier(curve) => curve.controll_points.iter_mut().for_each(offset),
+            Geometry::Path { curves, .. } => curves
+                .iter_mut()
+                .flat_map(|curve| curve.controll_points.iter_mut())
+                .for_each(offset),
+        }
+    }
+
+    // Distance from a point to the closest part of the outline.
+    pub fn distance_to(&self, point: Point) -> f32 {
+        let p = (point.x as f32, point.y as f32);
+        let to_vec2 = |p: &Point| (p.x as f32, p.y as f32);
+
+        self.primitives()
+            .iter()
+            .map(|primitive| match primitive {
+                Primitive::Point(a) => bezier::length(bezier::sub(p, to_vec2(a))),
+                Primitive::Line(a, b) => distance_to_segment(p, to_vec2(a), to_vec2(b)),
+                Primitive::Circle(center, radius) => {
+                    (bezier::length(bezier::sub(p, to_vec2(center))) - *radius as f32).abs()
+                }
+                Primitive::QuadraticBezier([a, b, c]) => {
+                    let cubic = bezier::elevate_quadratic(to_vec2(a), to_vec2(b), to_vec2(c));
+                    bezier::closest_point(&cubic, p).2
+                }
+                Primitive::CubicBezier(points) => {
+                    let cubic = [
+                        to_vec2(&points[0]),
+                        to_vec2(&points[1]),
+                        to_vec2(&points[2]),
+                        to_vec2(&points[3]),
+                    ];
+                    bezier::closest_point(&cubic, p).2
+                }
+                Primitive::FilledPolygon(points) => {
+                    let n = points.len();
+                    (0..n)
+                        .map(|i| distance_to_segment(p, to_vec2(&points[i]), to_vec2(&points[(i + 1) % n])))
+                        .fold(f32::MAX, f32::min)
+                }
+            })
+            .fold(f32::MAX, f32::min)
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        let boxes: Vec<Rect> = self.primitives().iter().map(Primitive::bounding_box).collect();
+        match boxes.split_first() {
+            Some((first, rest)) => rest.iter().fold(*first, |a, b| a.union(*b)),
+            None => Rect::new(0, 0, 0, 0),
+        }
+    }
+}
+
+fn set_point(points: &mut [Point], index: usize, to: Point) {
+    if let Some(point) = points.get_mut(index) {
+        *point = to;
+    }
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = bezier::sub(b, a);
+    let length_squared = bezier::dot(ab, ab);
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (bezier::dot(bezier::sub(p, a), ab) / length_squared).clamp(0.0, 1.0)
+    };
+
+    bezier::length(bezier::sub(p, bezier::add(a, bezier::mul(ab, t))))
+}
+
+pub struct Snap {
+    // Grid spacing in pixels, None turns grid snapping off.
+    pub grid: Option<i32>,
+    pub to_endpoints: bool,
+    // In screen pixels.
+    pub radius: i32,
+}
+
+impl Snap {
+    pub fn new() -> Self {
+        Snap {
+            grid: None,
+            to_endpoints: true,
+            radius: 8,
+        }
+    }
+
+    // Endpoints of shapes that are not being dragged win over the grid.
+    pub fn apply(&self, point: Point, scene: &Scene, ignore: &[usize], zoom: f32) -> Point {
+        self.to_endpoint(point, scene, ignore, zoom)
+            .unwrap_or_else(|| self.to_grid(point))
+    }
+
+    // The closest endpoint within the radius.
+    fn to_endpoint(
+        &self,
+        point: Point,
+        scene: &Scene,
+        ignore: &[usize],
+        zoom: f32,
+    ) -> Option<Point> {
+        if !self.to_endpoints {
+            return None;
+        }
+
+        scene
+            .shapes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !ignore.contains(i))
+            .flat_map(|(_, shape)| shape.endpoints())
+            .map(|p| (p, (p.x - point.x).abs().max((p.y - point.y).abs())))
+            .filter(|(_, distance)| *distance as f32 * zoom <= self.radius as f32)
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(p, _)| p)
+    }
+
+    fn to_grid(&self, point: Point) -> Point {
+        match self.grid {
+            Some(grid) if grid > 0 => Point::new(
+                (point.x as f32 / grid as f32).round() as i32 * grid,
+                (point.y as f32 / grid as f32).round() as i32 * grid,
+            ),
+            _ => point,
+        }
+    }
+
+    // How far to nudge shapes moved by offset so one of their points lands
+    // on an endpoint, or failing that so the first one lands on the grid.
+    fn offset_snap(
+        &self,
+        points: &[Point],
+        offset: (i32, i32),
+        scene: &Scene,
+        ignore: &[usize],
+        zoom: f32,
+    ) -> (i32, i32) {
+        let moved: Vec<Point> = points
+            .iter()
+            .map(|p| Point::new(p.x + offset.0, p.y + offset.1))
+            .collect();
+
+        let closest = moved
+            .iter()
+            .filter_map(|p| {
+                self.to_endpoint(*p, scene, ignore, zoom)
+                    .map(|to| (to.x - p.x, to.y - p.y))
+            })
+            .min_by_key(|(dx, dy)| dx.abs().max(dy.abs()));
+
+        match (closest, moved.first()) {
+            (Some(delta), _) => delta,
+            (None, Some(first)) => {
+                let to = self.to_grid(*first);
+                (to.x - first.x, to.y - first.y)
+            }
+            (None, None) => (0, 0),
+        }
+    }
+}
+
+enum Drag {
+    Handle { shape: usize, handle: usize },
+    // Cursor where the drag started and how far the shapes have moved.
+    Move { start: Point, moved: (i32, i32) },
+    Marquee { start: Point, end: Point },
+}
+
+pub struct Selection {
+    pub selected: Vec<usize>,
+    pub snap: Snap,
+    drag: Option<Drag>,
+    // Shapes as they were when the drag started, for the undo history.
+    before: Vec<(usize, Shape)>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection {
+            selected: Vec::new(),
+            snap: Snap::new(),
+            drag: None,
+            before: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.selected.clear();
+        self.drag = None;
+        self.before.clear();
+    }
+
+    fn handle_at(&self, scene: &Scene, point: Point, zoom: f32) -> Option<(usize, usize)> {
+        let radius = HANDLE_RADIUS / zoom;
+        self.selected.iter().rev().find_map(|&shape| {
+            scene.shapes[shape]
+                .handles()
+                .iter()
+                .position(|h| {
+                    (h.x - point.x).abs() as f32 <= radius && (h.y - point.y).abs() as f32 <= radius
+                })
+                .map(|handle| (shape, handle))
+        })
+    }
+
+    // Topmost shape whose outline passes near the point.
+    pub fn shape_at(scene: &Scene, point: Point, zoom: f32) -> Option<usize> {
+        scene
+            .shapes
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, shape)| shape.distance_to(point) <= HIT_DISTANCE / zoom)
+            .map(|(i, _)| i)
+    }
+
+    // Additive adds to (or toggles in) the selection instead of replacing it,
+    // like holding shift in most editors. The point is in scene coordinates,
+    // zoom is the camera's.
+    pub fn mouse_down(&mut self, scene: &Scene, point: Point, additive: bool, zoom: f32) {
+        if let Some((shape, handle)) = self.handle_at(scene, point, zoom) {
+            self.drag = Some(Drag::Handle { shape, handle });
+            self.before = vec![(shape, scene.shapes[shape].clone())];
+            return;
+        }
+
+        match Selection::shape_at(scene, point, zoom) {
+            Some(shape) => {
+                if additive {
+                    match self.selected.iter().position(|&s| s == shape) {
+                        Some(index) => {
+                            self.selected.remove(index);
+                        }
+                        None => self.selected.push(shape),
+                    }
+                } else if !self.selected.contains(&shape) {
+                    self.selected = vec![shape];
+                }
+
+                self.drag = Some(Drag::Move {
+                    start: point,
+                    moved: (0, 0),
+                });
+                self.before = self
+                    .selected
+                    .iter()
+                    .map(|&shape| (shape, scene.shapes[shape].clone()))
+                    .collect();
+            }
+            None => {
+                if !additive {
+                    self.selected.clear();
+                }
+                self.drag = Some(Drag::Marquee { start: point, end: point });
+            }
+        }
+    }
+
+    pub fn mouse_move(&mut self, scene: &mut Scene, point: Point, zoom: f32) {
+        match &mut self.drag {
+            Some(Drag::Handle { shape, handle }) => {
+                let to = self.snap.apply(point, scene, &[*shape], zoom);
+                scene.shapes[*shape].move_handle(*handle, to);
+            }
+            // The moved shapes' own endpoints snap, not the cursor, so
+            // grabbing a line anywhere along it still joins its ends.
+            Some(Drag::Move { start, moved }) => {
+                let offset = (point.x - start.x, point.y - start.y);
+                let points: Vec<Point> = self
+                    .before
+                    .iter()
+                    .flat_map(|(_, shape)| {
+                        let endpoints = shape.endpoints();
+                        if endpoints.is_empty() {
+                            shape.handles()
+                        } else {
+                            endpoints
+                        }
+                    })
+                    .collect();
+                let snap = self
+                    .snap
+                    .offset_snap(&points, offset, scene, &self.selected, zoom);
+                let to = (offset.0 + snap.0, offset.1 + snap.1);
+
+                for &shape in self.selected.iter() {
+                    scene.shapes[shape].translate(to.0 - moved.0, to.1 - moved.1);
+                }
+                *moved = to;
+            }
+            Some(Drag::Marquee { end, .. }) => *end = point,
+            None => {}
+        }
+    }
+
+    // Finishes the drag. Moving shapes or handles gives back the edit for
+    // the undo history.
+    pub fn mouse_up(&mut self, scene: &Scene) -> Option<Command> {
+        let before: Vec<(usize, Shape)> = self.before.drain(..).collect();
+
+        match self.drag.take()? {
+            Drag::Marquee { start, end } => {
+                let area = raster::bounding_box_of(&[start, end]);
+
+                for (i, shape) in scene.shapes.iter().enumerate() {
+                    let bounds = shape.bounding_box();
+                    let inside = area.contains_rect(bounds);
+                    if inside && !self.selected.contains(&i) {
+                        self.selected.push(i);
+                    }
+                }
+                None
+            }
+            Drag::Handle { .. } | Drag::Move { .. } => {
+                let edits: Vec<(usize, Shape, Shape)> = before
+                    .into_iter()
+                    .filter(|(i, shape)| shape.handles() != scene.shapes[*i].handles())
+                    .map(|(i, shape)| (i, shape, scene.shapes[i].clone()))
+                    .collect();
+
+                if edits.is_empty() {
+                    None
+                } else {
+                    Some(Command::EditShapes { edits })
+                }
+            }
+        }
+    }
+
+    // Removes the selected shapes, highest index first so the rest stay valid.
+    pub fn delete(&mut self, scene: &mut Scene) -> Option<Command> {
+        let mut selected: Vec<usize> = self
+            .selected
+            .iter()
+            .copied()
+            .filter(|&shape| shape < scene.shapes.len())
+            .collect();
+        selected.sort_unstable();
+        selected.dedup();
+        self.clear();
+
+        if selected.is_empty() {
+            return None;
+        }
+
+        let shapes: Vec<(usize, Shape)> = selected
+            .iter()
+            .map(|&shape| (shape, scene.shapes[shape].clone()))
+            .collect();
+        for &shape in selected.iter().rev() {
+            scene.shapes.remove(shape);
+        }
+
+        Some(Command::DeleteShapes { shapes })
+    }
+
+    // Handles of the selected shapes, with the bars from curve end points to
+    // their control points, and the marquee while one is dragged.
+    pub fn draw(&self, scene: &Scene, camera: &Camera, canvas: &mut Canvas<Window>) {
+        canvas.set_draw_color(Color::RGB(128, 128, 128));
+
+        for &shape in self.selected.iter() {
+            let shape = match scene.shapes.get(shape) {
+                Some(shape) => shape,
+                None => continue,
+            };
+
+            let curves: Vec<&[Point]> = match &shape.geometry {
+                Geometry::QuadraticBezier(points) => vec![&points[..]],
+                Geometry::CubicBezier(curve) => vec![&curve.controll_points[..]],
+                Geometry::Path { curves, .. } => {
+                    curves.iter().map(|curve| &curve.controll_points[..]).collect()
+                }
+                _ => Vec::new(),
+            };
+
+            for points in curves {
+                if let (Some(first), Some(second)) = (points.first(), points.get(1)) {
+                    let (first, second) = (camera.to_screen(*first), camera.to_screen(*second));
+                    graphics::draw_line(first, second, canvas);
+                }
+                if let [.., before_last, last] = points {
+                    let (before_last, last) = (camera.to_screen(*before_last), camera.to_screen(*last));
+                    graphics::draw_line(before_last, last, canvas);
+                }
+            }
+
+            for handle in shape.handles() {
+                graphics::draw_target(camera.to_screen(handle), canvas);
+            }
+        }
+
+        if let Some(Drag::Marquee { start, end }) = &self.drag {
+            let marquee =
+                raster::bounding_box_of(&[camera.to_screen(*start), camera.to_screen(*end)]);
+            canvas
+                .draw_rect(marquee)
+                .expect("Drawing marquee failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Line;
+
+    fn line(a: (i32, i32), b: (i32, i32)) -> Shape {
+        let line = Line::new(vec![Point::new(a.0, a.1), Point::new(b.0, b.1)]);
+        Shape::new(Geometry::Line(line), 0xFF000000)
+    }
+
+    #[test]
+    fn hit_distance_is_in_screen_pixels() {
+        let mut scene = Scene::new();
+        scene.add(line((0, 0), (100, 0)));
+
+        assert_eq!(Selection::shape_at(&scene, Point::new(50, 4), 1.0), Some(0));
+        assert_eq!(Selection::shape_at(&scene, Point::new(50, 4), 4.0), None);
+        assert_eq!(
+            Selection::shape_at(&scene, Point::new(50, 20), 0.25),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn handle_radius_is_in_screen_pixels() {
+        let mut scene = Scene::new();
+        scene.add(line((0, 0), (100, 0)));
+        let mut selection = Selection::new();
+        selection.selected = vec![0];
+
+        assert_eq!(
+            selection.handle_at(&scene, Point::new(4, 4), 1.0),
+            Some((0, 0))
+        );
+        assert_eq!(selection.handle_at(&scene, Point::new(4, 4), 4.0), None);
+    }
+
+    #[test]
+    fn moving_snaps_the_shape_endpoints() {
+        let mut scene = Scene::new();
+        scene.add(line((0, 0), (10, 0)));
+        scene.add(line((50, 20), (80, 20)));
+        let mut selection = Selection::new();
+
+        // Grabbed in the middle, the cursor ends up far from any endpoint
+        // but the line's start lands next to the first line's end.
+        selection.mouse_down(&scene, Point::new(65, 20), false, 1.0);
+        selection.mouse_move(&mut scene, Point::new(26, 1), 1.0);
+        assert_eq!(
+            scene.shapes[1].handles(),
+            vec![Point::new(10, 0), Point::new(40, 0)]
+        );
+
+        // Out of reach the shapes follow the cursor exactly.
+        selection.mouse_move(&mut scene, Point::new(65, 40), 1.0);
+        assert_eq!(
+            scene.shapes[1].handles(),
+            vec![Point::new(50, 40), Point::new(80, 40)]
+        );
+
+        match selection.mouse_up(&scene) {
+            Some(Command::EditShapes { edits }) => assert_eq!(edits.len(), 1),
+            _ => panic!("expected an edit"),
+        }
+    }
+
+    #[test]
+    fn moving_snaps_the_first_point_to_the_grid() {
+        let mut scene = Scene::new();
+        scene.add(line((3, 3), (23, 3)));
+        let mut selection = Selection::new();
+        selection.snap.grid = Some(10);
+
+        selection.mouse_down(&scene, Point::new(13, 3), false, 1.0);
+        selection.mouse_move(&mut scene, Point::new(21, 5), 1.0);
+        assert_eq!(
+            scene.shapes[0].handles(),
+            vec![Point::new(10, 10), Point::new(30, 10)]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::camera::Camera;
+use crate::graphics;
+use crate::history::{Command, History};
+use crate::hud;
+use crate::numbers::{self, NumberSeries, DEFAULT_NUM_HEIGHT};
+use crate::scene::Scene;
+
+const CARET_COLOR: Color = Color::RGB(40, 90, 200);
+
+// Typing into a NumberSeries in the scene. The series is laid out again
+// after every change, the whole edit becomes one undo step in finish.
+pub struct TextEdit {
+    pub series: usize,
+    // Character indices, the selection runs between anchor and caret.
+    caret: usize,
+    anchor: Option<usize>,
+    before: String,
+}
+
+impl TextEdit {
+    pub fn new(scene: &Scene, series: usize, caret: usize) -> Self {
+        TextEdit {
+            series,
+            caret,
+            anchor: None,
+            before: scene.series[series].number_str.clone(),
+        }
+    }
+
+    fn selection(&self) -> Option<(usize, usize)> {
+        match self.anchor {
+            Some(anchor) if anchor != self.caret => {
+                Some((anchor.min(self.caret), anchor.max(self.caret)))
+            }
+            _ => None,
+        }
+    }
+
+    fn chars(&self, scene: &Scene) -> Vec<char> {
+        scene.series[self.series].number_str.chars().collect()
+    }
+
+    fn set_chars(&self, scene: &mut Scene, chars: &[char]) {
+        scene.series[self.series].set_text(chars.iter().collect());
+    }
+
+    // Removes the selected characters, true if there were any.
+    fn delete_selection(&mut self, chars: &mut Vec<char>) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                chars.drain(start..end);
+                self.caret = start;
+                self.anchor = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Moves the caret, extending the selection when shift is held.
+    fn move_caret(&mut self, to: usize, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.caret);
+        } else {
+            self.anchor = None;
+        }
+        self.caret = to;
+    }
+
+    // True when editing is finished.
+    pub fn key_down(&mut self, scene: &mut Scene, keycode: Keycode, keymod: Mod) -> bool {
+        let mut chars = self.chars(scene);
+        let len = chars.len();
+        let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+
+        match keycode {
+            Keycode::Return | Keycode::KpEnter | Keycode::Escape => return true,
+            Keycode::Left => match self.selection() {
+                Some((start, _)) if !shift => self.move_caret(start, false),
+                _ => self.move_caret(self.caret.saturating_sub(1), shift),
+            },
+            Keycode::Right => match self.selection() {
+                Some((_, end)) if !shift => self.move_caret(end, false),
+                _ => self.move_caret((self.caret + 1).min(len), shift),
+            },
+            Keycode::Home => self.move_caret(0, shift),
+            Keycode::End => self.move_caret(len, shift),
+            Keycode::A if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                self.anchor = Some(0);
+                self.caret = len;
+            }
+            Keycode::Backspace => {
+                if !self.delete_selection(&mut chars) && self.caret > 0 {
+                    self.caret -= 1;
+                    chars.remove(self.caret);
+                }
+                self.set_chars(scene, &chars);
+            }
+            Keycode::Delete => {
+                if !self.delete_selection(&mut chars) && self.caret < len {
+                    chars.remove(self.caret);
+                }
+                self.set_chars(scene, &chars);
+            }
+            _ => {}
+        }
+
+        false
+    }
+
+    // Characters without a glyph are left out, the rest replace the
+    // selection.
+    pub fn text_input(&mut self, scene: &mut Scene, text: &str) {
+        let mut chars = self.chars(scene);
+        let mut changed = self.delete_selection(&mut chars);
+
+        for c in text.chars() {
+            if numbers::has_glyph(c) {
+                chars.insert(self.caret, c);
+                self.caret += 1;
+                changed = true;
+            } else {
+                hud::status(format!("No glyph for '{}'", c));
+            }
+        }
+
+        if changed {
+            self.set_chars(scene, &chars);
+        }
+    }
+
+    pub fn finish(self, scene: &mut Scene, history: &mut History) {
+        let series = &mut scene.series[self.series];
+
+        if series.number_str.is_empty() {
+            hud::status(String::from("Series text cannot be empty"));
+            series.set_text(self.before);
+        } else if series.number_str != self.before {
+            history.record(Command::SeriesText {
+                index: self.series,
+                before: self.before,
+                after: series.number_str.clone(),
+            });
+        }
+    }
+
+    // Caret and selection follow the series' scale and rotation.
+    pub fn draw(&self, scene: &Scene, camera: &Camera, canvas: &mut Canvas<Window>) {
+        let series = &scene.series[self.series];
+        let height = DEFAULT_NUM_HEIGHT as f32;
+        let corner = |index: usize, v: f32| {
+            camera.to_screen(series.to_scene(NumberSeries::char_offset(index), v))
+        };
+
+        canvas.set_draw_color(CARET_COLOR);
+
+        if let Some((start, end)) = self.selection() {
+            let outline: [Point; 4] = [
+                corner(start, 0.0),
+                corner(end, 0.0),
+                corner(end, height),
+                corner(start, height),
+            ];
+            for i in 0..outline.len() {
+                graphics::draw_line(outline[i], outline[(i + 1) % outline.len()], canvas);
+            }
+        }
+
+        graphics::draw_line(corner(self.caret, 0.0), corner(self.caret, height), canvas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::PixelBuffer;
+
+    fn scene_with(text: &str) -> Scene {
+        let mut scene = Scene::new();
+        scene
+            .series
+            .push(NumberSeries::new(0, 0, String::from(text), 1.0, 0.0));
+        scene
+    }
+
+    fn text(scene: &Scene) -> &str {
+        &scene.series[0].number_str
+    }
+
+    #[test]
+    fn typing_inserts_at_the_caret() {
+        let mut scene = scene_with("1234");
+        let mut edit = TextEdit::new(&scene, 0, 2);
+
+        edit.text_input(&mut scene, "90");
+        assert_eq!(text(&scene), "129034");
+        assert_eq!(scene.series[0].numbers.len(), 6);
+
+        edit.key_down(&mut scene, Keycode::Home, Mod::NOMOD);
+        edit.text_input(&mut scene, "7");
+        edit.key_down(&mut scene, Keycode::End, Mod::NOMOD);
+        edit.text_input(&mut scene, "8");
+        assert_eq!(text(&scene), "71290348");
+    }
+
+    #[test]
+    fn characters_without_a_glyph_are_left_out() {
+        let mut scene = scene_with("12");
+        let mut edit = TextEdit::new(&scene, 0, 1);
+
+        edit.text_input(&mut scene, "%5~");
+        assert_eq!(text(&scene), "152");
+        assert_eq!(edit.caret, 2);
+
+        edit.text_input(&mut scene, "%");
+        assert_eq!(text(&scene), "152");
+    }
+
+    #[test]
+    fn backspace_and_delete_remove_around_the_caret() {
+        let mut scene = scene_with("1234");
+        let mut edit = TextEdit::new(&scene, 0, 2);
+
+        edit.key_down(&mut scene, Keycode::Backspace, Mod::NOMOD);
+        assert_eq!(text(&scene), "134");
+        edit.key_down(&mut scene, Keycode::Delete, Mod::NOMOD);
+        assert_eq!(text(&scene), "14");
+
+        edit.key_down(&mut scene, Keycode::Home, Mod::NOMOD);
+        edit.key_down(&mut scene, Keycode::Backspace, Mod::NOMOD);
+        edit.key_down(&mut scene, Keycode::End, Mod::NOMOD);
+        edit.key_down(&mut scene, Keycode::Delete, Mod::NOMOD);
+        assert_eq!(text(&scene), "14");
+    }
+
+    #[test]
+    fn caret_stays_inside_the_text() {
+        let mut scene = scene_with("12");
+        let mut edit = TextEdit::new(&scene, 0, 1);
+
+        edit.key_down(&mut scene, Keycode::Left, Mod::NOMOD);
+        edit.key_down(&mut scene, Keycode::Left, Mod::NOMOD);
+        assert_eq!(edit.caret, 0);
+        for _ in 0..4 {
+            edit.key_down(&mut scene, Keycode::Right, Mod::NOMOD);
+        }
+        assert_eq!(edit.caret, 2);
+        assert_eq!(edit.selection(), None);
+    }
+
+    #[test]
+    fn selection_is_replaced_or_collapsed() {
+        let mut scene = scene_with("12345");
+        let mut edit = TextEdit::new(&scene, 0, 1);
+
+        edit.key_down(&mut scene, Keycode::Right, Mod::LSHIFTMOD);
+        edit.key_down(&mut scene, Keycode::Right, Mod::RSHIFTMOD);
+        assert_eq!(edit.selection(), Some((1, 3)));
+
+        // Without shift the caret goes to the end of the selection it is on.
+        edit.key_down(&mut scene, Keycode::Left, Mod::NOMOD);
+        assert_eq!((edit.selection(), edit.caret), (None, 1));
+
+        edit.key_down(&mut scene, Keycode::End, Mod::LSHIFTMOD);
+        edit.key_down(&mut scene, Keycode::Left, Mod::LSHIFTMOD);
+        assert_eq!(edit.selection(), Some((1, 4)));
+        edit.text_input(&mut scene, "0");
+        assert_eq!(text(&scene), "105");
+        assert_eq!((edit.selection(), edit.caret), (None, 2));
+
+        edit.key_down(&mut scene, Keycode::A, Mod::LCTRLMOD);
+        assert_eq!(edit.selection(), Some((0, 3)));
+        edit.key_down(&mut scene, Keycode::Backspace, Mod::NOMOD);
+        assert_eq!(text(&scene), "");
+        assert_eq!(edit.caret, 0);
+    }
+
+    #[test]
+    fn finishing_records_one_undo_step() {
+        let mut scene = scene_with("12");
+        let mut backdrop = PixelBuffer::new(1, 1, 0xFFFFFFFF);
+        let mut history = History::new(None);
+
+        let mut edit = TextEdit::new(&scene, 0, 2);
+        edit.text_input(&mut scene, "3");
+        edit.text_input(&mut scene, "4");
+        assert!(edit.key_down(&mut scene, Keycode::Return, Mod::NOMOD));
+        edit.finish(&mut scene, &mut history);
+        assert_eq!(text(&scene), "1234");
+
+        assert!(history.undo(&mut scene, &mut backdrop));
+        assert_eq!(text(&scene), "12");
+        assert!(!history.undo(&mut scene, &mut backdrop));
+
+        // Emptied text goes back to what it was, with nothing to undo.
+        let mut edit = TextEdit::new(&scene, 0, 2);
+        edit.key_down(&mut scene, Keycode::A, Mod::LCTRLMOD);
+        edit.key_down(&mut scene, Keycode::Delete, Mod::NOMOD);
+        edit.finish(&mut scene, &mut history);
+        assert_eq!(text(&scene), "12");
+        assert!(!history.undo(&mut scene, &mut backdrop));
+    }
+}
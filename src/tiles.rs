@@ -0,0 +1,348 @@
+use std::thread;
+
+use sdl2::rect::{Point, Rect};
+
+use crate::clip;
+use crate::graphics;
+use crate::raster::{self, DrawCommand, PixelBuffer, Primitive};
+
+pub const DEFAULT_TILE_SIZE: u32 = 64;
+
+struct Tile {
+    rect: Rect,
+    commands: Vec<usize>,
+}
+
+// A primitive clipped against the whole target once, in a form each tile
+// can plot its own part of without rasterizing the rest.
+enum Spans {
+    Empty,
+    Point(Point),
+    Lines(Vec<(Point, Point)>),
+    Circle(Point, Vec<Point>),
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl Spans {
+    // Clipping against the whole target rather than the tile keeps the
+    // geometry identical to the single threaded path.
+    fn new(primitive: &Primitive, bounds: Rect) -> Self {
+        match primitive {
+            Primitive::Point(p) if bounds.contains_point(*p) => Spans::Point(*p),
+            Primitive::Point(_) => Spans::Empty,
+            Primitive::Line(p_1, p_2) => match clip::clip_line(*p_1, *p_2, bounds) {
+                Some(line) => Spans::Lines(vec![line]),
+                None => Spans::Empty,
+            },
+            Primitive::Circle(center, radius) => {
+                if graphics::circle_visible(*center, *radius, bounds) {
+                    Spans::Circle(*center, graphics::circle_octant(*radius))
+                } else {
+                    Spans::Empty
+                }
+            }
+            Primitive::QuadraticBezier([p_1, p_2, p_3]) => {
+                if !raster::bounding_box_of(&[*p_1, *p_2, *p_3]).has_intersection(bounds) {
+                    return Spans::Empty;
+                }
+                Spans::Lines(clip::clip_polyline(
+                    &clip::flatten_quadratic_bezier(*p_1, *p_2, *p_3),
+                    bounds,
+                ))
+            }
+            Primitive::CubicBezier([p_1, p_2, p_3, p_4]) => {
+                if !raster::bounding_box_of(&[*p_1, *p_2, *p_3, *p_4]).has_intersection(bounds) {
+                    return Spans::Empty;
+                }
+                Spans::Lines(clip::clip_polyline(
+                    &clip::flatten_cubic_bezier(*p_1, *p_2, *p_3, *p_4),
+                    bounds,
+                ))
+            }
+            Primitive::FilledPolygon(points) => {
+                let points: Vec<(f32, f32)> =
+                    points.iter().map(|p| (p.x as f32, p.y as f32)).collect();
+                Spans::Polygon(clip::clip_polygon(&points, bounds))
+            }
+        }
+    }
+
+    fn plot(&self, rect: Rect, plot: &mut impl FnMut(i32, i32)) {
+        match self {
+            Spans::Empty => {}
+            Spans::Point(p) => {
+                if rect.contains_point(*p) {
+                    plot(p.x, p.y);
+                }
+            }
+            Spans::Lines(lines) => {
+                for (start, end) in lines {
+                    graphics::plot_line_within(*start, *end, rect, plot);
+                }
+            }
+            Spans::Circle(center, octant) => plot_circle_within(*center, octant, rect, plot),
+            Spans::Polygon(polygon) => graphics::scan_polygon(polygon, rect, plot),
+        }
+    }
+}
+
+// The octant's x goes up by one per point, so the points that can land in
+// rect form a range for each of the eight mirror images.
+fn plot_circle_within(
+    center: Point,
+    octant: &[Point],
+    rect: Rect,
+    plot: &mut impl FnMut(i32, i32),
+) {
+    let mirrors: [(i32, i32, bool); 8] = [
+        (1, 1, false),
+        (-1, 1, false),
+        (1, -1, false),
+        (-1, -1, false),
+        (1, 1, true),
+        (-1, 1, true),
+        (1, -1, true),
+        (-1, -1, true),
+    ];
+
+    for (sign_x, sign_y, swapped) in mirrors {
+        // The pixel coordinate that follows the octant's x, and its range.
+        let (sign, origin, low, high) = if swapped {
+            (sign_y, center.y, rect.top(), rect.bottom() - 1)
+        } else {
+            (sign_x, center.x, rect.left(), rect.right() - 1)
+        };
+        let (first, last) = if sign > 0 {
+            (low - origin, high - origin)
+        } else {
+            (origin - high, origin - low)
+        };
+        let first = first.max(0) as usize;
+        let last = last.min(octant.len() as i32 - 1);
+        if last < 0 {
+            continue;
+        }
+
+        for point in octant.iter().take(last as usize + 1).skip(first) {
+            let (x, y) = if swapped {
+                (center.x + sign_x * point.y, center.y + sign_y * point.x)
+            } else {
+                (center.x + sign_x * point.x, center.y + sign_y * point.y)
+            };
+            if rect.contains_point((x, y)) {
+                plot(x, y);
+            }
+        }
+    }
+}
+
+impl Tile {
+    fn render(&self, commands: &[DrawCommand], spans: &[Spans], background: u32) -> Vec<u32> {
+        let mut pixels = vec![background; (self.rect.width() * self.rect.height()) as usize];
+
+        // Commands are binned in submission order, so overlapping pixels end up
+        // with the same color the single threaded path would give them.
+        for &index in self.commands.iter() {
+            let color = commands[index].color;
+            spans[index].plot(self.rect, &mut |x, y| {
+                let local_x = (x - self.rect.x()) as u32;
+                let local_y = (y - self.rect.y()) as u32;
+                pixels[(local_y * self.rect.width() + local_x) as usize] = color;
+            });
+        }
+
+        pixels
+    }
+}
+
+fn bin_commands(commands: &[DrawCommand], width: u32, height: u32, tile_size: u32) -> Vec<Tile> {
+    let columns = (width + tile_size - 1) / tile_size;
+    let rows = (height + tile_size - 1) / tile_size;
+
+    let mut tiles: Vec<Tile> = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let x = column * tile_size;
+            let y = row * tile_size;
+            tiles.push(Tile {
+                rect: Rect::new(
+                    x as i32,
+                    y as i32,
+                    tile_size.min(width - x),
+                    tile_size.min(height - y),
+                ),
+                commands: Vec::new(),
+            });
+        }
+    }
+
+    for (index, command) in commands.iter().enumerate() {
+        let bounds = command.primitive.bounding_box();
+
+        let first_column = (bounds.left().max(0) as u32 / tile_size).min(columns);
+        let first_row = (bounds.top().max(0) as u32 / tile_size).min(rows);
+        let last_column = ((bounds.right().max(0) as u32 + tile_size - 1) / tile_size).min(columns);
+        let last_row = ((bounds.bottom().max(0) as u32 + tile_size - 1) / tile_size).min(rows);
+
+        for row in first_row..last_row {
+            for column in first_column..last_column {
+                tiles[(row * columns + column) as usize]
+                    .commands
+                    .push(index);
+            }
+        }
+    }
+
+    tiles
+}
+
+pub fn render_tiled(
+    commands: &[DrawCommand],
+    width: u32,
+    height: u32,
+    background: u32,
+    tile_size: u32,
+    thread_count: usize,
+) -> PixelBuffer {
+    let tile_size = tile_size.max(1);
+    let thread_count = thread_count.max(1);
+    let tiles = bin_commands(commands, width, height, tile_size);
+    let bounds = Rect::new(0, 0, width, height);
+    let spans: Vec<Spans> = commands
+        .iter()
+        .map(|command| Spans::new(&command.primitive, bounds))
+        .collect();
+
+    let rendered: Vec<(usize, Vec<u32>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..thread_count)
+            .map(|worker| {
+                let tiles = &tiles;
+                let spans = &spans;
+                scope.spawn(move || {
+                    tiles
+                        .iter()
+                        .enumerate()
+                        .skip(worker)
+                        .step_by(thread_count)
+                        .map(|(index, tile)| (index, tile.render(commands, spans, background)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Tile worker panicked"))
+            .collect()
+    });
+
+    let mut buffer = PixelBuffer::new(width, height, background);
+    for (index, pixels) in rendered {
+        let rect = tiles[index].rect;
+        for local_y in 0..rect.height() {
+            for local_x in 0..rect.width() {
+                buffer.set(
+                    rect.x() + local_x as i32,
+                    rect.y() + local_y as i32,
+                    pixels[(local_y * rect.width() + local_x) as usize],
+                );
+            }
+        }
+    }
+
+    buffer
+}
+
+pub fn default_thread_count() -> usize {
+    thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_single_threaded(
+        commands: &[DrawCommand],
+        width: u32,
+        height: u32,
+        background: u32,
+    ) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(width, height, background);
+        // Not raster::render, its smooth strokes toggle is shared with other
+        // tests.
+        raster::render_plain(commands, &mut buffer);
+        buffer
+    }
+
+    // Overlapping primitives in different colors that cross tile edges and
+    // the edges of the target.
+    fn commands() -> Vec<DrawCommand> {
+        vec![
+            DrawCommand::new(
+                Primitive::Line(Point::new(-20, 5), Point::new(210, 140)),
+                0xFFFF0000,
+            ),
+            DrawCommand::new(Primitive::Circle(Point::new(100, 70), 60), 0xFF00FF00),
+            DrawCommand::new(Primitive::Circle(Point::new(190, 0), 40), 0xFF0000FF),
+            DrawCommand::new(
+                Primitive::QuadraticBezier([
+                    Point::new(0, 150),
+                    Point::new(100, -80),
+                    Point::new(200, 150),
+                ]),
+                0xFF808080,
+            ),
+            DrawCommand::new(
+                Primitive::CubicBezier([
+                    Point::new(10, 10),
+                    Point::new(250, 20),
+                    Point::new(-50, 120),
+                    Point::new(190, 130),
+                ]),
+                0xFF000000,
+            ),
+            DrawCommand::new(
+                Primitive::Line(Point::new(64, 0), Point::new(64, 143)),
+                0xFFFFFF00,
+            ),
+            DrawCommand::new(Primitive::Point(Point::new(63, 63)), 0xFF00FFFF),
+            DrawCommand::new(
+                Primitive::FilledPolygon(vec![
+                    Point::new(30, -10),
+                    Point::new(180, 60),
+                    Point::new(40, 160),
+                ]),
+                0x80FF00FF,
+            ),
+            DrawCommand::new(Primitive::Circle(Point::new(100, 70), 110), 0xFF000080),
+        ]
+    }
+
+    #[test]
+    fn tiled_render_matches_single_threaded() {
+        let (width, height, background) = (200, 143, 0xFFFFFFFF);
+        let commands = commands();
+        let single = render_single_threaded(&commands, width, height, background);
+
+        for tile_size in [7, 64, 500] {
+            for thread_count in [1, 3, 8] {
+                let tiled = render_tiled(
+                    &commands,
+                    width,
+                    height,
+                    background,
+                    tile_size,
+                    thread_count,
+                );
+                assert!(
+                    single.pixels == tiled.pixels,
+                    "tile size {} with {} threads differs",
+                    tile_size,
+                    thread_count
+                );
+            }
+        }
+    }
+}
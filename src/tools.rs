@@ -0,0 +1,191 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::camera::Camera;
+use crate::graphics::{self, CubicBezierCurve, Line, Rectangle};
+use crate::hud;
+use crate::scene::{Geometry, Shape};
+
+const FREEHAND_TOLERANCE: f32 = 2.0;
+const PREVIEW_COLOR: u32 = 0xFF808080;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tool {
+    Select,
+    Fill,
+    Line,
+    Rectangle,
+    Circle,
+    QuadraticBezier,
+    CubicBezier,
+    Freehand,
+    Text,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Select => "select",
+            Tool::Fill => "fill",
+            Tool::Line => "line",
+            Tool::Rectangle => "rectangle",
+            Tool::Circle => "circle",
+            Tool::QuadraticBezier => "quadratic bezier",
+            Tool::CubicBezier => "cubic bezier",
+            Tool::Freehand => "freehand",
+            Tool::Text => "text",
+        }
+    }
+
+    // Clicks needed to finish a shape. Circles take the center and a point
+    // on the rim, freehand strokes are dragged instead of clicked.
+    fn points_needed(&self) -> usize {
+        match self {
+            Tool::Select | Tool::Fill | Tool::Freehand | Tool::Text => 0,
+            Tool::Line | Tool::Rectangle | Tool::Circle => 2,
+            Tool::QuadraticBezier => 3,
+            Tool::CubicBezier => 4,
+        }
+    }
+}
+
+fn geometry(tool: Tool, points: &[Point]) -> Option<Geometry> {
+    match (tool, points) {
+        (Tool::Line, [a, b]) => Some(Geometry::Line(Line::new(vec![*a, *b]))),
+        (Tool::Rectangle, [a, b]) => Some(Geometry::Rectangle(Rectangle::new(vec![*a, *b]))),
+        (Tool::Circle, [center, rim]) => {
+            let dx = (rim.x - center.x) as f32;
+            let dy = (rim.y - center.y) as f32;
+            Some(Geometry::Circle {
+                center: *center,
+                radius: (dx * dx + dy * dy).sqrt().round() as i32,
+            })
+        }
+        (Tool::QuadraticBezier, [a, b, c]) => Some(Geometry::QuadraticBezier([*a, *b, *c])),
+        (Tool::CubicBezier, [..]) => {
+            let mut curve = CubicBezierCurve::new(Vec::new());
+            for point in points {
+                curve.add_point(*point);
+            }
+
+            if curve.can_receive_points() {
+                None
+            } else {
+                Some(Geometry::CubicBezier(curve))
+            }
+        }
+        _ => None,
+    }
+}
+
+// Collects control points from the mouse for the selected tool and hands
+// back finished shapes for the scene.
+pub struct ToolState {
+    pub tool: Tool,
+    pub color: u32,
+    pub points: Vec<Point>,
+    pub stroke: Option<Vec<Point>>,
+    pub cursor: Point,
+}
+
+impl ToolState {
+    pub fn new() -> Self {
+        ToolState {
+            tool: Tool::Fill,
+            color: 0xFF000000,
+            points: Vec::new(),
+            stroke: None,
+            cursor: Point::new(0, 0),
+        }
+    }
+
+    pub fn select(&mut self, tool: Tool) {
+        self.cancel();
+        self.tool = tool;
+        hud::status(format!("Tool: {}", tool.name()));
+    }
+
+    pub fn cancel(&mut self) {
+        self.points.clear();
+        self.stroke = None;
+    }
+
+    pub fn mouse_down(&mut self, point: Point) -> Option<Shape> {
+        self.cursor = point;
+
+        match self.tool {
+            Tool::Select | Tool::Fill | Tool::Text => None,
+            Tool::Freehand => {
+                self.stroke = Some(vec![point]);
+                None
+            }
+            tool => {
+                self.points.push(point);
+                if self.points.len() < tool.points_needed() {
+                    return None;
+                }
+
+                let points: Vec<Point> = self.points.drain(..).collect();
+                geometry(tool, &points).map(|geometry| Shape::new(geometry, self.color))
+            }
+        }
+    }
+
+    pub fn mouse_move(&mut self, point: Point) {
+        self.cursor = point;
+
+        if let Some(stroke) = self.stroke.as_mut() {
+            stroke.push(point);
+        }
+    }
+
+    pub fn mouse_up(&mut self, point: Point) -> Option<Shape> {
+        self.cursor = point;
+
+        let mut samples = self.stroke.take()?;
+        samples.push(point);
+
+        let shape = Shape::from_stroke(&samples, FREEHAND_TOLERANCE, self.color);
+        match &shape {
+            Some(shape) => hud::status(format!(
+                "Fitted {} samples into {} segments",
+                samples.len(),
+                shape.primitives().len()
+            )),
+            None => hud::status(String::from("Stroke too short")),
+        }
+        shape
+    }
+
+    // Markers on the clicked control points and the shape as it would be
+    // if the next click landed under the cursor.
+    pub fn draw_preview(&self, camera: &Camera, canvas: &mut Canvas<Window>) {
+        if let Some(stroke) = &self.stroke {
+            let [b, g, r, a] = PREVIEW_COLOR.to_le_bytes();
+            canvas.set_draw_color(Color::RGBA(r, g, b, a));
+            for pair in stroke.windows(2) {
+                graphics::draw_line(camera.to_screen(pair[0]), camera.to_screen(pair[1]), canvas);
+            }
+            return;
+        }
+
+        if self.points.is_empty() {
+            return;
+        }
+
+        let mut points = self.points.clone();
+        while points.len() < self.tool.points_needed() {
+            points.push(self.cursor);
+        }
+
+        if let Some(geometry) = geometry(self.tool, &points) {
+            camera.shape(&Shape::new(geometry, PREVIEW_COLOR)).draw(canvas);
+        }
+
+        for point in self.points.iter() {
+            graphics::draw_target(camera.to_screen(*point), canvas);
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use sdl2::rect::Point;
+
+use crate::graphics::{CubicBezierCurve, Line};
+use crate::hud;
+use crate::numbers::{Number, DEFAULT_NUM_HEIGHT};
+
+// Vertices where the outline turns by more than this become sharp corners,
+// the rest get smooth Catmull-Rom style tangents.
+const CORNER_ANGLE: f32 = 55.0 * 0.017453293;
+
+// Contours enclosing less than this many pixels are treated as scan noise.
+const MIN_CONTOUR_AREA: f32 = 6.0;
+
+// Loads a bitmap and turns its dark pixels into a glyph. The ink's bounding
+// box is scaled to the default digit height, so traced glyphs line up with
+// the built in ones.
+pub fn trace_image(
+    path: &str,
+    number: char,
+    threshold: u8,
+    tolerance: f32,
+) -> Result<Number, image::ImageError> {
+    let image = image::open(path)?.to_rgba8();
+    let (width, height) = image.dimensions();
+
+    let inside: Vec<bool> = image
+        .pixels()
+        .map(|p| {
+            let [r, g, b, a] = p.0;
+            let luminance = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+            a > 127 && luminance < threshold as f32
+        })
+        .collect();
+
+    Ok(trace_pixels(&inside, width, height, number, tolerance))
+}
+
+pub fn trace_pixels(inside: &[bool], width: u32, height: u32, number: char, tolerance: f32) -> Number {
+    let contours: Vec<Vec<(f32, f32)>> = trace_contours(inside, width, height)
+        .into_iter()
+        .filter(|contour| polygon_area(contour).abs() >= MIN_CONTOUR_AREA)
+        .map(|contour| simplify_closed(&contour, tolerance))
+        .filter(|contour| contour.len() >= 3)
+        .collect();
+
+    let all_points = contours.iter().flatten();
+    let min_x = all_points.clone().map(|p| p.0).fold(f32::MAX, f32::min);
+    let max_x = all_points.clone().map(|p| p.0).fold(f32::MIN, f32::max);
+    let min_y = all_points.clone().map(|p| p.1).fold(f32::MAX, f32::min);
+    let max_y = all_points.map(|p| p.1).fold(f32::MIN, f32::max);
+
+    if contours.is_empty() || max_y <= min_y {
+        hud::status(format!("Nothing to trace for '{}'", number));
+        return Number::new(number, 0, 0, 0, 0, Point::new(0, 0), None, None);
+    }
+
+    let scale = DEFAULT_NUM_HEIGHT as f32 / (max_y - min_y);
+    let to_glyph = |p: (f32, f32)| ((p.0 - min_x) * scale, (p.1 - min_y) * scale);
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut bezier_curves: Vec<CubicBezierCurve> = Vec::new();
+
+    for contour in contours.iter() {
+        let glyph: Vec<(f32, f32)> = contour.iter().map(|p| to_glyph(*p)).collect();
+        let (contour_lines, contour_curves) = fit_closed(&glyph);
+        lines.extend(contour_lines);
+        bezier_curves.extend(contour_curves);
+    }
+
+    let w = ((max_x - min_x) * scale).round() as i32;
+    let h = DEFAULT_NUM_HEIGHT;
+
+    Number::new(
+        number,
+        w,
+        h,
+        0,
+        0,
+        Point::new(w / 2, h / 2),
+        Some(lines),
+        Some(bezier_curves),
+    )
+}
+
+// Follows the cracks between inside and outside pixels. Every boundary edge
+// keeps the inside on its right, so edges chain into closed loops of pixel
+// corner coordinates.
+pub fn trace_contours(inside: &[bool], width: u32, height: u32) -> Vec<Vec<(f32, f32)>> {
+    let is_inside = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && x < width as i32
+            && y < height as i32
+            && inside[(y as u32 * width + x as u32) as usize]
+    };
+
+    let mut edges: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+    let mut starts: Vec<(i32, i32)> = Vec::new();
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            if !is_inside(x, y) {
+                continue;
+            }
+
+            let mut add = |from: (i32, i32), to: (i32, i32)| {
+                edges.entry(from).or_insert_with(Vec::new).push(to);
+                starts.push(from);
+            };
+
+            if !is_inside(x, y - 1) {
+                add((x, y), (x + 1, y));
+            }
+            if !is_inside(x + 1, y) {
+                add((x + 1, y), (x + 1, y + 1));
+            }
+            if !is_inside(x, y + 1) {
+                add((x + 1, y + 1), (x, y + 1));
+            }
+            if !is_inside(x - 1, y) {
+                add((x, y + 1), (x, y));
+            }
+        }
+    }
+
+    let mut contours = Vec::new();
+
+    for start in starts {
+        if edges.get(&start).map_or(true, |next| next.is_empty()) {
+            continue;
+        }
+
+        let mut contour = vec![start];
+        let mut current = start;
+
+        while let Some(next) = edges.get_mut(&current).and_then(|next| next.pop()) {
+            if next == start {
+                break;
+            }
+            contour.push(next);
+            current = next;
+        }
+
+        contours.push(remove_collinear(&contour));
+    }
+
+    contours
+}
+
+fn remove_collinear(contour: &[(i32, i32)]) -> Vec<(f32, f32)> {
+    let n = contour.len();
+
+    (0..n)
+        .filter(|&i| {
+            let prev = contour[(i + n - 1) % n];
+            let current = contour[i];
+            let next = contour[(i + 1) % n];
+            (current.0 - prev.0) * (next.1 - current.1) != (current.1 - prev.1) * (next.0 - current.0)
+        })
+        .map(|i| (contour[i].0 as f32, contour[i].1 as f32))
+        .collect()
+}
+
+pub fn polygon_area(points: &[(f32, f32)]) -> f32 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+fn distance_to_line(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let dx = b.0 - a.0;
+    let dy = b.1 - a.1;
+    let length = (dx * dx + dy * dy).sqrt();
+
+    if length == 0.0 {
+        return ((p.0 - a.0).powi(2) + (p.1 - a.1).powi(2)).sqrt();
+    }
+
+    ((p.0 - a.0) * dy - (p.1 - a.1) * dx).abs() / length
+}
+
+// Ramer-Douglas-Peucker on an open polyline, both ends are kept.
+pub fn simplify(points: &[(f32, f32)], tolerance: f32) -> Vec<(f32, f32)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let (index, distance) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, distance_to_line(*p, first, last)))
+        .fold((0, 0.0), |best, current| if current.1 > best.1 { current } else { best });
+
+    if distance <= tolerance {
+        return vec![first, last];
+    }
+
+    let mut result = simplify(&points[..=index], tolerance);
+    result.pop();
+    result.extend(simplify(&points[index..], tolerance));
+    result
+}
+
+// Splits the loop at the point farthest from its first point and simplifies
+// both halves, so the result does not depend on where tracing started.
+pub fn simplify_closed(points: &[(f32, f32)], tolerance: f32) -> Vec<(f32, f32)> {
+    if points.len() < 4 {
+        return points.to_vec();
+    }
+
+    let first = points[0];
+    let farthest = (1..points.len())
+        .max_by(|&a, &b| {
+            let da = (points[a].0 - first.0).powi(2) + (points[a].1 - first.1).powi(2);
+            let db = (points[b].0 - first.0).powi(2) + (points[b].1 - first.1).powi(2);
+            da.partial_cmp(&db).unwrap()
+        })
+        .unwrap();
+
+    let mut second_half = points[farthest..].to_vec();
+    second_half.push(first);
+
+    let mut result = simplify(&points[..=farthest], tolerance);
+    result.pop();
+    result.extend(simplify(&second_half, tolerance));
+    result.pop();
+    result
+}
+
+fn to_point(p: (f32, f32)) -> Point {
+    Point::new(p.0.round() as i32, p.1.round() as i32)
+}
+
+fn is_corner(prev: (f32, f32), current: (f32, f32), next: (f32, f32)) -> bool {
+    let a = (current.0 - prev.0).atan2(current.1 - prev.1);
+    let b = (next.0 - current.0).atan2(next.1 - current.1);
+    let turn = (b - a).abs();
+    let turn = turn.min(2.0 * std::f32::consts::PI - turn);
+    turn > CORNER_ANGLE
+}
+
+// Straight runs between two corners become lines, everything else a cubic
+// whose tangents come from the neighbouring vertices.
+fn fit_closed(points: &[(f32, f32)]) -> (Vec<Line>, Vec<CubicBezierCurve>) {
+    let n = points.len();
+    let at = |i: usize| points[i % n];
+    let corners: Vec<bool> = (0..n)
+        .map(|i| is_corner(at(i + n - 1), at(i), at(i + 1)))
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut bezier_curves = Vec::new();
+
+    for i in 0..n {
+        let (a, b) = (at(i), at(i + 1));
+        let (a_corner, b_corner) = (corners[i], corners[(i + 1) % n]);
+
+        if a_corner && b_corner {
+            lines.push(Line::new(vec![to_point(a), to_point(b)]));
+            continue;
+        }
+
+        let c_1 = if a_corner {
+            (a.0 + (b.0 - a.0) / 3.0, a.1 + (b.1 - a.1) / 3.0)
+        } else {
+            let prev = at(i + n - 1);
+            (a.0 + (b.0 - prev.0) / 6.0, a.1 + (b.1 - prev.1) / 6.0)
+        };
+
+        let c_2 = if b_corner {
+            (b.0 - (b.0 - a.0) / 3.0, b.1 - (b.1 - a.1) / 3.0)
+        } else {
+            let next = at(i + 2);
+            (b.0 - (next.0 - a.0) / 6.0, b.1 - (next.1 - a.1) / 6.0)
+        };
+
+        bezier_curves.push(CubicBezierCurve::new(vec![
+            to_point(a),
+            to_point(c_1),
+            to_point(c_2),
+            to_point(b),
+        ]));
+    }
+
+    (lines, bezier_curves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 24;
+
+    // Pixels inside the square from min to max, both inclusive, minus the
+    // hole if there is one.
+    fn square(min: u32, max: u32, hole: Option<(u32, u32)>) -> Vec<bool> {
+        let within = |v: u32, (a, b): (u32, u32)| v >= a && v <= b;
+        (0..SIZE * SIZE)
+            .map(|i| {
+                let (x, y) = (i % SIZE, i / SIZE);
+                let in_hole = hole.map_or(false, |hole| within(x, hole) && within(y, hole));
+                within(x, (min, max)) && within(y, (min, max)) && !in_hole
+            })
+            .collect()
+    }
+
+    #[test]
+    fn filled_square_is_one_four_corner_contour() {
+        let contours = trace_contours(&square(4, 13, None), SIZE, SIZE);
+        assert_eq!(contours.len(), 1);
+
+        let contour = &contours[0];
+        assert_eq!(contour.len(), 4);
+        assert_eq!(polygon_area(contour).abs(), 100.0);
+        for corner in [(4.0, 4.0), (14.0, 4.0), (14.0, 14.0), (4.0, 14.0)] {
+            assert!(contour.contains(&corner), "{:?}", contour);
+        }
+    }
+
+    #[test]
+    fn ring_has_two_contours_wound_opposite_ways() {
+        let contours = trace_contours(&square(2, 21, Some((8, 15))), SIZE, SIZE);
+        assert_eq!(contours.len(), 2);
+
+        let mut areas: Vec<f32> = contours.iter().map(|c| polygon_area(c)).collect();
+        areas.sort_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap());
+        assert_eq!(areas[0].abs(), 64.0);
+        assert_eq!(areas[1].abs(), 400.0);
+        assert!(areas[0].signum() != areas[1].signum());
+    }
+
+    #[test]
+    fn traced_glyph_is_scaled_to_digit_height() {
+        let glyph = trace_pixels(&square(4, 13, None), SIZE, SIZE, 'A', 1.0);
+        assert_eq!(glyph.number, 'A');
+        assert_eq!((glyph.w, glyph.h), (DEFAULT_NUM_HEIGHT, DEFAULT_NUM_HEIGHT));
+        assert_eq!(glyph.lines.len() + glyph.bezier_curves.len(), 4);
+
+        let empty = trace_pixels(&vec![false; (SIZE * SIZE) as usize], SIZE, SIZE, 'B', 1.0);
+        assert!(empty.lines.is_empty() && empty.bezier_curves.is_empty());
+    }
+}
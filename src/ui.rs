@@ -0,0 +1,585 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::graphics::{self, Rectangle};
+use crate::numbers::{self, DEFAULT_NUM_HEIGHT, DEFAULT_NUM_WIDTH};
+
+const FACE: Color = Color::RGB(235, 235, 235);
+const HOT: Color = Color::RGB(215, 225, 245);
+const PRESSED: Color = Color::RGB(180, 195, 230);
+const BORDER: Color = Color::RGB(90, 90, 90);
+const ACCENT: Color = Color::RGB(40, 90, 200);
+
+fn set_color(canvas: &mut Canvas<Window>, color: Color) {
+    canvas.set_draw_color(color);
+}
+
+fn fill(canvas: &mut Canvas<Window>, rect: Rect, color: Color) {
+    set_color(canvas, color);
+    canvas.fill_rect(rect).expect("Filling widget failed");
+}
+
+fn outline(canvas: &mut Canvas<Window>, rect: Rect, color: Color) {
+    set_color(canvas, color);
+    Rectangle::new(vec![
+        rect.top_left(),
+        Point::new(rect.right() - 1, rect.bottom() - 1),
+    ])
+    .draw(canvas);
+}
+
+// Text drawn with the series' own glyphs, height pixels tall. Anything
+// the font has no glyph for is skipped.
+pub fn draw_number(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, height: i32) {
+    let scale = height as f32 / DEFAULT_NUM_HEIGHT as f32;
+    let advance = (DEFAULT_NUM_WIDTH as f32 * scale).round() as i32;
+
+    set_color(canvas, Color::RGB(0, 0, 0));
+    let mut pen = x;
+    for c in text.chars().filter(|c| numbers::has_glyph(*c)) {
+        if let Some(mut glyph) = numbers::create_digit(c, 0, 0) {
+            graphics::scale_number(&mut glyph, scale, Point::new(0, 0));
+            graphics::translate_number(&mut glyph, pen, y);
+            glyph.draw(canvas, false);
+        }
+        pen += advance;
+    }
+}
+
+pub fn number_width(text: &str, height: i32) -> i32 {
+    let advance = DEFAULT_NUM_WIDTH as f32 * height as f32 / DEFAULT_NUM_HEIGHT as f32;
+    (text.chars().filter(|c| numbers::has_glyph(*c)).count() as f32 * advance).round() as i32
+}
+
+// The value at pixel x on a slider track, clamped to its ends.
+fn slider_value(rect: Rect, x: i32, min: f32, max: f32) -> f32 {
+    let t = (x - rect.x()) as f32 / rect.width().max(1) as f32;
+    min + t.clamp(0.0, 1.0) * (max - min)
+}
+
+// Immediate mode widgets: every frame the caller asks for each widget with
+// its current value and gets told whether the user changed it. Input from
+// the event loop is collected in handle_event and dropped in end_frame.
+pub struct Ui {
+    pub mouse: Point,
+    mouse_held: bool,
+    pressed: bool,
+    released: bool,
+    // Widget the mouse went down on, it owns the mouse until release.
+    active: Option<&'static str>,
+    // Text field receiving keyboard input.
+    pub focus: Option<&'static str>,
+    // Text field that lost the focus this frame.
+    blurred: Option<&'static str>,
+    typed: String,
+    keys: Vec<Keycode>,
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        Ui {
+            mouse: Point::new(0, 0),
+            mouse_held: false,
+            pressed: false,
+            released: false,
+            active: None,
+            focus: None,
+            blurred: None,
+            typed: String::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    // Returns true if the event was meant for the widgets in area and the
+    // rest of the app should not see it.
+    pub fn handle_event(&mut self, event: &Event, area: Rect) -> bool {
+        match *event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                self.mouse = Point::new(x, y);
+                if area.contains_point(self.mouse) {
+                    self.pressed = true;
+                    self.mouse_held = true;
+                    true
+                } else {
+                    self.set_focus(None);
+                    false
+                }
+            }
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } if self.mouse_held => {
+                self.mouse = Point::new(x, y);
+                self.mouse_held = false;
+                self.released = true;
+                true
+            }
+            Event::MouseMotion { x, y, .. } => {
+                self.mouse = Point::new(x, y);
+                self.mouse_held
+            }
+            Event::TextInput { ref text, .. } if self.focus.is_some() => {
+                self.typed.push_str(text);
+                true
+            }
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } if self.focus.is_some() => {
+                self.keys.push(keycode);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.pressed = false;
+        self.released = false;
+        self.blurred = None;
+        self.typed.clear();
+        self.keys.clear();
+        if !self.mouse_held {
+            self.active = None;
+        }
+    }
+
+    // True while any widget is being dragged or held.
+    pub fn is_busy(&self) -> bool {
+        self.active.is_some()
+    }
+
+    fn set_focus(&mut self, focus: Option<&'static str>) {
+        if self.focus != focus {
+            self.blurred = self.focus;
+            self.focus = focus;
+        }
+    }
+
+    // Starts or continues an interaction on rect, true while id owns the mouse.
+    fn interact(&mut self, id: &'static str, rect: Rect) -> bool {
+        if self.pressed && self.active.is_none() && rect.contains_point(self.mouse) {
+            self.active = Some(id);
+        }
+        self.active == Some(id)
+    }
+
+    fn face(&self, id: &'static str, rect: Rect) -> Color {
+        if self.active == Some(id) {
+            PRESSED
+        } else if rect.contains_point(self.mouse) {
+            HOT
+        } else {
+            FACE
+        }
+    }
+
+    // Clicked when the mouse is released over the button it went down on.
+    fn click(&mut self, id: &'static str, rect: Rect) -> bool {
+        self.interact(id, rect) && self.released && rect.contains_point(self.mouse)
+    }
+
+    // Anything drawn on the button is up to the caller.
+    pub fn button(&mut self, canvas: &mut Canvas<Window>, id: &'static str, rect: Rect) -> bool {
+        let clicked = self.click(id, rect);
+        fill(canvas, rect, self.face(id, rect));
+        outline(canvas, rect, BORDER);
+
+        clicked
+    }
+
+    pub fn checkbox(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        id: &'static str,
+        rect: Rect,
+        value: &mut bool,
+    ) -> bool {
+        let clicked = self.button(canvas, id, rect);
+        if clicked {
+            *value = !*value;
+        }
+
+        if *value {
+            set_color(canvas, ACCENT);
+            let inner = Rect::new(
+                rect.x() + 3,
+                rect.y() + 3,
+                rect.width() - 6,
+                rect.height() - 6,
+            );
+            graphics::draw_line(inner.top_left(), inner.bottom_right(), canvas);
+            graphics::draw_line(inner.bottom_left(), inner.top_right(), canvas);
+        }
+
+        clicked
+    }
+
+    // Dragging anywhere on the track sets the value, true if it changed.
+    pub fn slider(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        id: &'static str,
+        rect: Rect,
+        min: f32,
+        max: f32,
+        value: &mut f32,
+    ) -> bool {
+        let changed = self.drag_slider(id, rect, min, max, value);
+
+        let middle = rect.y() + rect.height() as i32 / 2;
+        set_color(canvas, BORDER);
+        graphics::draw_line(
+            Point::new(rect.x(), middle),
+            Point::new(rect.right() - 1, middle),
+            canvas,
+        );
+
+        let t = ((*value - min) / (max - min)).clamp(0.0, 1.0);
+        let knob_x = rect.x() + (t * rect.width() as f32) as i32;
+        let knob = Rect::new(knob_x - 4, rect.y(), 8, rect.height());
+        fill(canvas, knob, self.face(id, knob));
+        outline(canvas, knob, BORDER);
+
+        changed
+    }
+
+    fn drag_slider(
+        &mut self,
+        id: &'static str,
+        rect: Rect,
+        min: f32,
+        max: f32,
+        value: &mut f32,
+    ) -> bool {
+        if self.interact(id, rect) && self.mouse_held {
+            let new_value = slider_value(rect, self.mouse.x, min, max);
+            if new_value != *value {
+                *value = new_value;
+                return true;
+            }
+        }
+        false
+    }
+
+    // Edits text in place, typed characters go through accept first. True
+    // when editing is finished, by Return or by focusing something else.
+    pub fn text_field(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        id: &'static str,
+        rect: Rect,
+        text: &mut String,
+        accept: fn(char) -> bool,
+    ) -> bool {
+        let finished = self.edit_text(id, rect, text, accept);
+
+        fill(canvas, rect, Color::RGB(255, 255, 255));
+        outline(
+            canvas,
+            rect,
+            if self.focus == Some(id) {
+                ACCENT
+            } else {
+                BORDER
+            },
+        );
+
+        let height = rect.height() as i32 - 8;
+        draw_number(canvas, text, rect.x() + 4, rect.y() + 4, height);
+
+        if self.focus == Some(id) {
+            let caret = rect.x() + 5 + number_width(text, height);
+            set_color(canvas, ACCENT);
+            graphics::draw_line(
+                Point::new(caret, rect.y() + 3),
+                Point::new(caret, rect.bottom() - 4),
+                canvas,
+            );
+        }
+
+        finished
+    }
+
+    fn edit_text(
+        &mut self,
+        id: &'static str,
+        rect: Rect,
+        text: &mut String,
+        accept: fn(char) -> bool,
+    ) -> bool {
+        if self.interact(id, rect) && self.pressed {
+            self.set_focus(Some(id));
+        }
+
+        // A click anywhere else in the panel also ends editing.
+        if self.pressed && self.focus == Some(id) && !rect.contains_point(self.mouse) {
+            self.set_focus(None);
+        }
+
+        let mut finished = self.blurred == Some(id);
+
+        if self.focus == Some(id) {
+            for c in self.typed.chars() {
+                if accept(c) {
+                    text.push(c);
+                }
+            }
+            for keycode in self.keys.iter() {
+                match keycode {
+                    Keycode::Backspace => {
+                        text.pop();
+                    }
+                    Keycode::Return | Keycode::KpEnter | Keycode::Escape => finished = true,
+                    _ => {}
+                }
+            }
+        }
+        if finished && self.focus == Some(id) {
+            self.focus = None;
+        }
+
+        finished
+    }
+
+    pub fn color_swatch(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        id: &'static str,
+        rect: Rect,
+        color: u32,
+        selected: bool,
+    ) -> bool {
+        let clicked = self.button(canvas, id, rect);
+
+        let [b, g, r, _] = color.to_le_bytes();
+        let inner = Rect::new(
+            rect.x() + 2,
+            rect.y() + 2,
+            rect.width() - 4,
+            rect.height() - 4,
+        );
+        fill(canvas, inner, Color::RGB(r, g, b));
+        if selected {
+            outline(canvas, rect, ACCENT);
+            outline(
+                canvas,
+                Rect::new(
+                    rect.x() - 1,
+                    rect.y() - 1,
+                    rect.width() + 2,
+                    rect.height() + 2,
+                ),
+                ACCENT,
+            );
+        }
+
+        clicked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+    use sdl2::mouse::MouseState;
+
+    // The panel, and a text field on it.
+    fn area() -> Rect {
+        Rect::new(0, 0, 200, 100)
+    }
+
+    fn field() -> Rect {
+        Rect::new(10, 10, 80, 20)
+    }
+
+    fn press(x: i32, y: i32) -> Event {
+        Event::MouseButtonDown {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x,
+            y,
+        }
+    }
+
+    fn release(x: i32, y: i32) -> Event {
+        Event::MouseButtonUp {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x,
+            y,
+        }
+    }
+
+    fn motion(x: i32, y: i32) -> Event {
+        Event::MouseMotion {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mousestate: MouseState::from_sdl_state(0),
+            x,
+            y,
+            xrel: 0,
+            yrel: 0,
+        }
+    }
+
+    fn text(text: &str) -> Event {
+        Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: String::from(text),
+        }
+    }
+
+    fn key(keycode: Keycode) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn digits(c: char) -> bool {
+        c.is_ascii_digit()
+    }
+
+    #[test]
+    fn slider_value_is_clamped_to_the_track() {
+        let track = Rect::new(10, 0, 100, 8);
+        assert_eq!(slider_value(track, 10, 0.0, 1.0), 0.0);
+        assert_eq!(slider_value(track, 60, 0.0, 1.0), 0.5);
+        assert_eq!(slider_value(track, 60, 2.0, 4.0), 3.0);
+        assert_eq!(slider_value(track, -40, 0.0, 1.0), 0.0);
+        assert_eq!(slider_value(track, 500, 0.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn dragging_past_the_end_of_a_slider_stops_at_max() {
+        let track = Rect::new(10, 40, 100, 8);
+        let mut ui = Ui::new();
+        let mut value = 0.0;
+
+        ui.handle_event(&press(35, 44), area());
+        assert!(ui.drag_slider("opacity", track, 0.0, 1.0, &mut value));
+        assert_eq!(value, 0.25);
+        ui.end_frame();
+
+        // The slider keeps the mouse when it leaves the track and the panel.
+        assert!(ui.handle_event(&motion(900, 300), area()));
+        assert!(ui.drag_slider("opacity", track, 0.0, 1.0, &mut value));
+        assert_eq!(value, 1.0);
+        assert!(!ui.drag_slider("opacity", track, 0.0, 1.0, &mut value));
+        ui.end_frame();
+
+        ui.handle_event(&release(900, 300), area());
+        ui.end_frame();
+        ui.handle_event(&motion(20, 44), area());
+        assert!(!ui.drag_slider("opacity", track, 0.0, 1.0, &mut value));
+        assert_eq!(value, 1.0);
+    }
+
+    #[test]
+    fn text_field_takes_accepted_characters_until_return() {
+        let mut ui = Ui::new();
+        let mut value = String::from("4");
+
+        ui.handle_event(&press(20, 20), area());
+        assert!(!ui.edit_text("size", field(), &mut value, digits));
+        assert_eq!(ui.focus, Some("size"));
+        ui.end_frame();
+
+        assert!(ui.handle_event(&text("1a2"), area()));
+        assert!(!ui.edit_text("size", field(), &mut value, digits));
+        assert_eq!(value, "412");
+        ui.end_frame();
+
+        ui.handle_event(&key(Keycode::Backspace), area());
+        ui.handle_event(&key(Keycode::Return), area());
+        assert!(ui.edit_text("size", field(), &mut value, digits));
+        assert_eq!(value, "41");
+        assert_eq!(ui.focus, None);
+        ui.end_frame();
+
+        // Without the focus typing goes to the rest of the app.
+        assert!(!ui.handle_event(&text("7"), area()));
+    }
+
+    #[test]
+    fn clicking_elsewhere_finishes_editing() {
+        let mut ui = Ui::new();
+        let mut value = String::new();
+
+        ui.handle_event(&press(20, 20), area());
+        ui.edit_text("size", field(), &mut value, digits);
+        ui.handle_event(&release(20, 20), area());
+        ui.end_frame();
+
+        ui.handle_event(&press(150, 80), area());
+        assert!(ui.edit_text("size", field(), &mut value, digits));
+        assert_eq!(ui.focus, None);
+        ui.end_frame();
+
+        ui.handle_event(&press(20, 20), area());
+        ui.edit_text("size", field(), &mut value, digits);
+        ui.end_frame();
+        assert!(!ui.handle_event(&press(500, 500), area()));
+        assert!(ui.edit_text("size", field(), &mut value, digits));
+    }
+
+    #[test]
+    fn the_pressed_widget_is_active_until_release() {
+        let button = Rect::new(10, 10, 30, 20);
+        let mut ui = Ui::new();
+
+        assert!(!ui.handle_event(&motion(20, 20), area()));
+        assert_eq!(ui.face("ok", button), HOT);
+        assert_eq!(ui.face("ok", Rect::new(50, 10, 30, 20)), FACE);
+
+        assert!(!ui.handle_event(&press(500, 500), area()));
+        assert!(!ui.click("ok", button));
+        assert!(!ui.is_busy());
+        ui.end_frame();
+
+        assert!(ui.handle_event(&press(20, 20), area()));
+        assert!(!ui.click("ok", button));
+        // Another widget under the mouse can't take it over.
+        assert!(!ui.click("other", button));
+        assert!(ui.is_busy());
+        assert_eq!(ui.face("ok", button), PRESSED);
+        ui.end_frame();
+
+        assert!(ui.handle_event(&release(25, 22), area()));
+        assert!(ui.click("ok", button));
+        ui.end_frame();
+        assert!(!ui.is_busy());
+
+        // Released somewhere else is not a click.
+        ui.handle_event(&press(20, 20), area());
+        ui.click("ok", button);
+        ui.end_frame();
+        ui.handle_event(&release(100, 80), area());
+        assert!(!ui.click("ok", button));
+    }
+}